@@ -0,0 +1,64 @@
+//! Heading anchors of every generated page, written out for tooling and used
+//! to check links into other pages' sections.
+
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::entry::Entry;
+
+/// Map of the heading anchors of every page, next to the manifest.
+pub const ANCHORS_PATH: &str = ".md_to_svelte/anchors.json";
+
+/// A heading a link can point to with `#id`.
+#[derive(Serialize)]
+pub struct Anchor {
+  pub id: String,
+  pub text: String,
+  pub level: u8,
+}
+
+/// Content type name to slug to the page's anchors, in document order.
+pub type AnchorMap = BTreeMap<String, BTreeMap<String, Vec<Anchor>>>;
+
+/// Adds the anchors of one content type's pages, taken from their tables of
+/// contents.
+pub fn collect(map: &mut AnchorMap, content_type: &str, entries: &[Entry]) {
+  let pages = map.entry(content_type.to_string()).or_default();
+  for entry in entries {
+    let anchors = entry.toc.iter()
+      .map(|heading| Anchor { id: heading.id.clone(), text: heading.text.clone(), level: heading.level })
+      .collect();
+    pages.insert(entry.frontmatter.slug.clone(), anchors);
+  }
+}
+
+/// Warns about links to `<page url>#<id>` where the page is generated but
+/// has no heading with that id. Links to other URLs are not checked.
+pub fn check_links(entries: &[Entry]) {
+  let ids: BTreeMap<&str, Vec<&str>> = entries.iter()
+    .map(|entry| (entry.url.trim_end_matches('/'), entry.toc.iter().map(|heading| heading.id.as_str()).collect()))
+    .collect();
+  let link = Regex::new(r##"href="(/[^"#]*)#([^"]+)""##).unwrap();
+  for entry in entries {
+    for caps in link.captures_iter(&entry.html) {
+      let Some(targets) = ids.get(caps[1].trim_end_matches('/')) else {
+        continue;
+      };
+      if !targets.contains(&&caps[2]) {
+        warn!("[broken-anchor] {}: link to {}#{} matches no heading on that page", entry.path.display(), &caps[1], &caps[2]);
+      }
+    }
+  }
+}
+
+/// Writes the anchor map. It goes to a temporary file first, so readers
+/// never see a partial map.
+pub fn write(map: &AnchorMap) -> std::io::Result<()> {
+  let path = Path::new(ANCHORS_PATH);
+  std::fs::create_dir_all(path.parent().unwrap())?;
+  let temp = path.with_extension("json.tmp");
+  std::fs::write(&temp, serde_json::to_string_pretty(map).unwrap())?;
+  std::fs::rename(&temp, path)
+}
@@ -0,0 +1,158 @@
+//! Files built into the tool that a site can replace: the avatar shown next
+//! to bylines, the page stylesheet and the page template. Replacements are read from
+//! `assets_dir` once at startup and passed to rendering in
+//! [`RenderOptions`](crate::RenderOptions).
+
+use std::path::Path;
+
+/// Directory below `static_root` the avatar is copied to.
+pub const STATIC_DIR: &str = "md_to_svelte";
+/// Name of the page stylesheet in `assets_dir`.
+pub const PAGE_CSS_FILE: &str = "page.css";
+/// Name of the page template in `assets_dir`.
+pub const PAGE_TEMPLATE_FILE: &str = "page.svelte";
+/// The `{{name}}` placeholders of the page template, filled in per page.
+pub const PAGE_TEMPLATE_PLACEHOLDERS: [&str; 20] = [
+  "header", "imports", "kind", "id", "title", "date", "tags", "authors", "related", "toc",
+  "revisions", "comments", "computed", "content", "frontmatter", "json_ld", "head", "item_scope", "body", "style",
+];
+/// Extensions an `avatar.<ext>` in `assets_dir` may have.
+pub const AVATAR_EXTENSIONS: [&str; 6] = ["png", "svg", "jpg", "jpeg", "webp", "gif"];
+
+const DEFAULT_AVATAR: &[u8] = include_bytes!("static/avatar.png");
+const DEFAULT_PAGE_CSS: &str = include_str!("static/page.css");
+const DEFAULT_PAGE_TEMPLATE: &str = include_str!("static/page.svelte");
+
+pub struct Assets {
+  /// File name of the avatar, such as `avatar.png`.
+  pub avatar_name: String,
+  pub avatar: Vec<u8>,
+  /// Page styles, with `/* critical */` markers around the critical rules.
+  pub page_css: String,
+  /// Markup of the `+page.svelte` components, with `{{name}}` placeholders.
+  pub page_template: String,
+  /// Hash of the replacements, empty when there are none.
+  pub fingerprint: String,
+}
+
+impl Default for Assets {
+  fn default() -> Assets {
+    Assets {
+      avatar_name: "avatar.png".to_string(),
+      avatar: DEFAULT_AVATAR.to_vec(),
+      page_css: DEFAULT_PAGE_CSS.to_string(),
+      page_template: DEFAULT_PAGE_TEMPLATE.to_string(),
+      fingerprint: String::new(),
+    }
+  }
+}
+
+impl Assets {
+  /// The built-in assets with any `avatar.<ext>`, `page.css` and
+  /// `page.svelte` found in `dir` in their place. A `dir` that is set but
+  /// missing is an error, and so is a template with an unknown placeholder.
+  pub fn load(dir: Option<&Path>) -> Result<Assets, String> {
+    let mut assets = Assets::default();
+    let Some(dir) = dir else {
+      return Ok(assets);
+    };
+    if !dir.is_dir() {
+      return Err(format!("Error reading assets_dir {}: not a directory", dir.display()));
+    }
+    let read = |name: &str| {
+      std::fs::read(dir.join(name))
+        .map_err(|e| format!("Error reading asset {}: {}", dir.join(name).display(), e))
+    };
+    let mut overrides = Vec::new();
+    let avatars: Vec<String> = AVATAR_EXTENSIONS.iter()
+      .map(|extension| format!("avatar.{}", extension))
+      .filter(|name| dir.join(name).is_file())
+      .collect();
+    if avatars.len() > 1 {
+      return Err(format!("Error reading assets_dir {}: found {}, keep only one avatar", dir.display(), avatars.join(" and ")));
+    }
+    if let Some(name) = avatars.into_iter().next() {
+      assets.avatar = read(&name)?;
+      overrides.push(format!("{} {}", name, crate::cache::hash_bytes(&assets.avatar)));
+      assets.avatar_name = name;
+    }
+    let read_text = |name: &str| {
+      String::from_utf8(read(name)?).map_err(|_| format!("Error reading asset {}: not valid UTF-8", dir.join(name).display()))
+    };
+    if dir.join(PAGE_CSS_FILE).is_file() {
+      assets.page_css = read_text(PAGE_CSS_FILE)?;
+      overrides.push(format!("{} {}", PAGE_CSS_FILE, crate::cache::hash(&assets.page_css)));
+    }
+    if dir.join(PAGE_TEMPLATE_FILE).is_file() {
+      assets.page_template = read_text(PAGE_TEMPLATE_FILE)?;
+      let placeholder = regex::Regex::new(r"\{\{(\w+)\}\}").unwrap();
+      let unknown: Vec<&str> = placeholder.captures_iter(&assets.page_template)
+        .map(|caps| caps.get(1).unwrap().as_str())
+        .filter(|name| !PAGE_TEMPLATE_PLACEHOLDERS.contains(name))
+        .collect();
+      if !unknown.is_empty() {
+        return Err(format!(
+          "Error reading asset {}: unknown placeholder {{{{{}}}}}, expected one of {}",
+          dir.join(PAGE_TEMPLATE_FILE).display(),
+          unknown[0],
+          PAGE_TEMPLATE_PLACEHOLDERS.join(", ")
+        ));
+      }
+      overrides.push(format!("{} {}", PAGE_TEMPLATE_FILE, crate::cache::hash(&assets.page_template)));
+    }
+    for name in &overrides {
+      debug!("Using {} from {}", name.split(' ').next().unwrap(), dir.display());
+    }
+    if !overrides.is_empty() {
+      assets.fingerprint = crate::cache::hash(&overrides.join("\n"));
+    }
+    Ok(assets)
+  }
+
+  /// URL the avatar is served at once copied below `static_root`.
+  pub fn avatar_url(&self) -> String {
+    format!("/{}/{}", STATIC_DIR, self.avatar_name)
+  }
+
+  /// Copies the avatar below `static_root`, unless an identical copy is
+  /// already there. Avatars of other formats left by earlier builds are
+  /// removed.
+  pub fn write_avatar(&self, static_root: &Path) -> std::io::Result<()> {
+    let dir = static_root.join(STATIC_DIR);
+    for extension in AVATAR_EXTENSIONS {
+      let stale = dir.join(format!("avatar.{}", extension));
+      if stale.file_name().unwrap().to_string_lossy() != self.avatar_name && stale.is_file() {
+        std::fs::remove_file(stale)?;
+      }
+    }
+    let path = dir.join(&self.avatar_name);
+    if std::fs::read(&path).is_ok_and(|existing| existing == self.avatar) {
+      return Ok(());
+    }
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(path, &self.avatar)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn page_template_can_be_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(Assets::load(Some(dir.path())).unwrap().page_template, DEFAULT_PAGE_TEMPLATE);
+    for name in PAGE_TEMPLATE_PLACEHOLDERS {
+      assert!(DEFAULT_PAGE_TEMPLATE.contains(&format!("{{{{{}}}}}", name)), "{}", name);
+    }
+
+    std::fs::write(dir.path().join(PAGE_TEMPLATE_FILE), "{{header}}\n<h1>{{title}}</h1>\n").unwrap();
+    let assets = Assets::load(Some(dir.path())).unwrap();
+    assert_eq!(assets.page_template, "{{header}}\n<h1>{{title}}</h1>\n");
+    assert!(!assets.fingerprint.is_empty());
+
+    std::fs::write(dir.path().join(PAGE_TEMPLATE_FILE), "<h1>{{titel}}</h1>\n").unwrap();
+    let e = Assets::load(Some(dir.path())).err().unwrap();
+    assert!(e.starts_with(&format!("Error reading asset {}: unknown placeholder {{{{titel}}}}", dir.path().join(PAGE_TEMPLATE_FILE).display())), "{}", e);
+  }
+}
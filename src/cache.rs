@@ -0,0 +1,101 @@
+//! Parsed entries kept between builds, in memory while watching and on disk
+//! in the cache directory between runs.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::entry::Entry;
+
+/// Default for the `cache_dir` config key.
+pub const DEFAULT_CACHE_DIR: &str = ".cache/md_to_svelte";
+/// File inside the cache directory holding the entries.
+pub const CACHE_FILE: &str = "entries.json";
+/// Bumped whenever the cached data changes shape.
+const CACHE_VERSION: u32 = 4;
+
+/// An entry with what is needed to reuse it: the hash of the source it was
+/// parsed from, the warnings parsing reported, and the draft and published
+/// flags, which the entry itself does not serialize.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct CachedEntry {
+  pub source: String,
+  pub warnings: Vec<String>,
+  pub draft: bool,
+  pub published: bool,
+  pub entry: Entry,
+}
+
+/// Parsed entries keyed by source path. An entry is reused while its source
+/// hashes the same and the build settings match `key`.
+#[derive(Serialize, Deserialize)]
+pub struct BuildCache {
+  version: u32,
+  key: String,
+  pub(crate) entries: BTreeMap<PathBuf, CachedEntry>,
+}
+
+impl Default for BuildCache {
+  fn default() -> BuildCache {
+    BuildCache {
+      version: CACHE_VERSION,
+      key: String::new(),
+      entries: BTreeMap::new(),
+    }
+  }
+}
+
+impl BuildCache {
+  /// Reads the cache from `dir`. A missing, unreadable or outdated cache
+  /// gives an empty one.
+  pub fn load(dir: &Path) -> BuildCache {
+    let path = dir.join(CACHE_FILE);
+    let Ok(text) = std::fs::read_to_string(&path) else {
+      return BuildCache::default();
+    };
+    match serde_json::from_str::<BuildCache>(&text) {
+      Ok(cache) if cache.version == CACHE_VERSION => cache,
+      Ok(_) => {
+        debug!("Ignoring {}, it was written by another version", path.display());
+        BuildCache::default()
+      }
+      Err(e) => {
+        debug!("Ignoring {}: {}", path.display(), e);
+        BuildCache::default()
+      }
+    }
+  }
+
+  /// Writes the cache into `dir`, creating it if missing.
+  pub fn save(&self, dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(CACHE_FILE), serde_json::to_string(self).unwrap())
+  }
+
+  /// Drops every entry unless they were built with the settings `key`
+  /// stands for. Returns whether the entries were kept.
+  pub(crate) fn check_key(&mut self, key: String) -> bool {
+    if self.key == key {
+      return true;
+    }
+    self.clear();
+    self.key = key;
+    false
+  }
+
+  /// Forgets every entry so the next build parses all sources again.
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+}
+
+/// Hex SHA-256 of `text`.
+pub(crate) fn hash(text: &str) -> String {
+  hash_bytes(text.as_bytes())
+}
+
+/// Hex SHA-256 of `bytes`.
+pub(crate) fn hash_bytes(bytes: &[u8]) -> String {
+  Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
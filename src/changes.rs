@@ -0,0 +1,100 @@
+//! Source files changed since a git ref, for builds that only regenerate
+//! the pages of those files.
+
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Files below the content directories that differ between a git ref and
+/// the working tree, relative to the working directory and without `./`
+/// components.
+pub struct ChangeSet {
+  pub git_ref: String,
+  /// Files added, modified, or renamed to.
+  pub changed: BTreeSet<PathBuf>,
+  /// Files deleted, or renamed away from.
+  pub removed: BTreeSet<PathBuf>,
+}
+
+impl ChangeSet {
+  /// Asks `git diff` for the files below `dirs` changed since `git_ref`.
+  /// Untracked files count as changed.
+  pub fn since(git_ref: &str, dirs: &[&Path]) -> Result<ChangeSet, String> {
+    let text = git(&["diff", "--name-status", "-z", "-M", "--relative", git_ref, "--"])?;
+    let mut changes = ChangeSet {
+      git_ref: git_ref.to_string(),
+      changed: BTreeSet::new(),
+      removed: BTreeSet::new(),
+    };
+    let mut fields = text.split('\0').filter(|field| !field.is_empty());
+    while let Some(status) = fields.next() {
+      let path = fields.next().ok_or("Error running git diff: truncated output")?;
+      match status.chars().next() {
+        // Renames and copies are followed by the new path.
+        Some('R') | Some('C') => {
+          let new_path = fields.next().ok_or("Error running git diff: truncated output")?;
+          if status.starts_with('R') {
+            changes.removed.insert(PathBuf::from(path));
+          }
+          changes.changed.insert(PathBuf::from(new_path));
+        }
+        Some('D') => {
+          changes.removed.insert(PathBuf::from(path));
+        }
+        _ => {
+          changes.changed.insert(PathBuf::from(path));
+        }
+      }
+    }
+    for path in git(&["ls-files", "-z", "--others", "--exclude-standard"])?.split('\0').filter(|path| !path.is_empty()) {
+      changes.changed.insert(PathBuf::from(path));
+    }
+    changes.changed = changes.changed.iter().map(|path| normalize(path)).collect();
+    changes.removed = changes.removed.iter().map(|path| normalize(path)).collect();
+    let dirs: Vec<PathBuf> = dirs.iter().map(|dir| normalize(dir)).collect();
+    let below = |path: &PathBuf| dirs.iter().any(|dir| path.starts_with(dir));
+    changes.changed.retain(below);
+    changes.removed.retain(below);
+    Ok(changes)
+  }
+
+  /// Whether `path`, written as the walk of an input directory gives it,
+  /// changed.
+  pub fn is_changed(&self, path: &Path) -> bool {
+    self.changed.contains(&normalize(path))
+  }
+
+  /// Whether a file below `dir` was removed.
+  pub fn removed_below(&self, dir: &Path) -> bool {
+    let dir = normalize(dir);
+    self.removed.iter().any(|path| path.starts_with(&dir))
+  }
+
+  /// Every changed and removed file, for logs and the manifest.
+  pub fn files(&self) -> Vec<String> {
+    let mut files: Vec<String> = self.changed.iter()
+      .map(|path| path.display().to_string())
+      .chain(self.removed.iter().map(|path| format!("{} (removed)", path.display())))
+      .collect();
+    files.sort();
+    files
+  }
+}
+
+/// `path` without `.` components, as git prints paths: input directories
+/// may be configured as `./content`.
+fn normalize(path: &Path) -> PathBuf {
+  path.components().filter(|component| *component != Component::CurDir).collect()
+}
+
+/// Runs git with `args` and returns what it printed.
+fn git(args: &[&str]) -> Result<String, String> {
+  let command = format!("git {}", args[0]);
+  let output = std::process::Command::new("git")
+    .args(args)
+    .output()
+    .map_err(|e| format!("Error running {}: {}", command, e))?;
+  if !output.status.success() {
+    return Err(format!("Error running {}: {}", command, String::from_utf8_lossy(&output.stderr).trim()));
+  }
+  String::from_utf8(output.stdout).map_err(|_| format!("Error running {}: output is not valid UTF-8", command))
+}
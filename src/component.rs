@@ -0,0 +1,791 @@
+use regex::Regex;
+use serde_json::json;
+use std::collections::BTreeSet;
+
+use crate::assets::Assets;
+use crate::config::{AuthorsMode, Block, ContentMode, MetaItem, UrlStyle};
+use crate::data::AUTHORS_MODULE;
+use crate::entry::{Entry, DISPLAY_DATE_FORMAT, ISO_DATE_FORMAT};
+use crate::frontmatter::Author;
+use crate::markdown::render_snippet;
+use crate::site::ARTICLE_KIND;
+use crate::output;
+use crate::slug::{tag_leaf, tag_slug};
+use crate::social::SocialMeta;
+use crate::RenderOptions;
+
+/// Rules of the page stylesheet between `/* critical */` and
+/// `/* end critical */` style what is visible first (title, byline, heading
+/// typography) and stay inline when `css_split` moves the rest out.
+const CRITICAL_START: &str = "/* critical */";
+const CRITICAL_END: &str = "/* end critical */";
+
+/// Name of the shared stylesheet written next to the slug directories.
+pub const SHARED_STYLESHEET: &str = "md_to_svelte.css";
+/// Name of the stylesheet written into each slug directory with `css_file`.
+pub const PAGE_STYLESHEET: &str = "styles.css";
+/// Name of the body HTML written into each slug directory with
+/// `content_mode = "file"`.
+pub const CONTENT_FILE: &str = "content.html";
+
+/// The rendered body as written to [`CONTENT_FILE`]. The header is an HTML
+/// comment, so it is harmless inside the injected markup.
+pub fn content_file(entry: &Entry, options: &RenderOptions) -> String {
+  format!("{}\n{}", page_header(entry, options), entry.html)
+}
+
+/// Header of the files generated for one page, naming its source.
+fn page_header(entry: &Entry, options: &RenderOptions) -> String {
+  output::svelte_header(&options.source_comment, &entry.path.to_string_lossy().replace('\\', "/"))
+}
+
+/// The page stylesheet of `assets` as (whole, critical, rest), without the
+/// markers.
+fn split_css(assets: &Assets) -> (String, String, String) {
+  let mut whole = String::new();
+  let mut critical = String::new();
+  let mut rest = String::new();
+  let mut in_critical = false;
+  for line in assets.page_css.lines() {
+    match line.trim() {
+      CRITICAL_START => in_critical = true,
+      CRITICAL_END => in_critical = false,
+      _ => {
+        let part = if in_critical { &mut critical } else { &mut rest };
+        for part in [part, &mut whole] {
+          part.push_str(line);
+          part.push('\n');
+        }
+      }
+    }
+  }
+  (whole, critical, rest)
+}
+
+/// The non-critical rules, written once per content type when `css_split`
+/// is on. Warns about selectors styled in both halves, since their rules
+/// would then apply in a different order than in the template.
+pub fn shared_stylesheet(assets: &Assets) -> String {
+  let (_, critical, rest) = split_css(assets);
+  let critical_selectors = selectors(&critical);
+  for selector in selectors(&rest).intersection(&critical_selectors) {
+    warn!("CSS selector '{}' appears in both the critical and the shared stylesheet", selector);
+  }
+  format!("{}\n{}", output::CSS_HEADER, rest)
+}
+
+/// Selectors of every rule, prefixed with the enclosing `@media` query.
+fn selectors(css: &str) -> BTreeSet<String> {
+  let mut selectors = BTreeSet::new();
+  let mut media: Option<String> = None;
+  let mut depth = 0;
+  for line in css.lines() {
+    let line = line.trim();
+    if let Some(prelude) = line.strip_suffix('{') {
+      let prelude = prelude.trim();
+      if prelude.starts_with('@') {
+        media = Some(prelude.to_string());
+      }
+      else {
+        for selector in prelude.split(',') {
+          let selector = selector.trim();
+          selectors.insert(match &media {
+            Some(media) => format!("{} {}", media, selector),
+            None => selector.to_string(),
+          });
+        }
+      }
+      depth += 1;
+    }
+    else if line == "}" {
+      depth -= 1;
+      if depth == 0 {
+        media = None;
+      }
+    }
+  }
+  selectors
+}
+
+/// schema.org vocabulary of a page: articles are authored, projects are
+/// software with creators.
+struct Schema {
+  item_type: &'static str,
+  title_property: &'static str,
+  author_property: &'static str,
+  /// Default name of the content type in breadcrumb trails.
+  collection: &'static str,
+}
+
+const ARTICLE_SCHEMA: Schema = Schema { item_type: "Article", title_property: "headline", author_property: "author", collection: "Articles" };
+const PROJECT_SCHEMA: Schema = Schema { item_type: "SoftwareSourceCode", title_property: "name", author_property: "creator", collection: "Projects" };
+
+fn schema(options: &RenderOptions) -> &'static Schema {
+  match options.kind.as_str() {
+    ARTICLE_KIND => &ARTICLE_SCHEMA,
+    _ => &PROJECT_SCHEMA,
+  }
+}
+
+/// Lays out the page markup in the order configured for the content type.
+fn render_body(entry: &Entry, options: &RenderOptions) -> String {
+  let content_config = &options.content;
+  let microdata = content_config.microdata().then(|| schema(options));
+  let avatar_url = options.assets.avatar_url();
+  let mut body = String::new();
+  for block in content_config.layout() {
+    match block {
+      Block::Title => {
+        let itemprop = match microdata {
+          Some(schema) => format!(" itemprop=\"{}\"", schema.title_property),
+          None => String::new(),
+        };
+        let class = content_config.class("title");
+        body.push_str(&format!("    <h1 class=\"{class}\"{itemprop}>{{title}}</h1>\n\n"));
+      }
+      // Without authors there is no byline, only the date if it is shown.
+      Block::Meta if entry.frontmatter.authors.is_empty() => {
+        if content_config.meta().contains(&MetaItem::Date) {
+          let class = content_config.class("meta");
+          body.push_str(&format!("    <div class=\"{class}\">\n      <p class=\"subtitle\">{{date}}</p>\n    </div>\n"));
+        }
+      }
+      Block::Meta => {
+        let class = content_config.class("meta");
+        body.push_str(&format!(r#"    <div class="{class}">
+      <div class="profile" style="height:48px">
+        <img src="{avatar_url}" alt="">
+        <span class="mono authors">
+"#));
+        for item in content_config.meta() {
+          match item {
+            MetaItem::Authors => match microdata {
+              Some(schema) => body.push_str(&format!(r#"          {{#each authors as author, index}}
+            <span itemprop="{}" itemscope itemtype="https://schema.org/Person">
+            {{#if author.url}}
+              <a itemprop="url" href="{{author.url}}"><span itemprop="name">{{author.name}}</span></a>
+            {{:else}}
+              <span itemprop="name">{{author.name}}</span>
+            {{/if}}
+            </span>
+            {{#if index < authors.length - 1}}<span class="ampersand">&amp;</span>{{/if}}
+          {{/each}}
+"#, schema.author_property)),
+              None => body.push_str(r#"          {#each authors as author, index}
+            {#if author.url}
+              <a href="{author.url}">{author.name}</a>
+            {:else}
+              <span>{author.name}</span>
+            {/if}
+            {#if index < authors.length - 1}<span class="ampersand">&amp;</span>{/if}
+          {/each}
+"#),
+            },
+            MetaItem::Date => body.push_str("          <p class=\"subtitle\">{date}</p>\n"),
+          }
+        }
+        body.push_str("        </span>\n      </div>\n    </div>\n");
+      }
+      Block::Rule => body.push_str("    <hr>\n\n"),
+      Block::Content => {
+        let mut attributes = String::new();
+        if content_config.data_attributes {
+          let tags: Vec<&str> = entry.tag_slugs.values().map(String::as_str).collect();
+          attributes.push_str(&format!(
+            " data-slug=\"{}\" data-tags=\"{}\"",
+            escape_attribute(&entry.frontmatter.slug),
+            escape_attribute(&tags.join(" "))
+          ));
+        }
+        if content_config.frontmatter_attribute {
+          attributes.push_str(" data-frontmatter={frontmatterJson}");
+        }
+        let class = content_config.class("content");
+        let content = match content_config.content_mode {
+          ContentMode::Html | ContentMode::File => "{@html content}".to_string(),
+          ContentMode::Markup => svelte_markup(&entry.html),
+        };
+        body.push_str(&format!("    <div class=\"{class}\"{attributes}>\n      {content}\n    </div>\n"));
+        if let Some(discussion) = render_discussion(entry, options) {
+          body.push_str(&format!("    {}\n", discussion));
+        }
+        if let Some(footer) = render_footer(entry, options) {
+          body.push_str(&format!("    <footer class=\"page-footer\">\n      {}\n    </footer>\n", footer));
+        }
+      }
+    }
+  }
+  body
+}
+
+/// The link to the entry's discussion, for content types with comments.
+fn render_discussion(entry: &Entry, options: &RenderOptions) -> Option<String> {
+  entry.comments?;
+  let url = entry.frontmatter.discussion_url.as_deref()?;
+  Some(svelte_markup(&options.content.discussion_template().replace("{url}", &escape_attribute(url))))
+}
+
+/// The configured footer with its tokens filled in, as component markup.
+fn render_footer(entry: &Entry, options: &RenderOptions) -> Option<String> {
+  let footer = options.content.footer.as_deref()?;
+  let path = entry.path.to_string_lossy().replace('\\', "/");
+  let edit_url = options.content.edit_url.as_deref().unwrap_or("").replace("{path}", &path);
+  let footer = footer
+    .replace("{slug}", &entry.frontmatter.slug)
+    .replace("{date}", &entry.date.format(ISO_DATE_FORMAT).to_string())
+    .replace("{edit_url}", &edit_url);
+  Some(svelte_markup(&render_snippet(&footer)))
+}
+
+/// The rendered HTML as component markup, so Svelte compiles it like any
+/// other template. Braces would otherwise start Svelte expressions.
+fn svelte_markup(html: &str) -> String {
+  html.trim_end().replace('{', "&#123;").replace('}', "&#125;")
+}
+
+/// Escapes a value for a double-quoted attribute. Braces would otherwise
+/// start a Svelte expression.
+fn escape_attribute(value: &str) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('"', "&quot;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('{', "&#123;")
+    .replace('}', "&#125;")
+    .replace('\n', "&#10;")
+    .replace('\r', "&#13;")
+}
+
+/// Open Graph and Twitter card tags for the page head.
+fn render_head(social: &SocialMeta, prefetch: &[&str]) -> String {
+  let mut tags = vec![
+    ("property", "og:type", social.og_type.as_str()),
+    ("property", "og:title", social.title.as_str()),
+  ];
+  if !social.description.is_empty() {
+    tags.push(("property", "og:description", social.description.as_str()));
+  }
+  if let Some(image) = &social.image {
+    tags.push(("property", "og:image", image.as_str()));
+  }
+  if let Some(author) = social.author.as_deref().filter(|_| social.og_type == "article") {
+    tags.push(("property", "article:author", author));
+  }
+  tags.push(("name", "twitter:card", social.twitter_card.as_str()));
+
+  let mut head = String::from("<svelte:head>\n");
+  for (attribute, key, value) in tags {
+    head.push_str(&format!("    <meta {}=\"{}\" content=\"{}\">\n", attribute, key, escape_attribute(value)));
+  }
+  for url in prefetch {
+    head.push_str(&format!("    <link rel=\"prefetch\" href=\"{}\">\n", escape_attribute(url)));
+  }
+  head.push_str("    {@html jsonLd}\n");
+  head.push_str("  </svelte:head>\n");
+  head
+}
+
+/// schema.org description of the page, followed by its breadcrumb trail if
+/// it has one, as ready `<script>` elements for `{@html}` in the head,
+/// since Svelte won't render a literal one there.
+fn json_ld_script(entry: &Entry, options: &RenderOptions) -> String {
+  let schema = schema(options);
+  let mut json_ld = json!({
+    "@context": "https://schema.org",
+    "@type": schema.item_type,
+    "datePublished": match &entry.datetime {
+      Some(datetime) => datetime.to_rfc3339(),
+      None => entry.date.format(ISO_DATE_FORMAT).to_string(),
+    },
+  });
+  json_ld[schema.title_property] = json!(entry.frontmatter.title);
+  if let Some(author) = entry.frontmatter.primary_author() {
+    let property = schema.author_property;
+    json_ld[property] = json!({ "@type": "Person", "name": author.name });
+    if let Some(url) = &author.url {
+      json_ld[property]["url"] = json!(url);
+    }
+  }
+  let mut element = format!("<script type=\"application/ld+json\">{}</script>", json_ld);
+  if let Some(breadcrumbs) = breadcrumb_list(entry, options) {
+    element.push_str(&format!("<script type=\"application/ld+json\">{}</script>", breadcrumbs));
+  }
+  // `<\/` keeps the element's closing tag from ending the component script.
+  format!("\n    const jsonLd = {};", serde_json::to_string(&element).unwrap().replace("</", "<\\/"))
+}
+
+/// schema.org `BreadcrumbList` leading from the content type's index over
+/// the entry's category to the entry, for content types with `breadcrumbs`.
+fn breadcrumb_list(entry: &Entry, options: &RenderOptions) -> Option<serde_json::Value> {
+  if !options.content.breadcrumbs {
+    return None;
+  }
+  let category = entry.frontmatter.category.as_deref().map(str::trim).filter(|category| !category.is_empty())?;
+  let site_url = options.site_url.as_deref()?;
+  let index_url = match options.url_style.unwrap_or_default() {
+    UrlStyle::NoSlash => options.route.clone(),
+    UrlStyle::Slash => format!("{}/", options.route),
+  };
+  let category_url = options.content.category_url.as_deref()?.replace("{category}", &tag_slug(category));
+  let label = options.content.breadcrumb_label.as_deref().unwrap_or(schema(options).collection);
+  let crumbs = [(label, index_url), (category, category_url), (entry.frontmatter.title.as_str(), entry.url.clone())];
+  let items: Vec<serde_json::Value> = crumbs.iter()
+    .enumerate()
+    .map(|(i, (name, url))| json!({
+      "@type": "ListItem",
+      "position": i + 1,
+      "name": name,
+      "item": format!("{}{}", site_url, url),
+    }))
+    .collect();
+  Some(json!({
+    "@context": "https://schema.org",
+    "@type": "BreadcrumbList",
+    "itemListElement": items,
+  }))
+}
+
+/// Task list items show their checkbox in place of the bullet.
+const TASK_LIST_STYLE: &str = r#"
+
+    .content :global(li:has(> .task-checkbox)) {
+      list-style: none;
+    }
+
+    .content :global(.task-checkbox) {
+      margin: 0 0.4em 0 -1.3em;
+      vertical-align: middle;
+    }"#;
+
+/// The file name header of fences like ```` ```rust:src/main.rs ````, joined
+/// to the code block below it.
+const CODE_FILENAME_STYLE: &str = r#"
+
+    .content :global(.code-filename) {
+      font-size: 0.85em;
+      padding: 0.3em 1em;
+      border: 1px solid rgba(0, 0, 0, 0.1);
+      border-bottom: none;
+      border-radius: 6px 6px 0 0;
+      background: rgba(0, 0, 0, 0.04);
+    }
+
+    .content :global(.code-filename + pre) {
+      margin-top: 0;
+      border-top-left-radius: 0;
+      border-top-right-radius: 0;
+    }"#;
+
+/// Code block rules scoped to the content wrapper. The markup inside comes
+/// from `{@html}`, so the selectors have to reach it through `:global`.
+fn code_style(background: &str) -> String {
+  format!(r#"
+
+    .content :global(pre[class*="language-"]) {{
+      background: {background};
+      border: 1px solid rgba(0, 0, 0, 0.1);
+      border-radius: 6px;
+      padding: 1em;
+      overflow-x: auto;
+    }}
+
+    .content :global(:not(pre) > code[class*="language-"]) {{
+      background: {background};
+      border-radius: 3px;
+      padding: 0.1em 0.3em;
+    }}"#)
+}
+
+/// The page's own rules: the template stylesheet, or its critical part with
+/// `css_split`, plus the rules that depend on the content settings.
+fn page_style(options: &RenderOptions) -> String {
+  let content_config = &options.content;
+  let (whole, critical, _) = split_css(&options.assets);
+  let mut style = if content_config.css_split { critical } else { whole };
+  style.truncate(style.trim_end().len());
+  match content_config.prism_theme.background() {
+    Some(background) if content_config.code_background => style.push_str(&code_style(background)),
+    _ => {}
+  }
+  style.push_str(CODE_FILENAME_STYLE);
+  if content_config.tasklists {
+    style.push_str(TASK_LIST_STYLE);
+  }
+  style
+}
+
+/// The page's rules as a plain stylesheet written next to the component
+/// with `css_file`. Imported CSS is not scoped by Svelte, so the `:global`
+/// wrappers that reach into `{@html}` content are dropped.
+pub fn page_stylesheet(options: &RenderOptions) -> String {
+  let global = Regex::new(r":global\(([^()]*(?:\([^()]*\)[^()]*)*)\)").unwrap();
+  format!("{}\n{}\n", output::CSS_HEADER, global.replace_all(&page_style(options), "$1"))
+}
+
+/// The date as shown on the page, as a range for entries that span one.
+/// With a `time_format`, a timestamp's time follows the date.
+fn display_date(entry: &Entry, time_format: Option<&str>) -> String {
+  let mut date = entry.date.format(DISPLAY_DATE_FORMAT).to_string();
+  if let (Some(datetime), Some(time_format)) = (&entry.datetime, time_format) {
+    date = format!("{} {}", date, datetime.format(time_format));
+  }
+  if let Some(date_end) = &entry.date_end {
+    format!("{} – {}", date, date_end.format(DISPLAY_DATE_FORMAT))
+  }
+  else if entry.frontmatter.ongoing {
+    format!("{} – present", date)
+  }
+  else {
+    date
+  }
+}
+
+pub fn generate_svelte_component(entry: &Entry, options: &RenderOptions) -> String {
+  let content_config = &options.content;
+  let frontmatter = &entry.frontmatter;
+  let tags: Vec<&str> = if content_config.full_tag_paths {
+    frontmatter.tags.iter().map(String::as_str).collect()
+  }
+  else {
+    frontmatter.tags.iter().map(|tag| tag_leaf(tag)).collect()
+  };
+  let tags_json = serde_json::to_string(&tags).unwrap();
+  let authors_json = match options.authors {
+    AuthorsMode::Inline => serde_json::to_string(&frontmatter.authors).unwrap(),
+    AuthorsMode::Normalized => {
+      let ids: Vec<String> = frontmatter.authors.iter().map(Author::id).collect();
+      format!("{}.map((id) => authorIndex[id])", serde_json::to_string(&ids).unwrap())
+    }
+  };
+  let related_json = serde_json::to_string(&entry.related).unwrap();
+  let toc_json = serde_json::to_string(&entry.toc).unwrap();
+  let revisions_json = serde_json::to_string(&entry.revisions).unwrap();
+  let formatted_date = display_date(entry, content_config.time_format.as_deref());
+
+  let content_json = json!(entry.html);
+  let body = render_body(entry, options);
+  let content_script = match content_config.content_mode {
+    ContentMode::Html => format!("\n    let content = {};", content_json),
+    ContentMode::Markup | ContentMode::File => String::new(),
+  };
+  let header = page_header(entry, options);
+  let kind = output::js_string(&options.kind);
+  let id_script = if options.ids {
+    let id = entry.id.as_deref().map_or("null".to_string(), output::js_string);
+    format!("\n    export const {}Id = {};", options.kind, id)
+  }
+  else {
+    String::new()
+  };
+  let frontmatter_script = if content_config.frontmatter_attribute {
+    let frontmatter_json = serde_json::to_string(frontmatter).unwrap();
+    // `<\/` keeps a `</script>` inside a value from closing the script block.
+    let literal = serde_json::to_string(&frontmatter_json).unwrap().replace("</", "<\\/");
+    format!("\n    const frontmatterJson = {};", literal)
+  }
+  else {
+    String::new()
+  };
+
+  let comments_script = match entry.comments {
+    Some(comments) => format!(
+      "\n    export const comments = {};\n    export const discussion_url = {};",
+      comments,
+      serde_json::to_string(&frontmatter.discussion_url).unwrap()
+    ),
+    None => String::new(),
+  };
+  let computed_script: String = entry.computed.iter()
+    .map(|(name, value)| format!("\n    export const {} = {};", name, value))
+    .collect();
+  let item_scope = if content_config.microdata() {
+    format!(" itemscope itemtype=\"https://schema.org/{}\"", schema(options).item_type)
+  }
+  else {
+    String::new()
+  };
+  let prefetch: Vec<&str> = if content_config.prefetch_related {
+    entry.related.iter().map(|related| related.url.as_str()).filter(|url| !url.is_empty()).collect()
+  }
+  else {
+    Vec::new()
+  };
+  let head = render_head(&entry.social, &prefetch);
+  let json_ld_script = json_ld_script(entry, options);
+  let theme = content_config.prism_theme;
+  let mut theme_import = match theme.import() {
+    Some(stylesheet) => format!("\n    import '{}';", stylesheet),
+    None => String::new(),
+  };
+  // Relative path up to the output directory, from pages nested as deep
+  // as their permalink.
+  let output_root = "../".repeat(entry.permalink.split('/').filter(|segment| !segment.is_empty()).count().max(1));
+  if options.authors == AuthorsMode::Normalized {
+    theme_import.push_str(&format!("\n    import {{ authors as authorIndex }} from '{}{}';", output_root, AUTHORS_MODULE));
+  }
+  if content_config.content_mode == ContentMode::File {
+    theme_import.push_str(&format!("\n    import content from './{}?raw';", CONTENT_FILE));
+  }
+  if content_config.css_split {
+    theme_import.push_str(&format!("\n    import '{}{}';", output_root, SHARED_STYLESHEET));
+  }
+  let style_block = if content_config.css_file {
+    theme_import.push_str(&format!("\n    import './{}';", PAGE_STYLESHEET));
+    String::new()
+  }
+  else {
+    format!("\n\n  <style>\n{}\n  </style>", page_style(options))
+  };
+
+  fill_template(&options.assets.page_template, &[
+    ("header", header),
+    ("imports", theme_import),
+    ("kind", kind),
+    ("id", id_script),
+    ("title", output::js_string(&frontmatter.title)),
+    ("date", output::js_string(&formatted_date)),
+    ("tags", tags_json),
+    ("authors", authors_json),
+    ("related", related_json),
+    ("toc", toc_json),
+    ("revisions", revisions_json),
+    ("comments", comments_script),
+    ("computed", computed_script),
+    ("content", content_script),
+    ("frontmatter", frontmatter_script),
+    ("json_ld", json_ld_script),
+    ("head", head),
+    ("item_scope", item_scope),
+    ("body", body),
+    ("style", style_block),
+  ])
+}
+
+/// `template` with each `{{name}}` placeholder replaced by its value, in one
+/// pass so values are never read as placeholders.
+fn fill_template(template: &str, values: &[(&str, String)]) -> String {
+  let placeholder = Regex::new(r"\{\{(\w+)\}\}").unwrap();
+  placeholder.replace_all(template, |caps: &regex::Captures| {
+    values.iter()
+      .find(|(name, _)| *name == &caps[1])
+      .map_or_else(|| caps[0].to_string(), |(_, value)| value.clone())
+  }).into_owned()
+}
+
+const READER_STYLE: &str = r#"    .reader {
+      max-width: 40em;
+      margin: 0 auto;
+      padding: 1em;
+      line-height: 1.6;
+    }
+
+    .reader :global(img) {
+      max-width: 100%;
+    }
+
+    .reader :global(pre) {
+      overflow-x: auto;
+    }"#;
+
+/// `html` with the `\(...\)` and `\[...\]` math left by the Markdown stage
+/// rendered as MathML, outside code. Fails on the first formula MathML
+/// can't be made for.
+fn render_math(html: &str) -> Result<String, String> {
+  let code = Regex::new(r"(?s)<pre[\s>].*?</pre>|<code[\s>].*?</code>").unwrap();
+  let math = Regex::new(r"(?s)\\\((.*?)\\\)|\\\[(.*?)\\\]").unwrap();
+  let mut rendered = String::new();
+  let mut last = 0;
+  let code_spans = code.find_iter(html).map(|span| (span.start(), span.end())).chain([(html.len(), html.len())]);
+  for (start, end) in code_spans {
+    let text = &html[last..start];
+    let mut text_last = 0;
+    for caps in math.captures_iter(text) {
+      let (tex, display) = match caps.get(1) {
+        Some(tex) => (tex.as_str(), false),
+        None => (&caps[2], true),
+      };
+      // Markdown emphasis inside the math has already broken it up.
+      if tex.contains('<') {
+        return Err(format!("{}: formatted as Markdown, escape its * and _", tex));
+      }
+      let tex = tex.replace("\\*", "*").replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&#39;", "'").replace("&amp;", "&");
+      let mathml = crate::mathml::to_mathml(&tex, display).map_err(|e| format!("{}: {}", tex.trim(), e))?;
+      let whole = caps.get(0).unwrap();
+      rendered.push_str(&text[text_last..whole.start()]);
+      rendered.push_str(&mathml);
+      text_last = whole.end();
+    }
+    rendered.push_str(&text[text_last..]);
+    rendered.push_str(&html[start..end]);
+    last = end;
+  }
+  Ok(rendered)
+}
+
+/// The lean reader version of a page: title, byline and content with
+/// minimal styles, and no highlighting, math loader or other scripts. Math
+/// is rendered as MathML, and an error names a formula that can't be.
+pub fn generate_reader_component(entry: &Entry, options: &RenderOptions) -> Result<String, String> {
+  let frontmatter = &entry.frontmatter;
+  let mut byline: Vec<String> = frontmatter.authors.iter().map(|author| escape_attribute(&author.name)).collect();
+  byline.push(escape_attribute(&display_date(entry, options.content.time_format.as_deref())));
+  let canonical = if entry.url.is_empty() {
+    String::new()
+  }
+  else {
+    format!("\n    <link rel=\"canonical\" href=\"{}\">", escape_attribute(&entry.url))
+  };
+  let kind = output::js_string(&options.kind);
+  Ok(format!(
+    r#"{}
+<script>
+    export const kind = {kind};
+    const content = {};
+</script>
+
+<svelte:head>
+    <title>{}</title>{canonical}
+</svelte:head>
+
+<article class="reader">
+  <h1>{}</h1>
+  <p class="byline">{}</p>
+  {{@html content}}
+</article>
+
+<style>
+{READER_STYLE}
+</style>
+"#,
+    page_header(entry, options),
+    json!(render_math(&entry.html)?),
+    escape_attribute(&frontmatter.title),
+    escape_attribute(&frontmatter.title),
+    byline.join(" · "),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::ContentConfig;
+  use crate::site::PROJECT_KIND;
+
+  fn build(source: &str, content: ContentConfig) -> String {
+    let options = RenderOptions::default().content(content);
+    let (entry, _) = Entry::parse(source, &options).unwrap();
+    generate_svelte_component(&entry, &options)
+  }
+
+  #[test]
+  fn time_format_with_quotes_stays_a_string() {
+    let content = ContentConfig { time_format: Some(r"%H 'h' %M\".to_string()), ..ContentConfig::default() };
+    let component = build("---\ntitle: Post\ndate: 2024-01-05T14:30:00Z\ntags: []\n---\nText\n", content);
+    assert!(component.contains(r"export const date = 'January 05, 2024 14 \'h\' 30\\';"), "{}", component);
+  }
+
+  #[test]
+  fn json_ld_names_the_title_by_schema() {
+    let article = RenderOptions::default();
+    let (entry, _) = Entry::parse("---\ntitle: Post\ndate: 2024-01-05\ntags: []\n---\nText\n", &article).unwrap();
+    let script = json_ld_script(&entry, &article);
+    assert!(script.contains(r#"\"headline\":\"Post\""#), "{}", script);
+    assert!(!script.contains(r#"\"name\":\"Post\""#), "{}", script);
+
+    let project = RenderOptions::default().kind(PROJECT_KIND);
+    let (entry, _) = Entry::parse("---\ntitle: Tool\ndate: 2022-03-01\ntags: []\n---\nText\n", &project).unwrap();
+    let script = json_ld_script(&entry, &project);
+    assert!(script.contains(r#"\"@type\":\"SoftwareSourceCode\""#), "{}", script);
+    assert!(script.contains(r#"\"name\":\"Tool\""#), "{}", script);
+    assert!(!script.contains("headline"), "{}", script);
+  }
+
+  #[test]
+  fn byline_follows_the_number_of_authors() {
+    let source = |authors: &str| format!("---\ntitle: Post\ndate: 2024-01-05\ntags: []\n{}---\nText\n", authors);
+    let none = build(&source(""), ContentConfig::default());
+    assert!(!none.contains("{#each authors"), "{}", none);
+    assert!(!none.contains("class=\"profile\""), "{}", none);
+    assert!(none.contains("<p class=\"subtitle\">{date}</p>"), "{}", none);
+    assert!(none.contains("export const authors = [];"), "{}", none);
+
+    let one = build(&source("authors: Ada\n"), ContentConfig::default());
+    assert!(one.contains("{#each authors as author, index}"), "{}", one);
+    assert!(one.contains(r#"export const authors = [{"name":"Ada","url":null}];"#), "{}", one);
+
+    let three = build(&source("authors: [Ada, Grace, Linus]\n"), ContentConfig::default());
+    assert!(three.contains(r#"export const authors = [{"name":"Ada","url":null},{"name":"Grace","url":null},{"name":"Linus","url":null}];"#), "{}", three);
+    assert_eq!(three.matches("{#each authors").count(), 1, "{}", three);
+  }
+
+  #[test]
+  fn default_author_fills_an_empty_byline() {
+    let author = Author { name: "Jane Doe".to_string(), url: None, primary: false };
+    let options = RenderOptions::default().default_author(Some(author));
+    let (entry, _) = Entry::parse("---\ntitle: Post\ndate: 2024-01-05\ntags: []\n---\nText\n", &options).unwrap();
+    let component = generate_svelte_component(&entry, &options);
+    assert!(component.contains("{#each authors as author, index}"), "{}", component);
+    assert!(component.contains(r#"export const authors = [{"name":"Jane Doe","url":null}];"#), "{}", component);
+  }
+
+  #[test]
+  fn multiline_values_stay_on_one_line() {
+    let source = "---\ntitle: |\n  It's two\n  lines </script>\ndate: 2024-01-05\ntags: []\nsocial:\n  description: |\n    First line.\n    Second \"line\".\n---\nText\n";
+    let options = RenderOptions::default();
+    let (entry, warnings) = Entry::parse(source, &options).unwrap();
+    assert!(warnings.iter().any(|warning| warning.starts_with("[title-multiline]")), "{:?}", warnings);
+    let component = generate_svelte_component(&entry, &options);
+    assert!(component.contains(r"export const title = 'It\'s two\nlines <\/script>';"), "{}", component);
+    let description = component.lines().find(|line| line.contains("og:description")).unwrap();
+    assert!(description.contains("content=\"First line. Second &quot;line&quot;.\""), "{}", description);
+  }
+
+  #[test]
+  fn dates_show_a_time_only_for_timestamps() {
+    let with_time = || ContentConfig { time_format: Some("%H:%M %:z".to_string()), ..ContentConfig::default() };
+    let source = |date: &str| format!("---\ntitle: Post\ndate: {}\ntags: []\n---\nText\n", date);
+
+    let component = build(&source("2024-01-05"), with_time());
+    assert!(component.contains("export const date = 'January 05, 2024';"), "{}", component);
+    let component = build(&source("2024-01-05T23:30:00-05:00"), ContentConfig::default());
+    assert!(component.contains("export const date = 'January 05, 2024';"), "{}", component);
+    // The date and time are the ones in the timestamp's own offset.
+    let component = build(&source("2024-01-05T23:30:00-05:00"), with_time());
+    assert!(component.contains("export const date = 'January 05, 2024 23:30 -05:00';"), "{}", component);
+  }
+
+  #[test]
+  fn markup_mode_shows_svelte_only_html_as_text() {
+    let content = ContentConfig { content_mode: ContentMode::Markup, ..ContentConfig::default() };
+    let source = "---\ntitle: Post\ndate: 2024-01-05\ntags: []\n---\nPress <kbd>Ctrl</kbd> {x} <script>alert(1)</script>\n\n<style>\np { color: red; }\n</style>\n\n<svelte:window on:keydown={go} />\n\n<b on:click=\"go\">Go</b>\n";
+    let component = build(source, content);
+    let body = &component[component.find("<div class=\"content\"").unwrap()..];
+    assert!(body.contains("Press <kbd>Ctrl</kbd> &#123;x&#125; &lt;script&gt;alert(1)&lt;/script&gt;"), "{}", body);
+    assert!(body.contains("&lt;style&gt;\np &#123; color: red; &#125;\n&lt;/style&gt;"), "{}", body);
+    assert!(body.contains("&lt;svelte:window on:keydown=&#123;go&#125; /&gt;"), "{}", body);
+    // A refused tag's closing tag is refused with it.
+    assert!(body.contains("&lt;b on:click=&quot;go&quot;&gt;Go&lt;/b&gt;"), "{}", body);
+    assert!(!body.contains("<script") && !body.contains("<style>\np") && !body.contains("<svelte:"), "{}", body);
+  }
+
+  #[test]
+  fn reader_math_is_mathml_outside_code() {
+    let html = "<p>Inline \\(a\\*b &lt; c\\) and <code>\\(5 and \\)6</code>.</p>\n\\[\\frac{1}{2}\\]\n<pre><code>\\(x\\)</code></pre>";
+    assert_eq!(
+      render_math(html).unwrap(),
+      "<p>Inline <math><mrow><mi>a</mi><mo>∗</mo><mi>b</mi><mo>&lt;</mo><mi>c</mi></mrow></math> and <code>\\(5 and \\)6</code>.</p>\n<math display=\"block\"><mfrac><mn>1</mn><mn>2</mn></mfrac></math>\n<pre><code>\\(x\\)</code></pre>"
+    );
+  }
+
+  #[test]
+  fn reader_page_is_left_out_for_unsupported_math() {
+    let options = RenderOptions::default();
+    let source = |math: &str| format!("---\ntitle: Post\ndate: 2024-01-05\ntags: []\n---\nSee ${}$.\n", math);
+    let (entry, _) = Entry::parse(&source("x^2"), &options).unwrap();
+    let reader = generate_reader_component(&entry, &options).unwrap();
+    assert!(reader.contains("<math><msup><mi>x</mi><mn>2</mn></msup></math>"), "{}", reader);
+    let (entry, _) = Entry::parse(&source(r"\overbrace{x}"), &options).unwrap();
+    assert_eq!(generate_reader_component(&entry, &options).err().unwrap(), r"\overbrace{x}: unsupported command \overbrace");
+  }
+}
@@ -0,0 +1,1379 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::cache::DEFAULT_CACHE_DIR;
+use crate::frontmatter::{normalize_tag, Author, FRONTMATTER_FIELDS};
+use crate::markdown::{TextOptions, REFERENCE_TOKENS, REVISION_TOKENS, TOKEN_PATTERN};
+use crate::site::{default_content_types, ContentType};
+
+pub const DEFAULT_CONFIG_PATH: &str = "md_to_svelte.toml";
+
+pub const DEFAULT_MARKDOWN_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+/// Settings read from `md_to_svelte.toml`. Every key is optional so a
+/// missing file behaves exactly like an empty one.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  /// Directory SvelteKit serves static files from. Every static artifact,
+  /// such as copied images, is written below it.
+  pub static_root: PathBuf,
+  /// Environment variables that `${NAME}` tokens in markdown prose may use.
+  pub env_allowlist: Vec<String>,
+  /// Author of entries whose frontmatter lists none. Without it such
+  /// entries are shown without a byline.
+  pub default_author: Option<Author>,
+  /// Reject frontmatter keys that are not fields, aliases or keys a
+  /// computed field reads, to catch typos like `tgas:`.
+  pub strict_frontmatter: bool,
+  /// Frontmatter keys read as another field, e.g. `publishDate = "date"`.
+  /// A target like `social.description` is a key of a mapping field.
+  pub frontmatter_aliases: BTreeMap<String, String>,
+  /// Authors of entries whose frontmatter lists none, by source path. They
+  /// take precedence over `default_author`.
+  pub author_rules: Vec<AuthorRule>,
+  /// Markdown or HTML appended after the content of every page. Content
+  /// types can override it.
+  pub footer: Option<String>,
+  /// Link to edit a page's source, with `{path}` standing for the source
+  /// path, e.g. `https://github.com/me/site/edit/main/{path}`.
+  pub edit_url: Option<String>,
+  /// Line after the header of generated components and TypeScript files
+  /// naming the source to edit instead, with `{source}` standing for its
+  /// path. Empty leaves the line out.
+  pub source_comment: String,
+  /// BCP 47 language tag of the content, such as `en-US`, added to every
+  /// entry of the data files and to the manifest so the frontend can format
+  /// the ISO dates for its readers.
+  pub content_locale: Option<String>,
+  /// Declare an interface for the entries of each data file, such as
+  /// `Article`, and type the exported array with it.
+  pub typed_data: bool,
+  /// Sections of content and where they are read from and generated to.
+  /// Unset uses `default_content_types`.
+  pub content_types: Option<Vec<ContentType>>,
+  /// UUID namespace of the stable ids of published entries. Ids are kept
+  /// in `md_to_svelte.ids.json`; none are assigned when unset.
+  pub id_namespace: Option<String>,
+  /// Only warn about slugs that differ just in case, for sites that are
+  /// only ever built and served on case-sensitive filesystems.
+  pub allow_case_collisions: bool,
+  /// Whether page URLs end in a slash. When set, internal links in the
+  /// content are rewritten to match.
+  pub url_style: Option<UrlStyle>,
+  /// Write a `+page.ts` next to every page exporting SvelteKit's
+  /// `trailingSlash` option to match `url_style`.
+  pub route_options: bool,
+  /// What to do when two sources of a content type have the same slug.
+  pub on_slug_collision: SlugCollision,
+  /// Whether data files and pages hold full author objects or ids into a
+  /// shared `authors.ts`.
+  pub authors: AuthorsMode,
+  /// Skip files git ignores when reading content and copying images.
+  /// `.mdsvelteignore` files apply either way.
+  pub respect_gitignore: bool,
+  /// File extensions, without the dot, read as markdown sources.
+  pub markdown_extensions: Vec<String>,
+  /// Absolute URL the site is served at, such as `https://example.com`.
+  /// The sitemap and the feed need it.
+  pub site_url: Option<String>,
+  /// Directory the parsed entries are cached in between runs, so unchanged
+  /// sources are not rendered again. Created when missing.
+  pub cache_dir: PathBuf,
+  /// Warn about sources that take longer than this many milliseconds to
+  /// parse and write.
+  pub slow_file_ms: Option<u64>,
+  /// Directory with replacements for the built-in `avatar.<ext>` and
+  /// `page.css`.
+  pub assets_dir: Option<PathBuf>,
+  /// Hash of the config file, empty without one. Cached entries built with
+  /// another config are not reused.
+  #[serde(skip)]
+  pub fingerprint: String,
+  pub doctor: DoctorConfig,
+  pub secrets: SecretsConfig,
+  pub tag_cloud: TagCloudConfig,
+  pub sitemap: SitemapConfig,
+  pub feed: FeedConfig,
+  pub plaintext: PlaintextConfig,
+  pub slugs: SlugConfig,
+  /// Per content type settings, keyed by content type name (`articles`, `projects`).
+  pub content: BTreeMap<String, ContentConfig>,
+}
+
+impl Default for Config {
+  fn default() -> Config {
+    Config {
+      static_root: PathBuf::from("static"),
+      env_allowlist: Vec::new(),
+      default_author: None,
+      strict_frontmatter: false,
+      frontmatter_aliases: BTreeMap::new(),
+      author_rules: Vec::new(),
+      footer: None,
+      edit_url: None,
+      source_comment: DEFAULT_SOURCE_COMMENT.to_string(),
+      content_locale: None,
+      typed_data: false,
+      content_types: None,
+      id_namespace: None,
+      allow_case_collisions: false,
+      url_style: None,
+      route_options: false,
+      on_slug_collision: SlugCollision::Error,
+      authors: AuthorsMode::Inline,
+      respect_gitignore: true,
+      markdown_extensions: DEFAULT_MARKDOWN_EXTENSIONS.map(String::from).to_vec(),
+      site_url: None,
+      cache_dir: PathBuf::from(DEFAULT_CACHE_DIR),
+      slow_file_ms: None,
+      assets_dir: None,
+      fingerprint: String::new(),
+      doctor: DoctorConfig::default(),
+      secrets: SecretsConfig::default(),
+      tag_cloud: TagCloudConfig::default(),
+      sitemap: SitemapConfig::default(),
+      feed: FeedConfig::default(),
+      plaintext: PlaintextConfig::default(),
+      slugs: SlugConfig::default(),
+      content: BTreeMap::new(),
+    }
+  }
+}
+
+/// A value derived from the frontmatter, exported from each page and added
+/// to the data file.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ComputedField {
+  /// A frontmatter field such as `title`, or `extra.<key>` for a key this
+  /// tool does not know; `.<key>` reads into a mapping.
+  pub from: String,
+  /// Replaces the value with the one listed for it.
+  pub map: Option<BTreeMap<String, toml::Value>>,
+  /// Used when the value is missing or not listed in `map`. Required with
+  /// `map`.
+  pub default: Option<toml::Value>,
+}
+
+/// Keys the data files and components already use, which computed fields
+/// may not shadow.
+const RESERVED_FIELDS: [&str; 21] = [
+  "kind",
+  "id",
+  "slug",
+  "url",
+  "title",
+  "authors",
+  "date",
+  "datetime",
+  "date_end",
+  "ongoing",
+  "locale",
+  "tags",
+  "checksum",
+  "reading_time",
+  "comments",
+  "discussion_url",
+  "related",
+  "tag_slugs",
+  "toc",
+  "revisions",
+  "content",
+];
+
+/// Gives entries below a path an author, e.g. guest posts in
+/// `guests/alice/`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorRule {
+  /// Pattern in `.gitignore` syntax, like `.mdsvelteignore`, relative to the
+  /// content directory.
+  pub pattern: String,
+  /// A name, or a table with `name` and `url`.
+  #[serde(deserialize_with = "crate::frontmatter::author")]
+  pub author: Author,
+}
+
+impl AuthorRule {
+  /// Length of the pattern before its first wildcard. When several rules
+  /// match, the one with the longest literal prefix wins.
+  fn specificity(&self) -> usize {
+    self.pattern.find(['*', '?', '[']).unwrap_or(self.pattern.len())
+  }
+}
+
+/// The most specific rule matching `path`, a source below `dir`. Equally
+/// specific rules are taken in the order listed.
+pub fn author_rule<'a>(rules: &'a [AuthorRule], dir: &Path, path: &Path) -> Option<&'a AuthorRule> {
+  rules.iter()
+    .rev()
+    .filter(|rule| crate::walk::matches_pattern(dir, &rule.pattern, path).unwrap_or(false))
+    .max_by_key(|rule| rule.specificity())
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DoctorConfig {
+  /// Checks `md_to_svelte doctor` should not run.
+  pub skip: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretsConfig {
+  /// Scan sources for keys and tokens before building.
+  pub scan: bool,
+  /// Also report email addresses.
+  pub emails: bool,
+  /// Extra regular expressions to report.
+  pub patterns: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagCloudConfig {
+  /// Data file the tag cloud is written to, such as `src/lib/tagCloud.ts`.
+  /// Unset writes none.
+  pub path: Option<PathBuf>,
+  /// Count the tags of projects as well as articles.
+  pub projects: bool,
+  /// Number of size steps the counts are spread over.
+  pub buckets: Option<usize>,
+}
+
+impl TagCloudConfig {
+  pub fn buckets(&self) -> usize {
+    self.buckets.unwrap_or(DEFAULT_TAG_CLOUD_BUCKETS)
+  }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SitemapConfig {
+  /// Write `sitemap.xml` listing every page to `static_root`.
+  pub enabled: bool,
+  /// Most URLs in one sitemap file. Above it the sitemap is split into
+  /// `sitemap-1.xml`, `sitemap-2.xml`, ... listed by `sitemap_index.xml`.
+  pub max_urls: Option<usize>,
+}
+
+impl SitemapConfig {
+  pub fn max_urls(&self) -> usize {
+    self.max_urls.unwrap_or(MAX_SITEMAP_URLS)
+  }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedConfig {
+  /// Write an Atom feed of the articles to `static_root/feed.xml`.
+  pub enabled: bool,
+  /// Title of the feed. Defaults to the host of `site_url`.
+  pub title: Option<String>,
+  /// Newest articles in `feed.xml`. Older ones go into archive feeds of the
+  /// same size linked with RFC 5005 `prev-archive` links.
+  pub max_items: Option<usize>,
+  /// Also write a feed of the newest `max_items` articles of every tag to
+  /// `feeds/<tag slug>.xml`.
+  pub tags: bool,
+  /// Write `feeds.opml` listing the feed and the tag feeds, for importing
+  /// them into a reader at once.
+  pub opml: bool,
+}
+
+impl FeedConfig {
+  pub fn max_items(&self) -> usize {
+    self.max_items.unwrap_or(DEFAULT_FEED_ITEMS)
+  }
+}
+
+/// How page slugs are derived from file names. Slugs pinned in the slug
+/// lock are used as they are.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlugConfig {
+  /// Transliterate letters such as `é` to ASCII and replace other non-ASCII
+  /// characters with a hyphen.
+  pub ascii: bool,
+  /// What to do with dots in a file name once the extension is removed.
+  pub dots: DotPolicy,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DotPolicy {
+  /// `notes.draft.md` is published at `notes.draft`.
+  #[default]
+  Keep,
+  /// `notes.draft.md` is published at `notes-draft`.
+  Hyphen,
+  /// A file name with a dot before the extension fails the entry.
+  Error,
+}
+
+/// How `--emit-plaintext` reads the parts of an article that are not prose.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlaintextConfig {
+  /// Said in place of each code block; an empty string leaves them out.
+  pub code: Option<String>,
+  /// Whether math is read as a placeholder or as its TeX source.
+  pub math: MathText,
+  /// Said in place of each formula with `math = "placeholder"`.
+  pub math_placeholder: Option<String>,
+  /// Put before the text of every heading.
+  pub heading_prefix: Option<String>,
+  /// Read images as their alt text; `false` leaves them out.
+  pub image_alt: Option<bool>,
+}
+
+impl PlaintextConfig {
+  pub fn code(&self) -> &str {
+    self.code.as_deref().unwrap_or(DEFAULT_PLAINTEXT_CODE)
+  }
+
+  pub fn math_placeholder(&self) -> &str {
+    self.math_placeholder.as_deref().unwrap_or(DEFAULT_PLAINTEXT_MATH)
+  }
+
+  pub fn heading_prefix(&self) -> &str {
+    self.heading_prefix.as_deref().unwrap_or(DEFAULT_PLAINTEXT_HEADING_PREFIX)
+  }
+
+  pub fn image_alt(&self) -> bool {
+    self.image_alt.unwrap_or(true)
+  }
+
+  /// These settings as options for [`crate::markdown::reading_text`].
+  pub(crate) fn text_options(&self) -> TextOptions<'_> {
+    TextOptions {
+      code: Some(self.code()),
+      math: (self.math == MathText::Placeholder).then(|| self.math_placeholder()),
+      heading_prefix: self.heading_prefix(),
+      omit_images: !self.image_alt(),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MathText {
+  /// `math_placeholder` in place of each formula.
+  #[default]
+  Placeholder,
+  /// The TeX source as written.
+  Tex,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentConfig {
+  /// Order of the blocks making up the page.
+  pub layout: Option<Vec<Block>>,
+  /// Order of the items inside the meta block.
+  pub meta: Option<Vec<MetaItem>>,
+  /// Show nested tags as `lang/rust` on pages instead of just `rust`.
+  pub full_tag_paths: bool,
+  /// Attach the frontmatter as JSON in a `data-frontmatter` attribute on the
+  /// content wrapper, for client-side scripts.
+  pub frontmatter_attribute: bool,
+  /// Put `data-slug` and `data-tags` (tag slugs separated by spaces) on the
+  /// content wrapper.
+  pub data_attributes: bool,
+  /// Class of the page title in place of `title`.
+  pub title_class: Option<String>,
+  /// Class of the byline block in place of `meta`.
+  pub meta_class: Option<String>,
+  /// Class of the content wrapper in place of `content`. The built-in
+  /// styles target `content`, so they no longer apply.
+  pub content_class: Option<String>,
+  /// Classes added after the class of `title`, `meta` or `content`, which
+  /// keeps the built-in styles.
+  pub extra_classes: BTreeMap<String, String>,
+  /// Split tags written as `tags: rust, wasm` on commas.
+  pub split_comma_tags: bool,
+  /// Tags every entry carries after its own, unless it already lists them.
+  pub default_tags: Vec<String>,
+  /// Render `- [ ]` and `- [x]` list items as disabled checkboxes.
+  pub tasklists: bool,
+  /// Read Obsidian image embeds, `![[diagram.png]]`, as images of
+  /// `images/diagram.png`.
+  pub wiki_embeds: bool,
+  /// Read `:::div.grid` ... `:::` containers as that element with those
+  /// classes, with the markdown inside rendered in full.
+  pub containers: bool,
+  /// Languages inline code may name as a prefix, `` `rust:let x = 5` ``,
+  /// to be highlighted as that language.
+  pub inline_code_languages: Vec<String>,
+  /// Mark up the page with schema.org microdata (`Article` for articles,
+  /// `SoftwareSourceCode` for projects). On unless set to false.
+  pub microdata: Option<bool>,
+  /// Whether comments are enabled on entries that don't set `comments`.
+  /// Comment settings and discussion links are only emitted for content
+  /// types that set this.
+  pub comments_default: Option<bool>,
+  /// Markup of the link to an entry's `discussion_url`, with `{url}` filled
+  /// in.
+  pub discussion_template: Option<String>,
+  /// Drop the `# ` heading a missing title was taken from, so the title is
+  /// not shown twice.
+  pub strip_title_heading: bool,
+  /// Prefix h2 to h4 with hierarchical numbers such as `2.3`. Entries can
+  /// override it with `number_headings` in their frontmatter.
+  pub number_headings: bool,
+  /// Text of the heading after which numbered headings are lettered `A`,
+  /// `B`, ... instead.
+  pub appendix_heading: Option<String>,
+  /// Prism theme imported by the page.
+  pub prism_theme: PrismTheme,
+  /// URL prefix `images/...` references are rewritten to. Images are copied
+  /// to the same path below `static_root`.
+  pub image_url_prefix: Option<String>,
+  /// Read `/images/...` references as this type's images, like
+  /// `images/...`, instead of as site-absolute URLs.
+  pub root_images: bool,
+  /// Markdown or HTML appended after the content, with `{slug}`, `{date}`
+  /// and `{edit_url}` filled in. Falls back to the site-wide `footer`.
+  pub footer: Option<String>,
+  /// Overrides the site-wide `edit_url`.
+  pub edit_url: Option<String>,
+  /// Keep only the critical page styles inline and move the rest to a
+  /// stylesheet shared by all pages of the type.
+  pub css_split: bool,
+  /// Write the page's styles to a `styles.css` next to each component and
+  /// import it instead of inlining a `<style>` block.
+  pub css_file: bool,
+  /// How the body is put into the component.
+  pub content_mode: ContentMode,
+  /// Values derived from the frontmatter, by the name they are exported
+  /// under.
+  pub computed: BTreeMap<String, ComputedField>,
+  /// Path of each page below the output directory and route, using
+  /// `{year}`, `{month}`, `{day}` and `{slug}`, such as
+  /// `{year}/{month}/{slug}`.
+  pub permalink: Option<String>,
+  /// Also write a lean reader version of each page, without scripts,
+  /// highlighting or the math loader, at `<slug>/reader`.
+  pub reader: bool,
+  /// Also write the data file in chunks of this many entries, newest first,
+  /// for paginated index pages.
+  pub page_size: Option<usize>,
+  /// Most related entries listed per page. Entries named in `related:` are
+  /// always kept; tag-based suggestions fill the remaining slots.
+  pub related_limit: Option<usize>,
+  /// Add `<link rel="prefetch">` hints for the related pages to the head.
+  pub prefetch_related: bool,
+  /// Add a schema.org `BreadcrumbList` of the content type, the category
+  /// and the title to the head of entries with a `category`.
+  pub breadcrumbs: bool,
+  /// Name of the content type's step in the breadcrumb trail, `Articles` or
+  /// `Projects` when unset.
+  pub breadcrumb_label: Option<String>,
+  /// Site-relative URL of a category's index page, with `{category}`
+  /// standing for the category's slug, e.g. `/articles/category/{category}`.
+  pub category_url: Option<String>,
+  /// strftime format of the time shown after the date of entries whose
+  /// `date` is a full timestamp, such as `%H:%M %:z`. Unset shows the date
+  /// only.
+  pub time_format: Option<String>,
+  /// Reading speed for words of space-separated scripts, used for
+  /// `reading_time`.
+  pub words_per_minute: Option<u32>,
+  /// Reading speed for Chinese and Japanese text, which has no spaces to
+  /// count words by.
+  pub cjk_characters_per_minute: Option<u32>,
+  /// How entries of the References section are written, using `{author}`,
+  /// `{title}` and `{url}`.
+  pub reference_format: Option<String>,
+  /// Markup added to the headings a revision lists, using `{date}` and
+  /// `{note}`.
+  pub revision_badge: Option<String>,
+  /// strftime format of the date in revision badges.
+  pub revision_date_format: Option<String>,
+  /// Give code blocks the theme's background, scoped to the content, so they
+  /// sit cleanly on the page background.
+  pub code_background: bool,
+  /// Strip raw HTML in the markdown down to `allowed_tags` and
+  /// `allowed_attributes`. Tags the renderer emits itself are always kept.
+  pub sanitize: bool,
+  /// Raw HTML tags kept when sanitizing. Defaults to a set of inline
+  /// semantic tags such as `kbd`, `mark` and `abbr`.
+  pub allowed_tags: Option<Vec<String>>,
+  /// Attributes kept on each tag when sanitizing, keyed by tag.
+  pub allowed_attributes: Option<BTreeMap<String, Vec<String>>>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UrlStyle {
+  /// `/articles/post`, SvelteKit's default.
+  #[default]
+  NoSlash,
+  /// `/articles/post/`.
+  Slash,
+}
+
+impl UrlStyle {
+  /// Value of SvelteKit's `trailingSlash` page option for this style.
+  pub fn trailing_slash(self) -> &'static str {
+    match self {
+      UrlStyle::NoSlash => "never",
+      UrlStyle::Slash => "always",
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SlugCollision {
+  /// Fail the build; only the first source is written.
+  #[default]
+  Error,
+  /// Give later sources `-2`, `-3`, ... suffixed slugs, with a warning.
+  WarnSuffix,
+  /// Leave out later sources, with a warning.
+  WarnSkip,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorsMode {
+  /// Every data file entry and page carries its authors' names and URLs.
+  #[default]
+  Inline,
+  /// Authors are written once per content type to `authors.ts`, keyed by
+  /// [`Author::id`], and data file entries and pages list ids. Pages import
+  /// the module and export the resolved objects, so templates are unchanged.
+  Normalized,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentMode {
+  /// One HTML string injected with `{@html}`.
+  #[default]
+  Html,
+  /// The HTML written out as component markup, which Svelte compiles and
+  /// hydrates, so SvelteKit handles its links. Raw HTML with tags outside
+  /// `allowed_tags` or with Svelte directives is shown as text.
+  Markup,
+  /// The HTML written to a `content.html` next to the component, which
+  /// imports it with Vite's `?raw` loader and injects it with `{@html}`.
+  File,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PrismTheme {
+  #[serde(rename = "default")]
+  Prism,
+  Coy,
+  Dark,
+  #[default]
+  Okaidia,
+  Solarizedlight,
+  Tomorrow,
+  Twilight,
+  /// Import no theme, for sites that style code blocks themselves.
+  None,
+}
+
+impl PrismTheme {
+  /// The stylesheet to import, if any.
+  pub fn import(self) -> Option<&'static str> {
+    match self {
+      PrismTheme::Prism => Some("prismjs/themes/prism.css"),
+      PrismTheme::Coy => Some("prismjs/themes/prism-coy.css"),
+      PrismTheme::Dark => Some("prismjs/themes/prism-dark.css"),
+      PrismTheme::Okaidia => Some("prismjs/themes/prism-okaidia.css"),
+      PrismTheme::Solarizedlight => Some("prismjs/themes/prism-solarizedlight.css"),
+      PrismTheme::Tomorrow => Some("prismjs/themes/prism-tomorrow.css"),
+      PrismTheme::Twilight => Some("prismjs/themes/prism-twilight.css"),
+      PrismTheme::None => None,
+    }
+  }
+
+  /// Background color the theme gives code blocks.
+  pub fn background(self) -> Option<&'static str> {
+    match self {
+      PrismTheme::Prism => Some("#f5f2f0"),
+      PrismTheme::Coy => Some("#fdfdfd"),
+      PrismTheme::Dark => Some("hsl(30, 20%, 25%)"),
+      PrismTheme::Okaidia => Some("#272822"),
+      PrismTheme::Solarizedlight => Some("#fdf6e3"),
+      PrismTheme::Tomorrow => Some("#2d2d2d"),
+      PrismTheme::Twilight => Some("hsl(0, 0%, 8%)"),
+      PrismTheme::None => None,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Block {
+  Title,
+  Meta,
+  Rule,
+  Content,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MetaItem {
+  Authors,
+  Date,
+}
+
+pub const DEFAULT_LAYOUT: [Block; 4] = [Block::Title, Block::Meta, Block::Rule, Block::Content];
+pub const DEFAULT_META: [MetaItem; 2] = [MetaItem::Authors, MetaItem::Date];
+pub const FOOTER_TOKENS: [&str; 3] = ["slug", "date", "edit_url"];
+pub const EDIT_URL_TOKENS: [&str; 1] = ["path"];
+pub const CATEGORY_URL_TOKENS: [&str; 1] = ["category"];
+pub const SOURCE_COMMENT_TOKENS: [&str; 1] = ["source"];
+pub const DEFAULT_SOURCE_COMMENT: &str = "Edit {source} instead; this file is overwritten on every build.";
+pub const PERMALINK_TOKENS: [&str; 4] = ["year", "month", "day", "slug"];
+pub const DEFAULT_PERMALINK: &str = "{slug}";
+pub const DEFAULT_RELATED_LIMIT: usize = 3;
+pub const DEFAULT_TAG_CLOUD_BUCKETS: usize = 5;
+/// Most URLs the sitemap protocol allows in one file.
+pub const MAX_SITEMAP_URLS: usize = 50_000;
+pub const DEFAULT_FEED_ITEMS: usize = 20;
+pub const DEFAULT_PLAINTEXT_CODE: &str = "code sample omitted";
+pub const DEFAULT_PLAINTEXT_MATH: &str = "equation omitted";
+pub const DEFAULT_PLAINTEXT_HEADING_PREFIX: &str = "Section: ";
+pub const DEFAULT_WORDS_PER_MINUTE: u32 = 200;
+pub const DEFAULT_CJK_CHARACTERS_PER_MINUTE: u32 = 500;
+pub const DEFAULT_REFERENCE_FORMAT: &str = "{author}. {title}. {url}";
+pub const DEFAULT_REVISION_BADGE: &str = r#"<span class="revision-badge" title="{note}">updated {date}</span>"#;
+pub const DEFAULT_REVISION_DATE_FORMAT: &str = "%Y-%m";
+pub const DISCUSSION_TOKENS: [&str; 1] = ["url"];
+pub const DEFAULT_DISCUSSION_TEMPLATE: &str = r#"<p class="discussion"><a href="{url}">Discuss this post</a></p>"#;
+
+impl Config {
+  /// Reads and validates the config at `path`. A missing file gives the
+  /// defaults unless it is `required`, as a path given on the command line
+  /// is.
+  pub fn load(path: &Path, required: bool) -> Result<Config, String> {
+    if !path.exists() && !required {
+      return Ok(Config::default());
+    }
+    let text = std::fs::read_to_string(path)
+      .map_err(|e| format!("Error reading config {}: {}", path.display(), e))?;
+    let (mut config, mut problems) = parse(&text)
+      .map_err(|problems| format!("Error parsing config {}: {}", path.display(), problems.join("\n  ")))?;
+    config.fingerprint = crate::cache::hash(&text);
+    problems.extend(config.validate());
+    if !problems.is_empty() {
+      return Err(format!("Invalid config {}:\n  {}", path.display(), problems.join("\n  ")));
+    }
+    Ok(config)
+  }
+
+  /// The parsed `id_namespace`, which `validate` checked.
+  pub fn id_namespace(&self) -> Option<uuid::Uuid> {
+    self.id_namespace.as_deref().map(|namespace| uuid::Uuid::parse_str(namespace).unwrap())
+  }
+
+  /// The configured content types, or the defaults when none are.
+  pub fn content_types(&self) -> Vec<ContentType> {
+    self.content_types.clone().unwrap_or_else(default_content_types)
+  }
+
+  /// URL paths the content types are served at.
+  pub fn page_routes(&self) -> Vec<String> {
+    self.content_types().iter().map(ContentType::route).collect()
+  }
+
+  /// Every directory a build writes to, with a name for messages.
+  pub fn output_dirs(&self) -> Vec<(String, PathBuf)> {
+    let mut dirs = vec![("static_root".to_string(), self.static_root.clone())];
+    for content_type in self.content_types() {
+      dirs.push((format!("output directory of {}", content_type.name), PathBuf::from(&content_type.output_dir)));
+    }
+    if let Some(path) = &self.tag_cloud.path {
+      let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+      dirs.push(("tag_cloud.path".to_string(), dir.to_path_buf()));
+    }
+    dirs
+  }
+
+  /// Fails if a directory the build writes to resolves, after following
+  /// `..` and symlinks, to a path outside `root`.
+  pub fn check_output_root(&self, root: &Path) -> Result<(), String> {
+    let root = root.canonicalize()
+      .map_err(|e| format!("Error resolving output root {}: {}", root.display(), e))?;
+    let mut problems = Vec::new();
+    for (name, dir) in self.output_dirs() {
+      let resolved = resolve_path(&dir);
+      if !resolved.starts_with(&root) {
+        problems.push(format!("{}: {} resolves to {}, outside {}", name, dir.display(), resolved.display(), root.display()));
+      }
+    }
+    if !problems.is_empty() {
+      return Err(format!("Refusing to write outside the output root:\n  {}", problems.join("\n  ")));
+    }
+    Ok(())
+  }
+
+  /// Checks constraints that involve more than one key or a key's value
+  /// against what the tool knows about. Returns every problem found.
+  fn validate(&self) -> Vec<String> {
+    let mut problems = Vec::new();
+    let checks = crate::doctor::detector_names();
+    for name in &self.doctor.skip {
+      if !checks.contains(&name.as_str()) {
+        problems.push(format!("doctor.skip: unknown check '{}', expected one of {}", name, checks.join(", ")));
+      }
+    }
+
+    for (key, dir) in [("static_root", &self.static_root), ("cache_dir", &self.cache_dir)] {
+      if dir.exists() && !dir.is_dir() {
+        problems.push(format!("{}: {} exists and is not a directory", key, dir.display()));
+      }
+    }
+    if let Some(dir) = &self.assets_dir {
+      if !dir.is_dir() {
+        problems.push(format!("assets_dir: {} is not a directory", dir.display()));
+      }
+    }
+
+    if self.markdown_extensions.is_empty() {
+      problems.push("markdown_extensions: must list at least one extension".to_string());
+    }
+    for extension in &self.markdown_extensions {
+      if extension.is_empty() || extension.contains(['.', '/', '\\']) {
+        problems.push(format!("markdown_extensions: '{}' must be an extension without the dot, like \"md\"", extension));
+      }
+    }
+
+    if let Some(path) = &self.tag_cloud.path {
+      if path.extension().is_none_or(|extension| extension != "ts") {
+        problems.push(format!("tag_cloud.path: '{}' must be a .ts file", path.display()));
+      }
+    }
+    if self.tag_cloud.buckets == Some(0) {
+      problems.push("tag_cloud.buckets: must be at least 1".to_string());
+    }
+    if let Some(url) = &self.site_url {
+      if !(url.starts_with("https://") || url.starts_with("http://")) || url.ends_with('/') {
+        problems.push(format!("site_url: '{}' must be an absolute http(s) URL without a trailing slash", url));
+      }
+    }
+    if self.sitemap.enabled && self.site_url.is_none() {
+      problems.push("sitemap.enabled: requires site_url".to_string());
+    }
+    if !(1..=MAX_SITEMAP_URLS).contains(&self.sitemap.max_urls()) {
+      problems.push(format!("sitemap.max_urls: must be between 1 and {}", MAX_SITEMAP_URLS));
+    }
+    if self.feed.enabled && self.site_url.is_none() {
+      problems.push("feed.enabled: requires site_url".to_string());
+    }
+    if (self.feed.tags || self.feed.opml) && !self.feed.enabled {
+      problems.push("feed: tags and opml have no effect without enabled".to_string());
+    }
+    if self.feed.max_items == Some(0) {
+      problems.push("feed.max_items: must be at least 1".to_string());
+    }
+    if self.slow_file_ms == Some(0) {
+      problems.push("slow_file_ms: must be at least 1".to_string());
+    }
+
+    for (alias, target) in &self.frontmatter_aliases {
+      let field = target.split('.').next().unwrap_or_default();
+      if !FRONTMATTER_FIELDS.contains(&field) || target.split('.').any(str::is_empty) {
+        problems.push(format!("frontmatter_aliases.{}: '{}' is not a frontmatter field ({})", alias, target, FRONTMATTER_FIELDS.join(", ")));
+      }
+      else if FRONTMATTER_FIELDS.contains(&alias.as_str()) {
+        problems.push(format!("frontmatter_aliases.{}: is itself a frontmatter field", alias));
+      }
+    }
+
+    for rule in &self.author_rules {
+      if let Err(e) = crate::walk::matches_pattern(Path::new("."), &rule.pattern, Path::new("./x")) {
+        problems.push(format!("author_rules: pattern '{}': {}", rule.pattern, e));
+      }
+      if rule.author.name.trim().is_empty() {
+        problems.push(format!("author_rules: pattern '{}' has an empty author name", rule.pattern));
+      }
+    }
+
+    if let Err(e) = crate::secrets::SecretScanner::new(&self.secrets) {
+      problems.push(format!("secrets.patterns: {}", e));
+    }
+
+    let token_regex = regex::Regex::new(TOKEN_PATTERN).unwrap();
+    let unknown_tokens = |key: &str, template: &str, known: &[&str], problems: &mut Vec<String>| {
+      for caps in token_regex.captures_iter(template) {
+        if !known.contains(&&caps[1]) {
+          problems.push(format!("{}: unknown token {}, expected one of {{{}}}", key, &caps[0], known.join("}, {")));
+        }
+      }
+    };
+    if let Some(footer) = &self.footer {
+      unknown_tokens("footer", footer, &FOOTER_TOKENS, &mut problems);
+    }
+    if let Some(edit_url) = &self.edit_url {
+      unknown_tokens("edit_url", edit_url, &EDIT_URL_TOKENS, &mut problems);
+    }
+
+    unknown_tokens("source_comment", &self.source_comment, &SOURCE_COMMENT_TOKENS, &mut problems);
+    if self.source_comment.contains("-->") || self.source_comment.contains('\n') {
+      problems.push("source_comment: must not contain '-->' or a line break".to_string());
+    }
+
+    let language_tag = regex::Regex::new(r"^[A-Za-z]{2,3}(-[A-Za-z0-9]{2,8})*$").unwrap();
+    if let Some(locale) = self.content_locale.as_deref().filter(|locale| !language_tag.is_match(locale)) {
+      problems.push(format!("content_locale: '{}' is not a BCP 47 language tag such as en-US", locale));
+    }
+    if let Some(namespace) = self.id_namespace.as_deref().filter(|namespace| uuid::Uuid::parse_str(namespace).is_err()) {
+      problems.push(format!("id_namespace: '{}' is not a UUID (generate one with uuidgen)", namespace));
+    }
+
+    if self.route_options && self.url_style.is_none() {
+      problems.push("route_options: requires url_style".to_string());
+    }
+
+    let identifier = regex::Regex::new(r"^[A-Za-z_$][A-Za-z0-9_$]*$").unwrap();
+    if let Some(content_types) = &self.content_types {
+      check_content_types(content_types, &mut problems);
+    }
+    let all_types = self.content_types();
+    let content_types: Vec<&str> = all_types.iter().map(|ty| ty.name.as_str()).collect();
+    for (name, content) in &self.content {
+      if !content_types.contains(&name.as_str()) {
+        problems.push(format!("content.{}: unknown content type, expected one of {}", name, content_types.join(", ")));
+      }
+      if has_duplicates(content.layout()) {
+        problems.push(format!("content.{}.layout: a block is listed more than once", name));
+      }
+      if has_duplicates(content.meta()) {
+        problems.push(format!("content.{}.meta: an item is listed more than once", name));
+      }
+      if content.meta.is_some() && !content.layout().contains(&Block::Meta) {
+        problems.push(format!("content.{}.meta: has no effect because layout has no meta block", name));
+      }
+      unknown_tokens(&format!("content.{}.reference_format", name), content.reference_format(), &REFERENCE_TOKENS, &mut problems);
+      unknown_tokens(&format!("content.{}.revision_badge", name), content.revision_badge(), &REVISION_TOKENS, &mut problems);
+      if let Some(template) = &content.discussion_template {
+        unknown_tokens(&format!("content.{}.discussion_template", name), template, &DISCUSSION_TOKENS, &mut problems);
+        if content.comments_default.is_none() {
+          problems.push(format!("content.{}.discussion_template: has no effect without comments_default", name));
+        }
+      }
+      if let Some(footer) = &content.footer {
+        unknown_tokens(&format!("content.{}.footer", name), footer, &FOOTER_TOKENS, &mut problems);
+      }
+      if let Some(edit_url) = &content.edit_url {
+        unknown_tokens(&format!("content.{}.edit_url", name), edit_url, &EDIT_URL_TOKENS, &mut problems);
+      }
+      if let Some(permalink) = &content.permalink {
+        unknown_tokens(&format!("content.{}.permalink", name), permalink, &PERMALINK_TOKENS, &mut problems);
+        if permalink.split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..") || permalink.contains('\\') {
+          problems.push(format!("content.{}.permalink: '{}' must be a relative path such as {{year}}/{{month}}/{{slug}}", name, permalink));
+        }
+      }
+      if let Some(category_url) = &content.category_url {
+        unknown_tokens(&format!("content.{}.category_url", name), category_url, &CATEGORY_URL_TOKENS, &mut problems);
+        if !category_url.starts_with('/') {
+          problems.push(format!("content.{}.category_url: '{}' must be a site-relative URL starting with /", name, category_url));
+        }
+      }
+      if content.breadcrumbs && content.category_url.is_none() {
+        problems.push(format!("content.{}.breadcrumbs: requires category_url", name));
+      }
+      if content.breadcrumbs && self.site_url.is_none() {
+        problems.push(format!("content.{}.breadcrumbs: requires site_url", name));
+      }
+      if let Some(prefix) = &content.image_url_prefix {
+        if !prefix.starts_with('/') || prefix.split('/').any(|segment| segment == "..") {
+          problems.push(format!("content.{}.image_url_prefix: '{}' must be an absolute path without '..'", name, prefix));
+        }
+      }
+      // Components export the id as `<kind>Id`.
+      let id_field = all_types.iter().find(|ty| ty.name == *name).map(|ty| format!("{}Id", ty.kind));
+      for (field, computed) in &content.computed {
+        let key = format!("content.{}.computed.{}", name, field);
+        if !identifier.is_match(field) || RESERVED_FIELDS.contains(&field.as_str()) || id_field.as_ref() == Some(field) {
+          let reserved: Vec<&str> = RESERVED_FIELDS.iter().copied().chain(id_field.as_deref()).collect();
+          problems.push(format!("{}: must be a JavaScript identifier not already used by the page ({})", key, reserved.join(", ")));
+        }
+        let mut path = computed.from.split('.');
+        let valid_from = match path.next() {
+          Some("extra") => path.next().is_some_and(|segment| !segment.is_empty()),
+          Some(field) => FRONTMATTER_FIELDS.contains(&field),
+          None => false,
+        };
+        if !valid_from || computed.from.split('.').any(str::is_empty) {
+          problems.push(format!("{}.from: '{}' is not a frontmatter field ({}) or extra.<key>", key, computed.from, FRONTMATTER_FIELDS.join(", ")));
+        }
+        if computed.map.is_some() && computed.default.is_none() {
+          problems.push(format!("{}.map: needs a default for values it does not list", key));
+        }
+      }
+      if content.page_size == Some(0) {
+        problems.push(format!("content.{}.page_size: must be at least 1", name));
+      }
+      for (key, class) in [("title_class", &content.title_class), ("meta_class", &content.meta_class), ("content_class", &content.content_class)] {
+        if let Some(class) = class {
+          if !valid_class(class) {
+            problems.push(format!("content.{}.{}: '{}' is not a list of class names", name, key, class));
+          }
+        }
+      }
+      for (element, class) in &content.extra_classes {
+        if !CLASS_ELEMENTS.contains(&element.as_str()) {
+          problems.push(format!("content.{}.extra_classes.{}: expected one of {}", name, element, CLASS_ELEMENTS.join(", ")));
+        }
+        else if !valid_class(class) {
+          problems.push(format!("content.{}.extra_classes.{}: '{}' is not a list of class names", name, element, class));
+        }
+      }
+      if let Some(format) = &content.time_format {
+        if chrono::format::StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error) {
+          problems.push(format!("content.{}.time_format: '{}' is not a valid strftime format", name, format));
+        }
+      }
+      if let Some(format) = &content.revision_date_format {
+        if chrono::format::StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error) {
+          problems.push(format!("content.{}.revision_date_format: '{}' is not a valid strftime format", name, format));
+        }
+      }
+      for tag in &content.default_tags {
+        if normalize_tag(tag, &mut Vec::new()).ok().as_ref() != Some(tag) {
+          problems.push(format!("content.{}.default_tags: '{}' is not a tag such as lang/rust", name, tag));
+        }
+      }
+      for language in &content.inline_code_languages {
+        if language.is_empty() || !language.chars().all(|c| c.is_ascii_alphanumeric() || "+#_-".contains(c)) {
+          problems.push(format!("content.{}.inline_code_languages: '{}' is not a language name", name, language));
+        }
+      }
+      if content.words_per_minute == Some(0) {
+        problems.push(format!("content.{}.words_per_minute: must be at least 1", name));
+      }
+      if content.cjk_characters_per_minute == Some(0) {
+        problems.push(format!("content.{}.cjk_characters_per_minute: must be at least 1", name));
+      }
+      if content.code_background && content.prism_theme == PrismTheme::None {
+        problems.push(format!("content.{}.code_background: requires a prism_theme other than none", name));
+      }
+      let tags = content.allowed_tags.iter().flatten().chain(content.allowed_attributes.iter().flat_map(|attributes| attributes.keys()));
+      for tag in tags {
+        if crate::sanitize::FORBIDDEN_TAGS.contains(&tag.to_ascii_lowercase().as_str()) {
+          problems.push(format!("content.{}.allowed_tags: <{}> can never be allowed", name, tag));
+        }
+      }
+      if !content.sanitize && content.content_mode != ContentMode::Markup && content.allowed_tags.is_some() {
+        problems.push(format!("content.{}.allowed_tags: only used with sanitize = true or content_mode = \"markup\"", name));
+      }
+      if !content.sanitize && content.allowed_attributes.is_some() {
+        problems.push(format!("content.{}.allowed_attributes: only used with sanitize = true", name));
+      }
+    }
+    for name in content_types {
+      let content = self.content_config(name);
+      if content.footer.as_deref().is_some_and(|footer| footer.contains("{edit_url}")) && content.edit_url.is_none() {
+        problems.push(format!("footer for {} uses {{edit_url}} but no edit_url is set", name));
+      }
+    }
+    problems
+  }
+
+  /// The configuration a build uses as TOML, with every default written
+  /// out and the site-wide settings content types inherit filled in.
+  pub fn effective(&self) -> String {
+    let mut config = toml::Table::try_from(self).unwrap();
+    let mut tag_cloud = toml::Table::try_from(&self.tag_cloud).unwrap();
+    tag_cloud.insert("buckets".to_string(), toml::Value::try_from(self.tag_cloud.buckets()).unwrap());
+    config.insert("tag_cloud".to_string(), tag_cloud.into());
+    let mut sitemap = toml::Table::try_from(&self.sitemap).unwrap();
+    sitemap.insert("max_urls".to_string(), toml::Value::try_from(self.sitemap.max_urls()).unwrap());
+    config.insert("sitemap".to_string(), sitemap.into());
+    let mut feed = toml::Table::try_from(&self.feed).unwrap();
+    feed.insert("max_items".to_string(), toml::Value::try_from(self.feed.max_items()).unwrap());
+    config.insert("feed".to_string(), feed.into());
+    let mut plaintext = toml::Table::try_from(&self.plaintext).unwrap();
+    plaintext.insert("code".to_string(), self.plaintext.code().into());
+    plaintext.insert("math_placeholder".to_string(), self.plaintext.math_placeholder().into());
+    plaintext.insert("heading_prefix".to_string(), self.plaintext.heading_prefix().into());
+    plaintext.insert("image_alt".to_string(), self.plaintext.image_alt().into());
+    config.insert("plaintext".to_string(), plaintext.into());
+    let content_types: Vec<ContentType> = self.content_types()
+      .into_iter()
+      .map(|mut content_type| {
+        content_type.image_url_prefix = content_type.image_url_prefix(&ContentConfig::default());
+        content_type.data_export = content_type.data_export().to_string();
+        content_type
+      })
+      .collect();
+    config.insert("content_types".to_string(), toml::Value::try_from(&content_types).unwrap());
+    let content: BTreeMap<&str, ContentConfig> = content_types
+      .iter()
+      .map(|content_type| {
+        let mut content = self.content_config(&content_type.name).resolved();
+        content.image_url_prefix = Some(content_type.image_url_prefix(&content));
+        (content_type.name.as_str(), content)
+      })
+      .collect();
+    config.insert("content".to_string(), toml::Value::try_from(content).unwrap());
+    toml::to_string(&config).unwrap()
+  }
+
+  /// Settings for a content type, with site-wide values filled in where the
+  /// type does not set its own.
+  pub fn content_config(&self, name: &str) -> ContentConfig {
+    let mut content = self.content.get(name).cloned().unwrap_or_default();
+    content.footer = content.footer.or_else(|| self.footer.clone());
+    content.edit_url = content.edit_url.or_else(|| self.edit_url.clone());
+    content
+  }
+}
+
+impl ContentConfig {
+  pub fn layout(&self) -> &[Block] {
+    self.layout.as_deref().unwrap_or(&DEFAULT_LAYOUT)
+  }
+
+  pub fn meta(&self) -> &[MetaItem] {
+    self.meta.as_deref().unwrap_or(&DEFAULT_META)
+  }
+
+  pub fn related_limit(&self) -> usize {
+    self.related_limit.unwrap_or(DEFAULT_RELATED_LIMIT)
+  }
+
+  /// Classes of the `title`, `meta` or `content` element.
+  pub fn class(&self, element: &str) -> String {
+    let base = match element {
+      "title" => self.title_class.as_deref(),
+      "meta" => self.meta_class.as_deref(),
+      _ => self.content_class.as_deref(),
+    };
+    let mut class = base.unwrap_or(element).to_string();
+    if let Some(extra) = self.extra_classes.get(element) {
+      class = format!("{} {}", class, extra).trim().to_string();
+    }
+    class
+  }
+
+  pub fn words_per_minute(&self) -> u32 {
+    self.words_per_minute.unwrap_or(DEFAULT_WORDS_PER_MINUTE)
+  }
+
+  pub fn cjk_characters_per_minute(&self) -> u32 {
+    self.cjk_characters_per_minute.unwrap_or(DEFAULT_CJK_CHARACTERS_PER_MINUTE)
+  }
+
+  pub fn microdata(&self) -> bool {
+    self.microdata.unwrap_or(true)
+  }
+
+  pub fn discussion_template(&self) -> &str {
+    self.discussion_template.as_deref().unwrap_or(DEFAULT_DISCUSSION_TEMPLATE)
+  }
+
+  pub fn reference_format(&self) -> &str {
+    self.reference_format.as_deref().unwrap_or(DEFAULT_REFERENCE_FORMAT)
+  }
+
+  pub fn revision_badge(&self) -> &str {
+    self.revision_badge.as_deref().unwrap_or(DEFAULT_REVISION_BADGE)
+  }
+
+  pub fn revision_date_format(&self) -> &str {
+    self.revision_date_format.as_deref().unwrap_or(DEFAULT_REVISION_DATE_FORMAT)
+  }
+
+  pub fn permalink(&self) -> &str {
+    self.permalink.as_deref().unwrap_or(DEFAULT_PERMALINK)
+  }
+
+  /// This config with the defaults of unset keys written out, leaving out
+  /// those that only apply together with another key.
+  fn resolved(mut self) -> ContentConfig {
+    self.layout = Some(self.layout().to_vec());
+    if self.layout().contains(&Block::Meta) {
+      self.meta = Some(self.meta().to_vec());
+    }
+    if self.comments_default.is_some() {
+      self.discussion_template = Some(self.discussion_template().to_string());
+    }
+    self.microdata = Some(self.microdata());
+    self.related_limit = Some(self.related_limit());
+    self.words_per_minute = Some(self.words_per_minute());
+    self.cjk_characters_per_minute = Some(self.cjk_characters_per_minute());
+    self.reference_format = Some(self.reference_format().to_string());
+    self.revision_badge = Some(self.revision_badge().to_string());
+    self.revision_date_format = Some(self.revision_date_format().to_string());
+    self.permalink = Some(self.permalink().to_string());
+    self
+  }
+}
+
+/// Elements whose class can be configured.
+const CLASS_ELEMENTS: [&str; 3] = ["title", "meta", "content"];
+
+/// Whether `class` is one or more class names separated by spaces, with
+/// nothing that would end the attribute or start a Svelte expression.
+fn valid_class(class: &str) -> bool {
+  !class.trim().is_empty() && !class.contains(['"', '\'', '<', '>', '{', '}', '=', '&'])
+}
+
+/// Makes `path` absolute, resolving symlinks in the part that exists and
+/// `..` in the rest, which may not have been created yet.
+fn resolve_path(path: &Path) -> PathBuf {
+  let mut resolved = PathBuf::new();
+  for component in std::env::current_dir().unwrap_or_default().join(path).components() {
+    match component {
+      Component::ParentDir => {
+        resolved.pop();
+      }
+      Component::CurDir => {}
+      component => {
+        resolved.push(component);
+        if let Ok(canonical) = resolved.canonicalize() {
+          resolved = canonical;
+        }
+      }
+    }
+  }
+  resolved
+}
+
+/// Problems with the configured content types, all reported at once:
+/// names, kinds and directories that are unusable or used twice, and input
+/// directories that do not exist.
+fn check_content_types(content_types: &[ContentType], problems: &mut Vec<String>) {
+  if content_types.is_empty() {
+    problems.push("content_types: must list at least one content type".to_string());
+  }
+  let name_pattern = regex::Regex::new(r"^[a-z0-9][a-z0-9_-]*$").unwrap();
+  let kind_pattern = regex::Regex::new(r"^[a-z][A-Za-z0-9]*$").unwrap();
+  let export_pattern = regex::Regex::new(r"^[A-Za-z_$][A-Za-z0-9_$]*$").unwrap();
+  for (i, content_type) in content_types.iter().enumerate() {
+    let earlier = &content_types[..i];
+    let name = &content_type.name;
+    if !name_pattern.is_match(name) {
+      problems.push(format!("content_types: name '{}' must be lowercase letters, digits, '-' and '_'", name));
+    }
+    if earlier.iter().any(|other| other.name == *name) {
+      problems.push(format!("content_types: name '{}' is used more than once", name));
+    }
+    if !kind_pattern.is_match(&content_type.kind) {
+      problems.push(format!("content_types: {}: kind '{}' must be a word such as article, it names the exports", name, content_type.kind));
+    }
+    if !export_pattern.is_match(content_type.data_export()) {
+      problems.push(format!("content_types: {}: data_export '{}' must be a JavaScript identifier; set data_export", name, content_type.data_export()));
+    }
+    if let Some(other) = earlier.iter().find(|other| other.input_dir == content_type.input_dir) {
+      problems.push(format!("content_types: {}: input_dir {} is also the input_dir of {}", name, content_type.input_dir, other.name));
+    }
+    else if !Path::new(&content_type.input_dir).is_dir() {
+      problems.push(format!("content_types: {}: input_dir {} does not exist", name, content_type.input_dir));
+    }
+    if let Some(other) = earlier.iter().find(|other| other.output_dir == content_type.output_dir) {
+      problems.push(format!("content_types: {}: output_dir {} is also the output_dir of {}", name, content_type.output_dir, other.name));
+    }
+    let prefix = &content_type.image_url_prefix;
+    if !prefix.is_empty() && (!prefix.starts_with('/') || prefix.split('/').any(|segment| segment == "..")) {
+      problems.push(format!("content_types: {}: image_url_prefix '{}' must be an absolute path without '..'", name, prefix));
+    }
+  }
+}
+
+fn has_duplicates<T: PartialEq>(items: &[T]) -> bool {
+  items.iter().enumerate().any(|(i, item)| items[..i].contains(item))
+}
+
+/// Parses `text`, collecting every unknown key rather than stopping at the
+/// first: each one is commented out, which keeps the line numbers of the
+/// rest, and the text is parsed again.
+fn parse(text: &str) -> Result<(Config, Vec<String>), Vec<String>> {
+  let mut text = text.to_string();
+  let mut unknown = Vec::new();
+  let messages = |mut unknown: Vec<(usize, String)>| {
+    unknown.sort();
+    unknown.into_iter().map(|(_, message)| message).collect()
+  };
+  loop {
+    let e = match toml::from_str::<Config>(&text) {
+      Ok(config) => return Ok((config, messages(unknown))),
+      Err(e) => e,
+    };
+    let line = e.span().map_or(0, |span| text[..span.start].matches('\n').count());
+    let rest = e.span().and_then(|span| without_item(&text, span.start));
+    match (unknown_key(&text, &e), rest) {
+      (Some(message), Some(rest)) => {
+        unknown.push((line, message));
+        text = rest;
+      }
+      (message, _) => {
+        unknown.push((line, message.unwrap_or_else(|| e.to_string())));
+        return Err(messages(unknown));
+      }
+    }
+  }
+}
+
+/// `text` with the item on the line of `offset` commented out: a table
+/// header with the keys below it, or a key with its value, which may span
+/// several lines.
+fn without_item(text: &str, offset: usize) -> Option<String> {
+  let lines: Vec<&str> = text.split_inclusive('\n').collect();
+  let first = text[..offset].matches('\n').count();
+  let comment = |end: usize| -> String {
+    lines.iter()
+      .enumerate()
+      .map(|(i, line)| if (first..end).contains(&i) { format!("#{}", line) } else { line.to_string() })
+      .collect()
+  };
+  let is_header = |line: &str| line.trim_start().starts_with('[');
+  let end = if is_header(lines.get(first)?) {
+    first + 1 + lines[first + 1..].iter().take_while(|line| !is_header(line)).count()
+  }
+  else {
+    (first + 1..=lines.len()).find(|end| toml::from_str::<toml::Table>(&comment(*end)).is_ok())?
+  };
+  Some(comment(end))
+}
+
+/// Describes an unknown key error with the full path of the key, its line
+/// and the closest known key, or every known key when none is close.
+fn unknown_key(text: &str, e: &toml::de::Error) -> Option<String> {
+  let unknown = regex::Regex::new(r"^unknown field `([^`]*)`, (?:expected (.*)|there are no fields)$").unwrap();
+  let caps = unknown.captures(e.message().trim())?;
+  let expected: Vec<&str> = caps.get(2)
+    .map(|list| list.as_str().split('`').skip(1).step_by(2).collect())
+    .unwrap_or_default();
+  let span = e.span()?;
+  let line = text[..span.start].matches('\n').count() + 1;
+  let header = regex::Regex::new(r"^\s*\[\[?\s*([^\]]+?)\s*\]\]?").unwrap();
+  let line_start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+  let table = text[..line_start].lines()
+    .rev()
+    .find_map(|line| header.captures(line).map(|caps| caps[1].to_string()));
+  // Dotted keys: the span only covers the last part.
+  let key = match &text[line_start..span.start] {
+    prefix if prefix.trim_start().starts_with('[') => text[line_start..span.end].trim_start_matches([' ', '\t', '[']).to_string(),
+    prefix if prefix.contains('=') => qualified(table, text[span].trim()),
+    _ => qualified(table, text[line_start..span.end].trim()),
+  };
+
+  let name = &caps[1];
+  let closest = expected.iter()
+    .map(|candidate| (edit_distance(name, candidate), *candidate))
+    .min()
+    .filter(|(distance, _)| *distance <= (name.chars().count() / 3).max(2));
+  let hint = match closest {
+    Some((_, candidate)) => format!("did you mean `{}`?", candidate),
+    None if expected.is_empty() => "the table takes no keys".to_string(),
+    None => format!("expected one of {}", expected.join(", ")),
+  };
+  Some(format!("unknown key {} on line {}, {}", key, line, hint))
+}
+
+/// `key` under the table it was written in, if any.
+fn qualified(table: Option<String>, key: &str) -> String {
+  match table {
+    Some(table) => format!("{}.{}", table, key),
+    None => key.to_string(),
+  }
+}
+
+/// Number of single character insertions, deletions and substitutions
+/// turning `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut previous: Vec<usize> = (0..=b.len()).collect();
+  for (i, a) in a.chars().enumerate() {
+    let mut current = vec![i + 1];
+    for (j, b) in b.iter().enumerate() {
+      let substitution = previous[j] + usize::from(a != *b);
+      current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+    }
+    previous = current;
+  }
+  previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn missing_config_is_an_error_only_when_given() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(DEFAULT_CONFIG_PATH);
+    assert!(Config::load(&path, false).is_ok());
+    let e = Config::load(&path, true).err().unwrap();
+    assert!(e.starts_with(&format!("Error reading config {}", path.display())), "{}", e);
+  }
+
+  #[test]
+  fn broken_config_reports_every_unknown_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(DEFAULT_CONFIG_PATH);
+    let text = "statc_root = \"static\"\nurl_styl = \"slash\"\nextra = [\n  \"a\",\n]\n\n[content.articles]\ntime_formt = \"%H\"\n\n[feeed]\nenabled = true\n\n[sitemap]\nenabld = true\n";
+    std::fs::write(&path, text).unwrap();
+    let e = Config::load(&path, true).err().unwrap();
+    let lines: Vec<&str> = e.lines().skip(1).map(str::trim).collect();
+    assert_eq!(lines.len(), 6, "{}", e);
+    assert_eq!(lines[0], "unknown key statc_root on line 1, did you mean `static_root`?");
+    assert_eq!(lines[1], "unknown key url_styl on line 2, did you mean `url_style`?");
+    assert!(lines[2].starts_with("unknown key extra on line 3, expected one of "), "{}", lines[2]);
+    assert_eq!(lines[3], "unknown key content.articles.time_formt on line 8, did you mean `time_format`?");
+    assert_eq!(lines[4], "unknown key feeed on line 10, did you mean `feed`?");
+    assert_eq!(lines[5], "unknown key sitemap.enabld on line 14, did you mean `enabled`?");
+  }
+
+  #[test]
+  fn broken_config_reports_every_problem() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(DEFAULT_CONFIG_PATH);
+    let file = dir.path().join("staticfile");
+    std::fs::write(&file, "").unwrap();
+    let missing = dir.path().join("nope");
+    let text = format!(
+      "statc_root = 1\nstatic_root = {:?}\nassets_dir = {:?}\n\n[content.articles]\nimage_url_prefix = \"../img\"\ntime_format = \"%Q\"\n",
+      file.display().to_string(),
+      missing.display().to_string(),
+    );
+    std::fs::write(&path, text).unwrap();
+    let e = Config::load(&path, true).err().unwrap();
+    assert!(e.starts_with(&format!("Invalid config {}:", path.display())), "{}", e);
+    for problem in [
+      "unknown key statc_root on line 1, did you mean `static_root`?".to_string(),
+      format!("static_root: {} exists and is not a directory", file.display()),
+      format!("assets_dir: {} is not a directory", missing.display()),
+      "content.articles.image_url_prefix: '../img' must be an absolute path without '..'".to_string(),
+      "content.articles.time_format: '%Q' is not a valid strftime format".to_string(),
+    ] {
+      assert!(e.lines().any(|line| line.trim() == problem), "missing {:?} in {}", problem, e);
+    }
+  }
+
+  #[test]
+  fn parse_error_keeps_the_unknown_keys_before_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(DEFAULT_CONFIG_PATH);
+    std::fs::write(&path, "statc_root = \"static\"\nurl_style = 5\n").unwrap();
+    let e = Config::load(&path, true).err().unwrap();
+    assert!(e.starts_with(&format!("Error parsing config {}: unknown key statc_root on line 1", path.display())), "{}", e);
+    assert!(e.contains("line 2"), "{}", e);
+  }
+
+  #[test]
+  fn default_tags_must_be_normalized() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(DEFAULT_CONFIG_PATH);
+    std::fs::write(&path, "[content.articles]\ndefault_tags = [\"lang/rust\", \"#svelte\", \"a//b\"]\n").unwrap();
+    let e = Config::load(&path, true).err().unwrap();
+    let problems: Vec<&str> = e.lines().skip(1).map(str::trim).collect();
+    assert_eq!(problems, [
+      "content.articles.default_tags: '#svelte' is not a tag such as lang/rust",
+      "content.articles.default_tags: 'a//b' is not a tag such as lang/rust",
+    ]);
+  }
+}
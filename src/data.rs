@@ -0,0 +1,382 @@
+use chrono::Datelike;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+use crate::config::AuthorsMode;
+use crate::entry::{Entry, ISO_DATE_FORMAT};
+use crate::frontmatter::Author;
+use crate::output;
+use crate::site::ARTICLE_KIND;
+use crate::slug::tag_slug;
+
+/// Module, next to the data file, holding every author once with
+/// `authors = "normalized"`.
+pub const AUTHORS_MODULE: &str = "authors";
+
+/// File stem of the data file of `kind`, such as `articleData`.
+fn data_file_stem(kind: &str) -> String {
+  format!("{}Data", kind)
+}
+
+/// How the entries of a content type are written to its data files.
+pub struct DataFormat<'a> {
+  pub kind: &'a str,
+  /// Name the entries are exported as, such as `articles`.
+  pub export: &'a str,
+  pub authors: AuthorsMode,
+  /// Added to every entry as `locale`; dates are ISO either way.
+  pub locale: Option<&'a str>,
+  /// First lines of every file.
+  pub header: &'a str,
+  /// Declare an interface for the entries and type the export with it.
+  pub typed: bool,
+}
+
+/// Writes `<kind>Data.ts`, such as `articleData.ts`.
+pub fn generate_data(entries: &[Entry], output_dir: &Path, format: &DataFormat) -> std::io::Result<()> {
+  let output_path = output_dir.join(format!("{}.ts", data_file_stem(format.kind)));
+  write_entries(&output_path, entries.iter().collect(), format)
+}
+
+/// Writes the entries, newest first, in chunks of `page_size` as
+/// `articleData.page-<n>.ts`, plus `articleData.pages.ts` with the totals.
+/// Chunks left over from a build with more pages are removed.
+pub fn generate_pages(entries: &[Entry], output_dir: &Path, page_size: usize, format: &DataFormat) -> std::io::Result<()> {
+  let file_stem = data_file_stem(format.kind);
+  let mut sorted: Vec<&Entry> = entries.iter().collect();
+  sorted.sort_by(|a, b| b.date.cmp(&a.date).then(a.frontmatter.slug.cmp(&b.frontmatter.slug)));
+
+  let page_count = sorted.len().div_ceil(page_size);
+  for (i, page) in sorted.chunks(page_size).enumerate() {
+    let output_path = output_dir.join(format!("{}.page-{}.ts", file_stem, i + 1));
+    write_entries(&output_path, page.to_vec(), format)?;
+  }
+  for stale in page_count + 1.. {
+    let path = output_dir.join(format!("{}.page-{}.ts", file_stem, stale));
+    if !path.exists() {
+      break;
+    }
+    if output::is_generated(&path) {
+      std::fs::remove_file(&path)?;
+    }
+  }
+
+  let output_path = output_dir.join(format!("{}.pages.ts", file_stem));
+  output::write_generated(&output_path, &format!(
+    "{}\nexport const total = {};\nexport const pageSize = {};\nexport const pageCount = {};\n",
+    format.header,
+    sorted.len(),
+    page_size,
+    page_count
+  ))
+}
+
+fn write_entries(output_path: &Path, entries: Vec<&Entry>, format: &DataFormat) -> std::io::Result<()> {
+  let DataFormat { kind, export, authors, locale, header, typed } = *format;
+  let mut module = Vec::new();
+  writeln!(module, "{}", header)?;
+
+  if typed {
+    let name = interface_name(kind);
+    write!(module, "{}", entry_interface(&name, &entries, format))?;
+    writeln!(module, "export const {}: {}[] = [", export, name)?;
+  }
+  else {
+    writeln!(module, "export const {} = [", export)?;
+  }
+  for entry in entries {
+    let frontmatter = &entry.frontmatter;
+    writeln!(module, "  {{")?;
+    writeln!(module, "    kind: {},", output::js_string(kind))?;
+    if let Some(id) = &entry.id {
+      writeln!(module, "    id: '{}',", id)?;
+    }
+    writeln!(module, "    slug: '{}',", frontmatter.slug)?;
+    writeln!(module, "    url: '{}',", entry.url)?;
+    writeln!(module, "    title: {},", output::js_string(&frontmatter.title))?;
+    if authors == AuthorsMode::Normalized {
+      let ids: Vec<String> = frontmatter.authors.iter().map(|author| output::js_string(&author.id())).collect();
+      writeln!(module, "    authors: [{}],", ids.join(", "))?;
+    }
+    else {
+      writeln!(module, "    authors: [")?;
+      for author in &frontmatter.authors {
+        writeln!(module, "      {},", author_object(author))?;
+      }
+      writeln!(module, "    ],")?;
+    }
+    writeln!(module, "    date: '{}',", entry.date.format(ISO_DATE_FORMAT))?;
+    if let Some(datetime) = &entry.datetime {
+      writeln!(module, "    datetime: '{}',", datetime.to_rfc3339())?;
+    }
+    if kind != ARTICLE_KIND {
+      match &entry.date_end {
+        Some(date_end) => writeln!(module, "    date_end: '{}',", date_end.format(ISO_DATE_FORMAT))?,
+        None => writeln!(module, "    date_end: null,")?,
+      }
+      writeln!(module, "    ongoing: {},", frontmatter.ongoing)?;
+    }
+    if let Some(locale) = locale {
+      writeln!(module, "    locale: {},", output::js_string(locale))?;
+    }
+    writeln!(module, "    tags: {:?},", frontmatter.tags)?;
+    writeln!(module, "    checksum: '{}',", entry.checksum)?;
+    writeln!(module, "    reading_time: {},", entry.reading_time)?;
+    if let Some(comments) = entry.comments {
+      writeln!(module, "    comments: {},", comments)?;
+      writeln!(module, "    discussion_url: {},", serde_json::to_string(&frontmatter.discussion_url).unwrap())?;
+    }
+    for (name, value) in &entry.computed {
+      writeln!(module, "    {}: {},", name, value)?;
+    }
+    writeln!(module, "    related: {},", serde_json::to_string(&entry.related).unwrap())?;
+    writeln!(module, "    tag_slugs: {}", serde_json::to_string(&entry.tag_slugs).unwrap())?;
+    writeln!(module, "  }},")?;
+  }
+  writeln!(module, "];")?;
+
+  output::write_generated(output_path, &String::from_utf8(module).unwrap())
+}
+
+/// `Article` for the `article` kind.
+fn interface_name(kind: &str) -> String {
+  let mut chars = kind.chars();
+  chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// The interface of the entries `write_entries` writes, with the fields it
+/// writes for `entries`. Computed fields are typed by the values they take.
+fn entry_interface(name: &str, entries: &[&Entry], format: &DataFormat) -> String {
+  let mut fields = vec![
+    ("kind", output::js_string(format.kind)),
+  ];
+  // Drafts built with `--drafts` have no id.
+  let with_id = entries.iter().filter(|entry| entry.id.is_some()).count();
+  if with_id > 0 {
+    fields.push((if with_id == entries.len() { "id" } else { "id?" }, "string".to_string()));
+  }
+  fields.push(("slug", "string".to_string()));
+  fields.push(("url", "string".to_string()));
+  fields.push(("title", "string".to_string()));
+  let authors = match format.authors {
+    AuthorsMode::Inline => "{ name: string; url: string | null }[]",
+    AuthorsMode::Normalized => "string[]",
+  };
+  fields.push(("authors", authors.to_string()));
+  fields.push(("date", "string".to_string()));
+  if entries.iter().any(|entry| entry.datetime.is_some()) {
+    fields.push(("datetime?", "string".to_string()));
+  }
+  if format.kind != ARTICLE_KIND {
+    fields.push(("date_end", "string | null".to_string()));
+    fields.push(("ongoing", "boolean".to_string()));
+  }
+  if format.locale.is_some() {
+    fields.push(("locale", "string".to_string()));
+  }
+  fields.push(("tags", "string[]".to_string()));
+  fields.push(("checksum", "string".to_string()));
+  fields.push(("reading_time", "number".to_string()));
+  if entries.iter().any(|entry| entry.comments.is_some()) {
+    fields.push(("comments", "boolean".to_string()));
+    fields.push(("discussion_url", "string | null".to_string()));
+  }
+  let mut computed: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+  for (field, value) in entries.iter().flat_map(|entry| &entry.computed) {
+    computed.entry(field).or_default().insert(ts_type(value));
+  }
+  for (field, types) in computed {
+    fields.push((field, types.into_iter().collect::<Vec<_>>().join(" | ")));
+  }
+  fields.push(("related", "{ slug: string; title: string }[]".to_string()));
+  fields.push(("tag_slugs", "Record<string, string>".to_string()));
+
+  let mut interface = format!("export interface {} {{\n", name);
+  for (field, field_type) in fields {
+    interface.push_str(&format!("  {}: {};\n", field, field_type));
+  }
+  interface.push_str("}\n\n");
+  interface
+}
+
+fn ts_type(value: &serde_json::Value) -> &'static str {
+  match value {
+    serde_json::Value::Null => "null",
+    serde_json::Value::Bool(_) => "boolean",
+    serde_json::Value::Number(_) => "number",
+    serde_json::Value::String(_) => "string",
+    serde_json::Value::Array(_) => "unknown[]",
+    serde_json::Value::Object(_) => "Record<string, unknown>",
+  }
+}
+
+fn author_object(author: &Author) -> String {
+  let url = author.url.as_deref().map(output::js_string).unwrap_or_else(|| "null".to_string());
+  format!("{{ name: {}, url: {} }}", output::js_string(&author.name), url)
+}
+
+/// `authors.ts`: every author of the entries by id, with types for both
+/// shapes a data file entry may list authors in.
+pub fn authors_module(entries: &[Entry], header: &str) -> String {
+  let authors: BTreeMap<String, &Author> = entries.iter()
+    .flat_map(|entry| &entry.frontmatter.authors)
+    .map(|author| (author.id(), author))
+    .collect();
+  let mut module = format!("{}\n", header);
+  module.push_str("export type Author = { name: string; url: string | null };\n");
+  module.push_str("/** An author in a data file entry: an object, or an id into `authors`. */\n");
+  module.push_str("export type AuthorRef = Author | string;\n\n");
+  module.push_str("export const authors: Record<string, Author> = {\n");
+  for (id, author) in authors {
+    module.push_str(&format!("  {}: {},\n", output::js_string(&id), author_object(author)));
+  }
+  module.push_str("};\n");
+  module
+}
+
+/// Adds the entries' tags to `counts`, by the tag as normalized.
+pub fn count_tags(counts: &mut BTreeMap<String, usize>, entries: &[Entry]) {
+  for entry in entries {
+    for tag in entry.tag_slugs.keys() {
+      *counts.entry(tag.clone()).or_default() += 1;
+    }
+  }
+}
+
+/// Writes the `tagCloud` data file: every tag with its entry count, slug and
+/// a `size` from 1 to `buckets` scaled between the rarest and most common
+/// tag, most common first.
+pub fn generate_tag_cloud(counts: &BTreeMap<String, usize>, path: &Path, buckets: usize, header: &str) -> std::io::Result<()> {
+  let mut tags: Vec<(&String, usize)> = counts.iter().map(|(tag, count)| (tag, *count)).collect();
+  tags.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+  let min = tags.last().map_or(0, |tag| tag.1);
+  let max = tags.first().map_or(0, |tag| tag.1);
+
+  let mut module = format!("{}\nexport const tagCloud = [\n", header);
+  for (tag, count) in tags {
+    let size = if max == min { buckets } else { 1 + (count - min) * (buckets - 1) / (max - min) };
+    module.push_str(&format!(
+      "  {{ tag: {}, count: {}, slug: {}, size: {} }},\n",
+      output::js_string(tag),
+      count,
+      output::js_string(&tag_slug(tag)),
+      size
+    ));
+  }
+  module.push_str("];\n");
+  output::write_generated(path, &module)
+}
+
+struct TagNode {
+  name: String,
+  path: String,
+  slug: String,
+  parent: Option<String>,
+  children: BTreeSet<String>,
+  /// Slugs of the entries tagged with this tag or any of its descendants.
+  entries: BTreeSet<String>,
+}
+
+/// Writes `tagData.ts` with every tag both as a flat list and as a tree.
+/// Parent tags count the union of their descendants' entries.
+pub fn generate_tag_data(entries: &[Entry], output_dir: &Path, header: &str) -> std::io::Result<()> {
+  let mut nodes: BTreeMap<String, TagNode> = BTreeMap::new();
+  for entry in entries {
+    for tag in entry.tag_slugs.keys() {
+      let segments: Vec<&str> = tag.split('/').collect();
+      for depth in 1..=segments.len() {
+        let path = segments[..depth].join("/");
+        let parent = (depth > 1).then(|| segments[..depth - 1].join("/"));
+        if let Some(parent) = &parent {
+          nodes.get_mut(parent).unwrap().children.insert(path.clone());
+        }
+        nodes.entry(path.clone())
+          .or_insert_with(|| TagNode {
+            name: segments[depth - 1].to_string(),
+            slug: tag_slug(&path),
+            path,
+            parent,
+            children: BTreeSet::new(),
+            entries: BTreeSet::new(),
+          })
+          .entries
+          .insert(entry.frontmatter.slug.clone());
+      }
+    }
+  }
+
+  fn node_json(node: &TagNode) -> serde_json::Value {
+    json!({
+      "name": node.name,
+      "path": node.path,
+      "slug": node.slug,
+      "parent": node.parent,
+      "children": node.children,
+      "count": node.entries.len(),
+    })
+  }
+
+  fn tree_json(nodes: &BTreeMap<String, TagNode>, node: &TagNode) -> serde_json::Value {
+    let mut value = node_json(node);
+    value["children"] = node.children.iter().map(|child| tree_json(nodes, &nodes[child])).collect();
+    value
+  }
+
+  let output_path = output_dir.join("tagData.ts");
+  let mut module = Vec::new();
+  writeln!(module, "{}", header)?;
+  writeln!(module, "export const tags = [")?;
+  for node in nodes.values() {
+    writeln!(module, "  {},", node_json(node))?;
+  }
+  writeln!(module, "];")?;
+  let roots: Vec<serde_json::Value> = nodes.values()
+    .filter(|node| node.parent.is_none())
+    .map(|node| tree_json(&nodes, node))
+    .collect();
+  writeln!(module, "export const tagTree = {};", serde_json::to_string_pretty(&roots).unwrap())?;
+
+  output::write_generated(&output_path, &String::from_utf8(module).unwrap())
+}
+
+/// Writes `archiveData.ts`, grouping entries by year and month, newest first.
+/// Drafts and hidden entries, present when built with `--drafts`, are left
+/// out.
+pub fn generate_archive_data(entries: &[Entry], output_dir: &Path, header: &str) -> std::io::Result<()> {
+  let mut years: BTreeMap<i32, BTreeMap<u32, Vec<&Entry>>> = BTreeMap::new();
+  for entry in entries.iter().filter(|entry| entry.frontmatter.held_back().is_none()) {
+    years.entry(entry.date.year())
+      .or_default()
+      .entry(entry.date.month())
+      .or_default()
+      .push(entry);
+  }
+
+  let output_path = output_dir.join("archiveData.ts");
+  let mut module = Vec::new();
+  writeln!(module, "{}", header)?;
+  writeln!(module, "export const archive = [")?;
+  for (year, months) in years.iter_mut().rev() {
+    writeln!(module, "  {{ year: {}, months: [", year)?;
+    for (month, posts) in months.iter_mut().rev() {
+      posts.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.frontmatter.slug.cmp(&b.frontmatter.slug)));
+      writeln!(module, "    {{ month: {}, posts: [", month)?;
+      for post in posts.iter() {
+        let post_json = json!({
+          "slug": post.frontmatter.slug,
+          "url": post.url,
+          "title": post.frontmatter.title,
+          "date": post.date.format(ISO_DATE_FORMAT).to_string(),
+        });
+        writeln!(module, "      {},", post_json)?;
+      }
+      writeln!(module, "    ] }},")?;
+    }
+    writeln!(module, "  ] }},")?;
+  }
+  writeln!(module, "];")?;
+
+  output::write_generated(&output_path, &String::from_utf8(module).unwrap())
+}
@@ -0,0 +1,281 @@
+//! Finds syntax left over from other static site generators, and markdown
+//! that would not render as written, in the markdown sources and optionally
+//! rewrites it. Never runs as part of a build.
+
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::markdown::BLOCK_TAGS;
+use crate::site;
+use crate::walk;
+
+/// A problem on one line, with the replacement line when it can be fixed
+/// mechanically.
+struct Finding {
+  line: usize,
+  message: String,
+  fix: Option<String>,
+}
+
+struct Detector {
+  name: &'static str,
+  detect: fn(&[&str]) -> Vec<Finding>,
+}
+
+const DETECTORS: [Detector; 5] = [
+  Detector { name: "jekyll-highlight", detect: jekyll_highlight },
+  Detector { name: "hugo-figure", detect: hugo_figure },
+  Detector { name: "hugo-shortcode", detect: hugo_shortcode },
+  Detector { name: "missing-reference", detect: missing_reference },
+  Detector { name: "html-block-markdown", detect: html_block_markdown },
+];
+
+pub fn detector_names() -> Vec<&'static str> {
+  DETECTORS.iter().map(|detector| detector.name).collect()
+}
+
+pub struct DoctorOptions {
+  /// Compute fixes and show them as diffs.
+  pub fix: bool,
+  /// Write fixes back to the source files instead of showing diffs.
+  pub write: bool,
+  /// Detectors to leave out.
+  pub skip: BTreeSet<String>,
+}
+
+pub fn doctor(config: &Config, options: &DoctorOptions) -> std::io::Result<()> {
+  let mut total = 0;
+  for content_type in config.content_types() {
+    let markdown_files = walk::source_files(Path::new(&content_type.input_dir), config.respect_gitignore)
+      .into_iter()
+      .filter(|path| walk::has_extension(path, &config.markdown_extensions));
+    for path in markdown_files {
+      // Fixes are written back, so a lossy decode is never acceptable here.
+      if let Some(source) = site::read_source(&path, false) {
+        total += check_file(&path, &source, config, options)?;
+      }
+    }
+  }
+  info!("{} problems found", total);
+  Ok(())
+}
+
+fn check_file(path: &Path, source: &str, config: &Config, options: &DoctorOptions) -> std::io::Result<usize> {
+  let lines: Vec<&str> = source.lines().collect();
+  let mut fixes: Vec<Option<String>> = vec![None; lines.len()];
+  let mut count = 0;
+
+  for detector in &DETECTORS {
+    if options.skip.contains(detector.name) || config.doctor.skip.iter().any(|name| name == detector.name) {
+      continue;
+    }
+    for finding in (detector.detect)(&lines) {
+      count += 1;
+      warn!("{}:{}: [{}] {}", path.display(), finding.line + 1, detector.name, finding.message);
+      if finding.fix.is_some() {
+        fixes[finding.line] = finding.fix;
+      }
+    }
+  }
+
+  if !options.fix || fixes.iter().all(Option::is_none) {
+    return Ok(count);
+  }
+  if options.write {
+    let mut fixed = String::with_capacity(source.len());
+    for (i, line) in source.split_inclusive('\n').enumerate() {
+      match &fixes[i] {
+        Some(fix) => {
+          let content = line.trim_end_matches(['\r', '\n']);
+          fixed.push_str(fix);
+          fixed.push_str(&line[content.len()..]);
+        }
+        None => fixed.push_str(line),
+      }
+    }
+    std::fs::write(path, fixed)?;
+    info!("Fixed {}", path.display());
+  }
+  else {
+    println!("--- {}", path.display());
+    println!("+++ {}", path.display());
+    for (i, fix) in fixes.iter().enumerate() {
+      if let Some(fix) = fix {
+        println!("@@ line {} @@", i + 1);
+        println!("-{}", lines[i]);
+        for line in fix.split('\n') {
+          println!("+{}", line);
+        }
+      }
+    }
+  }
+  Ok(count)
+}
+
+/// Indexes of the lines outside fenced code blocks.
+fn prose_lines(lines: &[&str]) -> Vec<usize> {
+  let mut fence: Option<&str> = None;
+  let mut prose = Vec::new();
+  for (i, line) in lines.iter().enumerate() {
+    let trimmed = line.trim_start();
+    if let Some(marker) = fence {
+      if trimmed.starts_with(marker) {
+        fence = None;
+      }
+      continue;
+    }
+    if let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+      fence = Some(marker);
+      continue;
+    }
+    prose.push(i);
+  }
+  prose
+}
+
+/// `{% highlight rust %}` ... `{% endhighlight %}` becomes a fenced block.
+fn jekyll_highlight(lines: &[&str]) -> Vec<Finding> {
+  let start = Regex::new(r"^\s*\{%-?\s*highlight\s+([\w+#-]+)[^%]*-?%\}\s*$").unwrap();
+  let end = Regex::new(r"^\s*\{%-?\s*endhighlight\s*-?%\}\s*$").unwrap();
+  let mut findings = Vec::new();
+  for i in prose_lines(lines) {
+    if let Some(caps) = start.captures(lines[i]) {
+      findings.push(Finding {
+        line: i,
+        message: "Jekyll highlight block, use a fenced code block".to_string(),
+        fix: Some(format!("```{}", &caps[1])),
+      });
+    }
+    else if end.is_match(lines[i]) {
+      findings.push(Finding {
+        line: i,
+        message: "end of Jekyll highlight block".to_string(),
+        fix: Some("```".to_string()),
+      });
+    }
+  }
+  findings
+}
+
+/// `{{< figure src="a.png" alt="A" >}}` on its own becomes `![A](a.png)`.
+fn hugo_figure(lines: &[&str]) -> Vec<Finding> {
+  let figure = Regex::new(r"^\s*\{\{<\s*figure\s+(.*?)\s*/?>\}\}\s*$").unwrap();
+  let attribute = Regex::new(r#"(\w+)\s*=\s*"([^"]*)""#).unwrap();
+  let mut findings = Vec::new();
+  for i in prose_lines(lines) {
+    let Some(caps) = figure.captures(lines[i]) else {
+      continue;
+    };
+    let attributes: Vec<(&str, &str)> = attribute.captures_iter(caps.get(1).unwrap().as_str())
+      .map(|attr| (attr.get(1).unwrap().as_str(), attr.get(2).unwrap().as_str()))
+      .collect();
+    let get = |key: &str| attributes.iter().find(|(name, _)| *name == key).map(|(_, value)| *value);
+    let fix = get("src").map(|src| {
+      let alt = get("alt").or(get("title")).or(get("caption")).unwrap_or("");
+      format!("![{}]({})", alt, src)
+    });
+    findings.push(Finding {
+      line: i,
+      message: "Hugo figure shortcode, use image syntax".to_string(),
+      fix,
+    });
+  }
+  findings
+}
+
+/// Any other Hugo shortcode; there is no general rewrite for these.
+fn hugo_shortcode(lines: &[&str]) -> Vec<Finding> {
+  let shortcode = Regex::new(r"\{\{[<%]\s*/?\s*([\w-]+)").unwrap();
+  let mut findings = Vec::new();
+  for i in prose_lines(lines) {
+    for caps in shortcode.captures_iter(lines[i]) {
+      if &caps[1] == "figure" {
+        continue;
+      }
+      findings.push(Finding {
+        line: i,
+        message: format!("Hugo shortcode '{}' is not supported", &caps[1]),
+        fix: None,
+      });
+    }
+  }
+  findings
+}
+
+/// `[text][label]` and `[label][]` links whose `[label]: url` definition is missing.
+fn missing_reference(lines: &[&str]) -> Vec<Finding> {
+  let definition = Regex::new(r"^\s{0,3}\[([^\]]+)\]:\s*\S").unwrap();
+  let reference = Regex::new(r"\[([^\]]+)\]\[([^\]]*)\]").unwrap();
+  let prose = prose_lines(lines);
+  let defined: BTreeSet<String> = prose.iter()
+    .filter_map(|&i| definition.captures(lines[i]))
+    .map(|caps| caps[1].to_lowercase())
+    .collect();
+
+  let mut findings = Vec::new();
+  for i in prose {
+    for caps in reference.captures_iter(lines[i]) {
+      let label = if caps[2].is_empty() { &caps[1] } else { &caps[2] };
+      if !defined.contains(&label.to_lowercase()) {
+        findings.push(Finding {
+          line: i,
+          message: format!("reference link [{}] has no definition", label),
+          fix: None,
+        });
+      }
+    }
+  }
+  findings
+}
+
+/// A block tag such as `<div class="grid">` on its own line directly followed
+/// by markdown, or a closing tag directly after it. The HTML block only ends
+/// at a blank line, so without one the markdown is passed through raw.
+fn html_block_markdown(lines: &[&str]) -> Vec<Finding> {
+  let tags = BLOCK_TAGS.join("|");
+  let open = Regex::new(&format!(r"^\s{{0,3}}<({})(\s[^>]*)?>\s*$", tags)).unwrap();
+  let close = Regex::new(&format!(r"^\s{{0,3}}</({})>\s*$", tags)).unwrap();
+  let is_markdown = |line: &str| !line.trim().is_empty() && !line.trim_start().starts_with('<');
+  let mut findings = Vec::new();
+  for i in prose_lines(lines) {
+    if let Some(caps) = open.captures(lines[i]) {
+      if lines.get(i + 1).is_some_and(|next| is_markdown(next)) {
+        findings.push(Finding {
+          line: i,
+          message: format!("markdown right after <{}> is not rendered, add a blank line", &caps[1]),
+          fix: Some(format!("{}\n", lines[i])),
+        });
+      }
+    }
+    else if let Some(caps) = close.captures(lines[i]) {
+      if i > 0 && is_markdown(lines[i - 1]) {
+        findings.push(Finding {
+          line: i,
+          message: format!("</{}> right after markdown ends up inside it, add a blank line", &caps[1]),
+          fix: Some(format!("\n{}", lines[i])),
+        });
+      }
+    }
+  }
+  findings
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn markdown_against_a_block_tag_is_reported() {
+    let lines: Vec<&str> = "<div class=\"grid\">\n- one\n- two\n</div>\n\n<section>\n\nText\n\n</section>\n```html\n<div>\nraw\n</div>\n```".lines().collect();
+    let findings = html_block_markdown(&lines);
+    let reported: Vec<(usize, &str)> = findings.iter().map(|finding| (finding.line, finding.message.as_str())).collect();
+    assert_eq!(reported, [
+      (0, "markdown right after <div> is not rendered, add a blank line"),
+      (3, "</div> right after markdown ends up inside it, add a blank line"),
+    ]);
+    assert_eq!(findings[0].fix.as_deref(), Some("<div class=\"grid\">\n"));
+    assert_eq!(findings[1].fix.as_deref(), Some("\n</div>"));
+  }
+}
@@ -0,0 +1,295 @@
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::frontmatter::{check_title, extract_frontmatter, latest_revisions, normalize_dates, normalize_tag, parse_dates, split_comma_tags, Dates, FrontMatter, FRONTMATTER_FIELDS};
+use crate::markdown::{cite_references, convert_containers, convert_wiki_embeds, first_h1, markdown_to_html, revision_badge, substitute_variables, Heading};
+use crate::slug::tag_slug;
+use crate::origins::Origins;
+use crate::related::Related;
+use crate::social::{self, SocialMeta};
+use crate::timings::Timings;
+use crate::RenderOptions;
+
+pub const ISO_DATE_FORMAT: &str = "%Y-%m-%d";
+pub const DISPLAY_DATE_FORMAT: &str = "%B %d, %Y";
+
+/// A parsed source file. Dates are parsed once here and every output formats
+/// them from these values, never from the raw frontmatter strings.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+  /// Source file, empty for documents rendered from memory.
+  pub path: PathBuf,
+  /// URL of the generated page, empty for documents rendered from memory.
+  #[serde(skip)]
+  pub url: String,
+  /// Path of the page below the content type's output directory and route,
+  /// from its `permalink` pattern.
+  #[serde(skip)]
+  pub permalink: String,
+  /// Stable id from the id lock, for published entries when `id_namespace`
+  /// is set.
+  #[serde(skip)]
+  pub id: Option<String>,
+  pub frontmatter: FrontMatter,
+  /// Whether the authors are the default author, the frontmatter listing
+  /// none.
+  pub default_authors: bool,
+  pub date: NaiveDate,
+  /// The full timestamp, when `date` was given as one.
+  pub datetime: Option<DateTime<FixedOffset>>,
+  pub date_end: Option<NaiveDate>,
+  /// URL segment for each tag, keyed by the tag as written.
+  pub tag_slugs: BTreeMap<String, String>,
+  pub html: String,
+  /// First 8 hex digits of the SHA-256 of `html`, for cache busting.
+  pub checksum: String,
+  pub toc: Vec<Heading>,
+  /// Date of the latest revision listing each heading, by heading id.
+  #[serde(default)]
+  pub revisions: BTreeMap<String, NaiveDate>,
+  pub word_count: usize,
+  /// Minutes to read the prose, rounded up.
+  pub reading_time: usize,
+  pub assets: Vec<String>,
+  pub text: String,
+  /// Reading text for `--emit-plaintext`.
+  #[serde(default)]
+  pub plaintext: String,
+  pub social: SocialMeta,
+  /// Whether comments are enabled, for content types with `comments_default`.
+  pub comments: Option<bool>,
+  /// Values of the content type's computed fields.
+  pub computed: BTreeMap<String, serde_json::Value>,
+  /// Filled in once all entries are loaded, see `related::resolve_related`.
+  #[serde(skip)]
+  pub related: Vec<Related>,
+  /// External origins the generated files load from, filled in as they are
+  /// written.
+  #[serde(skip)]
+  pub origins: Origins,
+  /// What parsing and writing the page cost in this build; parsing is
+  /// skipped for cached entries.
+  #[serde(skip)]
+  pub timings: Timings,
+}
+
+impl Entry {
+  /// Parses and renders a markdown source. Problems that don't stop the entry
+  /// from being built are returned as warnings.
+  pub fn parse(source: &str, options: &RenderOptions) -> Result<(Entry, Vec<String>), String> {
+    let mut warnings = Vec::new();
+    let (mut frontmatter, mut markdown) = extract_frontmatter(source, &options.frontmatter_aliases)?;
+    if options.strict_frontmatter {
+      check_unknown_keys(&frontmatter, options)?;
+    }
+    if frontmatter.title.trim().is_empty() {
+      let (title, range) = first_h1(&markdown).ok_or("missing title, and no `# ` heading to take it from")?;
+      frontmatter.title = title;
+      if options.content.strip_title_heading {
+        markdown.replace_range(range, "");
+      }
+    }
+    if !options.slug.is_empty() {
+      frontmatter.slug = options.slug.clone();
+    }
+    // A nameless author would be an empty schema.org Person; an entry left
+    // without authors gets no byline, or the default author.
+    let listed = frontmatter.authors.len();
+    frontmatter.authors.retain(|author| !author.name.trim().is_empty());
+    if frontmatter.authors.len() < listed {
+      warnings.push("authors: an author without a name was left out".to_string());
+    }
+    let default_authors = frontmatter.authors.is_empty() && options.default_author.is_some();
+    if default_authors {
+      frontmatter.authors.extend(options.default_author.clone());
+    }
+    if frontmatter.authors.iter().filter(|author| author.primary).count() > 1 {
+      return Err("only one author can be marked `primary: true`".to_string());
+    }
+    // Block scalars end in a newline that would show up in every output.
+    frontmatter.title = frontmatter.title.trim_end().to_string();
+    check_title(&frontmatter.title, &mut warnings);
+    if options.content.split_comma_tags {
+      frontmatter.tags = split_comma_tags(std::mem::take(&mut frontmatter.tags), &mut warnings);
+    }
+    if let Some(social) = &frontmatter.social {
+      social::validate(social, &mut warnings)?;
+    }
+    let dates = parse_dates(&frontmatter, &options.kind)?;
+    normalize_dates(&mut frontmatter, &dates, &mut warnings);
+    let Dates { date, datetime, date_end } = dates;
+    let comments = options.content.comments_default.map(|default| frontmatter.comments.unwrap_or(default));
+    if let Some(url) = &frontmatter.discussion_url {
+      if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(format!("discussion_url '{}' must be an absolute http(s) URL", url));
+      }
+    }
+    if comments.is_none() && (frontmatter.comments.is_some() || frontmatter.discussion_url.is_some()) {
+      warnings.push("`comments` and `discussion_url` are ignored, this content type does not set comments_default".to_string());
+    }
+    frontmatter.tags = frontmatter.tags.iter()
+      .map(|tag| normalize_tag(tag, &mut warnings))
+      .collect::<Result<_, _>>()?;
+    for tag in &options.content.default_tags {
+      if !frontmatter.tags.contains(tag) {
+        frontmatter.tags.push(tag.clone());
+      }
+    }
+    let mut tag_slugs = BTreeMap::new();
+    for tag in &frontmatter.tags {
+      let slug = tag_slug(tag);
+      if slug.is_empty() {
+        warnings.push(format!("tag '{}' has no characters usable in a URL, no tag page will link to it", tag));
+        continue;
+      }
+      tag_slugs.insert(tag.clone(), slug);
+    }
+    let markdown = if options.variables.is_empty() {
+      markdown
+    }
+    else {
+      substitute_variables(&markdown, &options.variables, &mut warnings)
+    };
+    let markdown = if options.content.wiki_embeds {
+      convert_wiki_embeds(&markdown, &mut warnings)
+    }
+    else {
+      markdown
+    };
+    let markdown = if options.content.containers {
+      convert_containers(&markdown, &mut warnings)
+    }
+    else {
+      markdown
+    };
+    let markdown = if frontmatter.references.is_empty() {
+      markdown
+    }
+    else {
+      cite_references(&markdown, &frontmatter.references, options.content.reference_format(), &mut warnings)
+    };
+    let number_headings = frontmatter.number_headings.unwrap_or(options.content.number_headings);
+    let latest = latest_revisions(&frontmatter)?;
+    let badges = latest.iter()
+      .map(|(anchor, (date, revision))| {
+        let date = date.format(options.content.revision_date_format()).to_string();
+        (anchor.clone(), revision_badge(options.content.revision_badge(), &date, revision.note.as_deref()))
+      })
+      .collect();
+    let markdown = markdown_to_html(&markdown, options, number_headings, &badges);
+    if let Some(anchor) = latest.keys().find(|anchor| !markdown.toc.iter().any(|heading| heading.id == **anchor)) {
+      return Err(format!("revisions: no heading has the id '{}'", anchor));
+    }
+    let revisions = latest.into_iter().map(|(anchor, (date, _))| (anchor, date)).collect();
+    let social = social::resolve(&frontmatter, &markdown.text, &markdown.assets, options);
+
+    let computed = frontmatter.computed(&options.content.computed);
+    let checksum = content_checksum(&markdown.html);
+    let entry = Entry {
+      path: PathBuf::new(),
+      url: String::new(),
+      permalink: String::new(),
+      id: None,
+      frontmatter,
+      default_authors,
+      date,
+      datetime,
+      date_end,
+      tag_slugs,
+      html: markdown.html,
+      checksum,
+      toc: markdown.toc,
+      revisions,
+      word_count: markdown.word_count,
+      reading_time: markdown.reading_time,
+      assets: markdown.assets,
+      text: markdown.text,
+      plaintext: markdown.plaintext,
+      social,
+      comments,
+      computed,
+      related: Vec::new(),
+      origins: Origins::new(),
+      timings: Timings::default(),
+    };
+    Ok((entry, warnings))
+  }
+}
+
+fn content_checksum(html: &str) -> String {
+  Sha256::digest(html.as_bytes())[..4].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Fails on frontmatter keys that are neither fields nor read by one of the
+/// content type's computed fields as `extra.<key>`.
+fn check_unknown_keys(frontmatter: &FrontMatter, options: &RenderOptions) -> Result<(), String> {
+  let unknown: Vec<&str> = frontmatter.extra.keys()
+    .map(String::as_str)
+    .filter(|key| {
+      let path = format!("extra.{}", key);
+      !options.content.computed.values().any(|field| field.from == path || field.from.starts_with(&format!("{}.", path)))
+    })
+    .collect();
+  if unknown.is_empty() {
+    return Ok(());
+  }
+  Err(format!(
+    "unknown frontmatter {} {} (strict_frontmatter is on; known fields are {})",
+    if unknown.len() == 1 { "key" } else { "keys" },
+    unknown.iter().map(|key| format!("'{}'", key)).collect::<Vec<_>>().join(", "),
+    FRONTMATTER_FIELDS.join(", ")
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::ContentConfig;
+
+  fn parse(source: &str, content: ContentConfig) -> Result<Entry, String> {
+    Entry::parse(source, &RenderOptions::default().content(content)).map(|(entry, _)| entry)
+  }
+
+  #[test]
+  fn default_tags_follow_the_entry_tags_once() {
+    let content = ContentConfig { default_tags: vec!["svelte".to_string(), "tutorial".to_string()], ..ContentConfig::default() };
+    let source = "---\ntitle: Post\ndate: 2024-01-05\ntags: [lang/rust, '#svelte', ' lang / C++ ']\n---\nText\n";
+    let entry = parse(source, content.clone()).unwrap();
+    assert_eq!(entry.frontmatter.tags, ["lang/rust", "svelte", "lang/C++", "tutorial"]);
+    assert_eq!(entry.tag_slugs.get("tutorial").map(String::as_str), Some("tutorial"));
+
+    let entry = parse("---\ntitle: Post\ndate: 2024-01-05\ntags: []\n---\nText\n", content).unwrap();
+    assert_eq!(entry.frontmatter.tags, ["svelte", "tutorial"]);
+  }
+
+  #[test]
+  fn missing_title_comes_from_the_first_heading() {
+    let source = "---\ndate: 2024-01-05\ntags: []\n---\nIntro\n\n# Field *notes*\n\nText\n\n# Second\n";
+    let entry = parse(source, ContentConfig::default()).unwrap();
+    assert_eq!(entry.frontmatter.title, "Field notes");
+    assert_eq!(entry.html.matches("<h1").count(), 2, "{}", entry.html);
+
+    let entry = parse(source, ContentConfig { strip_title_heading: true, ..ContentConfig::default() }).unwrap();
+    assert_eq!(entry.frontmatter.title, "Field notes");
+    assert_eq!(entry.html.matches("<h1").count(), 1, "{}", entry.html);
+    assert!(entry.html.contains("Second"), "{}", entry.html);
+    assert!(entry.html.contains("<p>Intro</p>"), "{}", entry.html);
+  }
+
+  #[test]
+  fn given_title_keeps_the_heading() {
+    let source = "---\ntitle: Given\ndate: 2024-01-05\ntags: []\n---\n# Heading\n";
+    let entry = parse(source, ContentConfig { strip_title_heading: true, ..ContentConfig::default() }).unwrap();
+    assert_eq!(entry.frontmatter.title, "Given");
+    assert!(entry.html.contains("Heading</h1>"), "{}", entry.html);
+  }
+
+  #[test]
+  fn missing_title_without_a_heading_is_an_error() {
+    let error = parse("---\ndate: 2024-01-05\ntags: []\n---\n## Not a title\n", ContentConfig::default()).err().unwrap();
+    assert_eq!(error, "missing title, and no `# ` heading to take it from");
+  }
+}
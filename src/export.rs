@@ -0,0 +1,83 @@
+//! Writes a generator-agnostic snapshot of all published content. The layout
+//! is described in the README and versioned by [`FORMAT_VERSION`].
+
+use serde_json::json;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::entry::{Entry, ISO_DATE_FORMAT};
+use crate::cache::BuildCache;
+use crate::site::{self, BuildOptions, ContentType};
+
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Images inside the bundle are referenced relative to the entry directory.
+const ASSET_DIR: &str = "assets";
+
+pub fn export(config: &Config, build_options: &BuildOptions, dir: &Path) -> std::io::Result<()> {
+  let variables = site::env_variables(&config.env_allowlist);
+  let mut index = Vec::new();
+
+  for content_type in site::existing_content_types(config) {
+    let options = site::render_options(&content_type, config.content_config(&content_type.name))
+      .image_base(ASSET_DIR)
+      .variables(variables.clone())
+      .default_author(config.default_author.clone())
+      .url_style(config.url_style)
+      .page_routes(config.page_routes())
+      .frontmatter_aliases(config.frontmatter_aliases.clone())
+      .strict_frontmatter(config.strict_frontmatter);
+
+    let entries = site::load_entries(&content_type, &options, build_options, &mut BuildCache::default(), None);
+    for entry in &entries {
+      let path = format!("{}/{}", content_type.name, entry.frontmatter.slug);
+      export_entry(entry, &content_type, &dir.join(&path))?;
+      index.push(json!({
+        "type": content_type.name,
+        "slug": entry.frontmatter.slug,
+        "title": entry.frontmatter.title,
+        "date": entry.date.format(ISO_DATE_FORMAT).to_string(),
+        "path": path,
+      }));
+    }
+    info!("Exported {} entries from {}", entries.len(), content_type.input_dir);
+  }
+
+  let index = json!({
+    "format": "md_to_svelte-export",
+    "version": FORMAT_VERSION,
+    "entries": index,
+  });
+  std::fs::write(dir.join("index.json"), serde_json::to_string_pretty(&index).unwrap())
+}
+
+fn export_entry(entry: &Entry, content_type: &ContentType, dir: &Path) -> std::io::Result<()> {
+  std::fs::create_dir_all(dir)?;
+
+  let mut frontmatter = serde_json::to_value(&entry.frontmatter).unwrap();
+  frontmatter["date"] = match &entry.datetime {
+    Some(datetime) => json!(datetime.to_rfc3339()),
+    None => json!(entry.date.format(ISO_DATE_FORMAT).to_string()),
+  };
+  frontmatter["date_end"] = json!(entry.date_end.map(|date| date.format(ISO_DATE_FORMAT).to_string()));
+  std::fs::write(dir.join("frontmatter.json"), serde_json::to_string_pretty(&frontmatter).unwrap())?;
+  std::fs::write(dir.join("content.html"), &entry.html)?;
+  std::fs::write(dir.join("content.txt"), &entry.text)?;
+
+  let mut assets = Vec::new();
+  for asset in &entry.assets {
+    let Some(relative) = asset.strip_prefix("images/") else {
+      continue;
+    };
+    let source = Path::new(&content_type.input_dir).join(asset);
+    if !source.is_file() {
+      warn!("{}: image {} does not exist", entry.frontmatter.slug, source.display());
+      continue;
+    }
+    let target = dir.join(ASSET_DIR).join(relative);
+    std::fs::create_dir_all(target.parent().unwrap())?;
+    std::fs::copy(&source, &target)?;
+    assets.push(format!("{}/{}", ASSET_DIR, relative));
+  }
+  std::fs::write(dir.join("assets.json"), serde_json::to_string_pretty(&assets).unwrap())
+}
@@ -0,0 +1,287 @@
+//! Atom feeds of the articles. The newest ones are in `feed.xml`; older ones
+//! are kept in archive feeds linked as RFC 5005 archived feeds, so the feed
+//! stays small however many articles there are. Optionally every tag gets a
+//! feed too, and an OPML file lists them all.
+
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::FeedConfig;
+use crate::entry::{Entry, ISO_DATE_FORMAT};
+use crate::output::{self, escape_xml, XML_HEADER};
+use crate::slug::tag_slug;
+
+pub const FEED_FILE: &str = "feed.xml";
+/// Directory below `static_root` of the tag feeds.
+pub const TAG_FEED_DIR: &str = "feeds";
+pub const OPML_FILE: &str = "feeds.opml";
+
+/// Writes the feeds `config` asks for to `static_root` and returns the
+/// files written. Feed files of earlier builds that were not written again
+/// are removed.
+pub fn write(articles: &[&Entry], site_url: &str, config: &FeedConfig, static_root: &Path) -> std::io::Result<Vec<PathBuf>> {
+  let title = config.title.clone()
+    .unwrap_or_else(|| site_url.split("://").nth(1).unwrap_or(site_url).to_string());
+  let mut articles = articles.to_vec();
+  articles.sort_by(|a, b| (a.date, &a.frontmatter.slug).cmp(&(b.date, &b.frontmatter.slug)));
+
+  let mut written = write_main(&articles, site_url, &title, config.max_items(), static_root)?;
+  let mut feeds = vec![(title.clone(), format!("{}/{}", site_url, FEED_FILE))];
+  if config.tags {
+    for (feed_title, url, path) in write_tag_feeds(&articles, site_url, &title, config.max_items(), static_root)? {
+      feeds.push((feed_title, url));
+      written.push(path);
+    }
+  }
+  if config.opml {
+    let path = static_root.join(OPML_FILE);
+    output::write_generated(&path, &opml(&feeds, site_url, &title))?;
+    written.push(path);
+  }
+  remove_stale(static_root, &written)?;
+  Ok(written)
+}
+
+/// Writes the newest `max_items` of `articles`, given oldest first, to
+/// `feed.xml` and the rest to `feed-archive-1.xml` (the oldest),
+/// `feed-archive-2.xml`, ... of `max_items` each. Articles are ordered by
+/// date, then slug, so a new article only changes `feed.xml` and the newest
+/// archive.
+fn write_main(articles: &[&Entry], site_url: &str, title: &str, max_items: usize, static_root: &Path) -> std::io::Result<Vec<PathBuf>> {
+  let (older, newest) = articles.split_at(articles.len().saturating_sub(max_items));
+  let archives: Vec<&[&Entry]> = older.chunks(max_items).collect();
+  let current = format!("{}/{}", site_url, FEED_FILE);
+  let archive_url = |number: usize| format!("{}/{}", site_url, archive_file(number));
+
+  let mut written = Vec::new();
+  for (i, chunk) in archives.iter().enumerate() {
+    let number = i + 1;
+    let mut links = vec![("self", archive_url(number)), ("current", current.clone())];
+    if number > 1 {
+      links.push(("prev-archive", archive_url(number - 1)));
+    }
+    if number < archives.len() {
+      links.push(("next-archive", archive_url(number + 1)));
+    }
+    let path = static_root.join(archive_file(number));
+    output::write_generated(&path, &document(chunk, site_url, title, &links, true))?;
+    written.push(path);
+  }
+  let mut links = vec![("self", current.clone())];
+  if !archives.is_empty() {
+    links.push(("prev-archive", archive_url(archives.len())));
+  }
+  let path = static_root.join(FEED_FILE);
+  output::write_generated(&path, &document(newest, site_url, title, &links, false))?;
+  written.push(path);
+  Ok(written)
+}
+
+/// Writes a feed of the newest `max_items` articles of every tag to
+/// `feeds/<tag slug>.xml`, and returns the title, URL and path of each.
+/// Tag feeds have no archives.
+fn write_tag_feeds(articles: &[&Entry], site_url: &str, title: &str, max_items: usize, static_root: &Path) -> std::io::Result<Vec<(String, String, PathBuf)>> {
+  let mut tags: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+  for entry in articles {
+    for tag in entry.tag_slugs.keys() {
+      tags.entry(tag).or_default().push(entry);
+    }
+  }
+  let mut feeds = Vec::new();
+  for (tag, entries) in tags {
+    let name = format!("{}.xml", tag_slug(tag));
+    let url = format!("{}/{}/{}", site_url, TAG_FEED_DIR, name);
+    let feed_title = format!("{}: {}", title, tag);
+    let newest = &entries[entries.len().saturating_sub(max_items)..];
+    let path = static_root.join(TAG_FEED_DIR).join(name);
+    output::write_generated(&path, &document(newest, site_url, &feed_title, &[("self", url.clone())], false))?;
+    feeds.push((feed_title, url, path));
+  }
+  Ok(feeds)
+}
+
+/// An OPML subscription list of `feeds`, given as title and URL.
+fn opml(feeds: &[(String, String)], site_url: &str, title: &str) -> String {
+  let mut xml = format!("{}\n<opml version=\"2.0\">\n", XML_HEADER);
+  xml.push_str(&format!("  <head><title>{}</title></head>\n  <body>\n", escape_xml(title)));
+  for (feed_title, url) in feeds {
+    xml.push_str(&format!(
+      "    <outline type=\"rss\" text=\"{0}\" title=\"{0}\" xmlUrl=\"{1}\" htmlUrl=\"{2}/\"/>\n",
+      escape_xml(feed_title),
+      escape_xml(url),
+      escape_xml(site_url),
+    ));
+  }
+  xml.push_str("  </body>\n</opml>\n");
+  xml
+}
+
+fn archive_file(number: usize) -> String {
+  format!("feed-archive-{}.xml", number)
+}
+
+/// When the entry was published, as an RFC 3339 timestamp.
+fn timestamp(entry: &Entry) -> String {
+  match entry.datetime {
+    Some(datetime) => datetime.to_rfc3339(),
+    None => format!("{}T00:00:00Z", entry.date.format(ISO_DATE_FORMAT)),
+  }
+}
+
+/// One feed document listing `articles`, which come oldest first, newest
+/// first.
+fn document(articles: &[&Entry], site_url: &str, title: &str, links: &[(&str, String)], archive: bool) -> String {
+  let updated = articles.last().map_or("1970-01-01T00:00:00Z".to_string(), |entry| timestamp(entry));
+  let mut xml = format!(
+    "{}\n<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:fh=\"http://purl.org/syndication/history/1.0\">\n",
+    XML_HEADER
+  );
+  xml.push_str(&format!("  <title>{}</title>\n", escape_xml(title)));
+  xml.push_str(&format!("  <id>{}/</id>\n", escape_xml(site_url)));
+  xml.push_str(&format!("  <updated>{}</updated>\n", updated));
+  xml.push_str(&format!("  <author><name>{}</name></author>\n", escape_xml(title)));
+  xml.push_str(&format!("  <link rel=\"alternate\" href=\"{}/\"/>\n", escape_xml(site_url)));
+  for (rel, href) in links {
+    xml.push_str(&format!("  <link rel=\"{}\" href=\"{}\"/>\n", rel, escape_xml(href)));
+  }
+  if archive {
+    xml.push_str("  <fh:archive/>\n");
+  }
+  for entry in articles.iter().rev() {
+    let url = escape_xml(&format!("{}{}", site_url, entry.url));
+    xml.push_str("  <entry>\n");
+    xml.push_str(&format!("    <title>{}</title>\n", escape_xml(&entry.frontmatter.title)));
+    xml.push_str(&format!("    <id>{}</id>\n", url));
+    xml.push_str(&format!("    <link rel=\"alternate\" href=\"{}\"/>\n", url));
+    xml.push_str(&format!("    <updated>{}</updated>\n", timestamp(entry)));
+    for author in &entry.frontmatter.authors {
+      xml.push_str(&format!("    <author><name>{}</name></author>\n", escape_xml(&author.name)));
+    }
+    for tag in &entry.frontmatter.tags {
+      xml.push_str(&format!("    <category term=\"{}\"/>\n", escape_xml(tag)));
+    }
+    if !entry.social.description.is_empty() {
+      xml.push_str(&format!("    <summary>{}</summary>\n", escape_xml(&entry.social.description)));
+    }
+    xml.push_str("  </entry>\n");
+  }
+  xml.push_str("</feed>\n");
+  xml
+}
+
+/// Removes generated feed files of an earlier build that are not in
+/// `written`. Tag feed directories left empty are removed too.
+pub(crate) fn remove_stale(static_root: &Path, written: &[PathBuf]) -> std::io::Result<()> {
+  let name = Regex::new(r"^(feed(-archive-\d+)?\.xml|feeds\.opml)$").unwrap();
+  remove_generated(static_root, &name, written, false)?;
+  remove_generated(&static_root.join(TAG_FEED_DIR), &Regex::new(r"\.xml$").unwrap(), written, true)
+}
+
+/// Removes the generated files in `dir` whose name matches `name` and that
+/// are not in `written`, and with `recurse` does the same in the
+/// directories below, removing those left empty.
+fn remove_generated(dir: &Path, name: &Regex, written: &[PathBuf], recurse: bool) -> std::io::Result<()> {
+  let Ok(read_dir) = std::fs::read_dir(dir) else {
+    return Ok(());
+  };
+  for entry in read_dir {
+    let path = entry?.path();
+    if recurse && path.is_dir() {
+      remove_generated(&path, name, written, true)?;
+      let _ = std::fs::remove_dir(&path);
+      continue;
+    }
+    let matches = path.file_name().and_then(|name| name.to_str()).is_some_and(|file| name.is_match(file));
+    if matches && !written.contains(&path) && output::is_generated(&path) {
+      std::fs::remove_file(&path)?;
+    }
+  }
+  if recurse {
+    let _ = std::fs::remove_dir(dir);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::DateTime;
+  use crate::config::ContentConfig;
+  use crate::RenderOptions;
+
+  #[test]
+  fn dates_are_rfc_3339_whatever_the_time_format() {
+    let content = ContentConfig { time_format: Some("%I:%M %p (%Z), %A".to_string()), ..ContentConfig::default() };
+    let options = RenderOptions::default().content(content);
+    let parse = |date: &str| Entry::parse(&format!("---\ntitle: Post\ndate: {}\ntags: []\n---\nText\n", date), &options).unwrap().0;
+    let day = parse("2024-01-05");
+    let timestamp = parse("2024-02-10T23:30:00-05:00");
+
+    let xml = document(&[&day, &timestamp], "https://example.com", "Blog", &[], false);
+    let updated: Vec<&str> = Regex::new(r"<updated>([^<]*)</updated>").unwrap()
+      .captures_iter(&xml)
+      .map(|captures| captures.get(1).unwrap().as_str())
+      .collect();
+    assert_eq!(updated, ["2024-02-10T23:30:00-05:00", "2024-02-10T23:30:00-05:00", "2024-01-05T00:00:00Z"], "{}", xml);
+    for value in updated {
+      assert!(DateTime::parse_from_rfc3339(value).is_ok(), "{}", value);
+    }
+  }
+
+  #[test]
+  fn thousands_of_articles_are_archived_and_linked() {
+    let options = RenderOptions::default();
+    let (entry, _) = Entry::parse("---\ntitle: Q&A <1>\ndate: 2020-01-01\ntags: [rust]\n---\nText\n", &options).unwrap();
+    let articles: Vec<Entry> = (0..2450)
+      .map(|i| {
+        let mut article = entry.clone();
+        article.date = entry.date + chrono::Days::new(i / 3);
+        article.frontmatter.slug = format!("post-{:04}", i);
+        article.url = format!("/blog/post-{:04}", i);
+        article
+      })
+      .collect();
+    let articles: Vec<&Entry> = articles.iter().rev().collect();
+    let dir = tempfile::tempdir().unwrap();
+    let config = FeedConfig { max_items: Some(100), ..FeedConfig::default() };
+    let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+    let ids = Regex::new(r"<entry>\s*<title>[^<]*</title>\s*<id>([^<]*)</id>").unwrap();
+    let link = |xml: &str, rel: &str| Regex::new(&format!(r#"<link rel="{}" href="([^"]*)"/>"#, rel)).unwrap()
+      .captures(xml)
+      .map(|caps| caps[1].to_string());
+    let url = |name: &str| format!("https://example.com/{}", name);
+
+    let written = write(&articles, "https://example.com", &config, dir.path()).unwrap();
+    assert_eq!(written.len(), 25);
+    let feed = read(FEED_FILE);
+    crate::output::tests::assert_well_formed(&feed);
+    assert_eq!(link(&feed, "prev-archive"), Some(url("feed-archive-24.xml")));
+    assert_eq!(link(&feed, "next-archive"), None);
+
+    let mut seen: Vec<String> = ids.captures_iter(&feed).map(|caps| caps[1].to_string()).collect();
+    assert_eq!(seen.len(), 100);
+    assert_eq!(seen[0], "https://example.com/blog/post-2449");
+    for number in 1..=24 {
+      let name = archive_file(number);
+      let xml = read(&name);
+      crate::output::tests::assert_well_formed(&xml);
+      assert!(xml.contains("<fh:archive/>"), "{}", name);
+      assert_eq!(link(&xml, "self"), Some(url(&name)));
+      assert_eq!(link(&xml, "current"), Some(url(FEED_FILE)));
+      assert_eq!(link(&xml, "prev-archive"), (number > 1).then(|| url(&archive_file(number - 1))));
+      assert_eq!(link(&xml, "next-archive"), (number < 24).then(|| url(&archive_file(number + 1))));
+      let chunk: Vec<String> = ids.captures_iter(&xml).map(|caps| caps[1].to_string()).collect();
+      assert_eq!(chunk.len(), if number == 24 { 50 } else { 100 }, "{}", name);
+      seen.extend(chunk);
+    }
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen.len(), 2450);
+
+    let config = FeedConfig { max_items: Some(1000), ..FeedConfig::default() };
+    assert_eq!(write(&articles, "https://example.com", &config, dir.path()).unwrap().len(), 3);
+    assert!(dir.path().join(archive_file(2)).exists());
+    assert!(!dir.path().join(archive_file(3)).exists());
+  }
+}
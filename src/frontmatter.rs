@@ -0,0 +1,584 @@
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config::ComputedField;
+use crate::entry::ISO_DATE_FORMAT;
+use crate::site::ARTICLE_KIND;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Author {
+  pub name: String,
+  pub url: Option<String>,
+  /// Credited in structured data and share tags; the first author is
+  /// primary when none is marked.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub primary: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FrontMatter {
+  #[serde(default)]
+  pub slug: String,
+  /// Taken from the first `# ` heading when left out.
+  #[serde(default)]
+  pub title: String,
+  #[serde(default, deserialize_with = "authors")]
+  pub authors: Vec<Author>,
+  pub date: String,
+  /// Last date of activity for content spanning a range, e.g. a project.
+  #[serde(default)]
+  pub date_end: Option<String>,
+  /// Marks a range with no end date as still running.
+  #[serde(default)]
+  pub ongoing: bool,
+  #[serde(deserialize_with = "one_or_many")]
+  pub tags: Vec<String>,
+  /// Slugs of entries to list as related, ahead of the tag-based ones.
+  #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+  pub related: Vec<String>,
+  /// Sources that `[@key]` citations in the body point to.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub references: Vec<Reference>,
+  /// Overrides for the share metadata in the page head.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub social: Option<Social>,
+  /// Overrides the content type's `comments_default`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub comments: Option<bool>,
+  /// Absolute http(s) link to a discussion of the entry elsewhere.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub discussion_url: Option<String>,
+  /// Overrides the content type's `number_headings`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub number_headings: Option<bool>,
+  /// Revisions of the entry, each badging the headings it updated.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub revisions: Vec<Revision>,
+  /// Section or series the entry belongs to, the middle step of its
+  /// breadcrumb trail.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub category: Option<String>,
+  /// Drafts are only built with `--drafts`.
+  #[serde(default, skip_serializing)]
+  pub draft: bool,
+  /// Holds the entry back like a draft whatever its date, for entries
+  /// waiting on approval.
+  #[serde(default = "default_true", skip_serializing)]
+  pub published: bool,
+  /// Keys not listed above, for computed fields to read as `extra.<key>`.
+  #[serde(skip)]
+  pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+/// Keys of the frontmatter fields above that computed fields may read.
+pub const FRONTMATTER_FIELDS: [&str; 17] = [
+  "slug",
+  "title",
+  "authors",
+  "date",
+  "date_end",
+  "ongoing",
+  "tags",
+  "related",
+  "references",
+  "social",
+  "comments",
+  "discussion_url",
+  "number_headings",
+  "revisions",
+  "category",
+  "draft",
+  "published",
+];
+
+fn default_true() -> bool {
+  true
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Reference {
+  pub key: String,
+  #[serde(default)]
+  pub author: Option<String>,
+  pub title: String,
+  #[serde(default)]
+  pub url: Option<String>,
+}
+
+/// A revision of an entry, listing the ids of the headings it updated.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Revision {
+  pub date: String,
+  #[serde(default)]
+  pub note: Option<String>,
+  #[serde(default, deserialize_with = "one_or_many")]
+  pub anchors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Social {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub og_type: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub twitter_card: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub image: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub title: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+  /// Keys not listed above, kept only to warn about them.
+  #[serde(flatten, skip_serializing)]
+  pub unknown: BTreeMap<String, serde_yaml::Value>,
+}
+
+impl FrontMatter {
+  pub fn primary_author(&self) -> Option<&Author> {
+    self.authors.iter().find(|author| author.primary).or(self.authors.first())
+  }
+
+  /// Why the entry is only built with `--drafts`, if it is.
+  pub fn held_back(&self) -> Option<&'static str> {
+    if self.draft {
+      Some("a draft")
+    }
+    else if !self.published {
+      Some("unpublished")
+    }
+    else {
+      None
+    }
+  }
+
+  /// The value at a computed field's `from` path: a field name, or
+  /// `extra.<key>` for keys this tool does not know, followed by `.<key>`
+  /// segments into mappings.
+  fn lookup(&self, path: &str) -> Option<serde_json::Value> {
+    let mut segments = path.split('.');
+    let mut value = match segments.next()? {
+      "extra" => serde_json::to_value(self.extra.get(segments.next()?)?).ok()?,
+      "draft" => serde_json::Value::Bool(self.draft),
+      "published" => serde_json::Value::Bool(self.published),
+      field => serde_json::to_value(self).ok()?.get(field)?.clone(),
+    };
+    for segment in segments {
+      value = value.get(segment)?.clone();
+    }
+    Some(value)
+  }
+
+  /// Values of the content type's computed fields, by field name.
+  pub fn computed(&self, fields: &BTreeMap<String, ComputedField>) -> BTreeMap<String, serde_json::Value> {
+    fields.iter()
+      .map(|(name, field)| {
+        let value = self.lookup(&field.from).filter(|value| !value.is_null());
+        let value = match (&field.map, value) {
+          (Some(map), Some(value)) => {
+            let key = match value {
+              serde_json::Value::String(key) => key,
+              value => value.to_string(),
+            };
+            map.get(&key).or(field.default.as_ref()).cloned()
+          }
+          (Some(_), None) => field.default.clone(),
+          (None, Some(value)) => return (name.clone(), value),
+          (None, None) => field.default.clone(),
+        };
+        (name.clone(), value.map(|value| serde_json::to_value(value).unwrap()).unwrap_or_default())
+      })
+      .collect()
+  }
+}
+
+/// Accepts `key: value` as shorthand for `key: [value]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+  One(T),
+  Many(Vec<T>),
+}
+
+impl<T> From<OneOrMany<T>> for Vec<T> {
+  fn from(value: OneOrMany<T>) -> Vec<T> {
+    match value {
+      OneOrMany::One(item) => vec![item],
+      OneOrMany::Many(items) => items,
+    }
+  }
+}
+
+/// An author written either as a bare name or as a `name`/`url` mapping.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AuthorForm {
+  Name(String),
+  Full(Author),
+}
+
+impl Author {
+  /// Stable id of the author in `authors.ts`: the first 8 hex digits of the
+  /// SHA-256 of the name and URL.
+  pub fn id(&self) -> String {
+    let key = format!("{}\n{}", self.name, self.url.as_deref().unwrap_or(""));
+    crate::cache::hash(&key)[..8].to_string()
+  }
+}
+
+impl From<AuthorForm> for Author {
+  fn from(form: AuthorForm) -> Author {
+    match form {
+      AuthorForm::Name(name) => Author { name, url: None, primary: false },
+      AuthorForm::Full(author) => author,
+    }
+  }
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+  Ok(OneOrMany::deserialize(deserializer)?.into())
+}
+
+fn authors<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Author>, D::Error> {
+  let authors: Vec<AuthorForm> = OneOrMany::deserialize(deserializer)?.into();
+  Ok(authors.into_iter().map(Author::from).collect())
+}
+
+/// An author written either as a bare name or as a `name`/`url` mapping.
+pub fn author<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Author, D::Error> {
+  Ok(AuthorForm::deserialize(deserializer)?.into())
+}
+
+/// Parses the frontmatter block and returns it with the body. Keys listed
+/// in `aliases` are renamed to the field they stand for first.
+pub fn extract_frontmatter(content: &str, aliases: &BTreeMap<String, String>) -> Result<(FrontMatter, String), String> {
+  let (yaml, markdown) = split_frontmatter(content)?;
+  let mut keys: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(yaml).unwrap_or_default();
+  let mut frontmatter: FrontMatter = if aliases.is_empty() {
+    serde_yaml::from_str(yaml)
+  }
+  else {
+    apply_aliases(&mut keys, aliases)?;
+    serde_yaml::from_value(serde_yaml::to_value(&keys).unwrap())
+  }
+  .map_err(|e| format!("invalid frontmatter: {}", e))?;
+  frontmatter.extra = keys.into_iter().filter(|(key, _)| !FRONTMATTER_FIELDS.contains(&key.as_str())).collect();
+  Ok((frontmatter, markdown.to_string()))
+}
+
+/// Moves each aliased key to its field. A target such as
+/// `social.description` is a key inside a mapping field.
+fn apply_aliases(keys: &mut BTreeMap<String, serde_yaml::Value>, aliases: &BTreeMap<String, String>) -> Result<(), String> {
+  for (alias, target) in aliases {
+    let Some(value) = keys.remove(alias) else {
+      continue;
+    };
+    let (field, inner) = match target.split_once('.') {
+      Some((field, inner)) => (field, Some(inner)),
+      None => (target.as_str(), None),
+    };
+    let slot = match inner {
+      None => keys.get(field).is_none().then(|| keys.entry(field.to_string()).or_insert(value)),
+      Some(inner) => {
+        let mapping = keys.entry(field.to_string()).or_insert_with(|| serde_yaml::Value::Mapping(Default::default()));
+        let mapping = mapping.as_mapping_mut().ok_or_else(|| format!("`{}` must be a mapping to take the alias `{}`", field, alias))?;
+        let key = serde_yaml::Value::String(inner.to_string());
+        (!mapping.contains_key(&key)).then(|| mapping.entry(key).or_insert(value))
+      }
+    };
+    if slot.is_none() {
+      return Err(format!("`{}` is an alias of `{}`, which is also set", alias, target));
+    }
+  }
+  Ok(())
+}
+
+/// Splits a source into its frontmatter and body. A BOM and blank lines may
+/// come before the opening `---`, and both fences may have trailing
+/// whitespace and either line ending. The body keeps its line endings.
+fn split_frontmatter(content: &str) -> Result<(&str, &str), String> {
+  let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+  let is_fence = |line: &str| line.trim_end() == "---";
+  let mut lines = content.split_inclusive('\n');
+  let mut offset = 0;
+  let mut line_number = 0;
+  let opened_on = loop {
+    let line = lines.next().ok_or("missing frontmatter block")?;
+    line_number += 1;
+    offset += line.len();
+    if is_fence(line) {
+      break line_number;
+    }
+    if !line.trim().is_empty() {
+      return Err("missing frontmatter block".to_string());
+    }
+  };
+  let yaml_start = offset;
+  for line in lines {
+    if is_fence(line) {
+      return Ok((&content[yaml_start..offset], &content[offset + line.len()..]));
+    }
+    offset += line.len();
+  }
+  Err(format!("frontmatter opened on line {} has no closing `---`", opened_on))
+}
+
+/// Splits `tags: rust, wasm` style values into separate tags.
+pub fn split_comma_tags(tags: Vec<String>, warnings: &mut Vec<String>) -> Vec<String> {
+  let mut split = Vec::new();
+  for tag in tags {
+    if tag.contains(',') {
+      warnings.push(format!("tag '{}' was split on commas, write tags as a YAML list instead", tag));
+      split.extend(tag.split(',').map(|part| part.trim().to_string()).filter(|part| !part.is_empty()));
+    }
+    else {
+      split.push(tag);
+    }
+  }
+  split
+}
+
+/// Tags are `/`-separated paths such as `lang/rust`; whitespace around each
+/// segment is dropped, and so is a leading `#` copied from other platforms.
+pub fn normalize_tag(tag: &str, warnings: &mut Vec<String>) -> Result<String, String> {
+  if tag.chars().any(char::is_control) {
+    return Err(format!("tag {:?} contains control characters", tag));
+  }
+  let mut trimmed = tag.trim();
+  if let Some(stripped) = trimmed.strip_prefix('#') {
+    trimmed = stripped;
+  }
+  let segments: Vec<&str> = trimmed.split('/').map(str::trim).collect();
+  if segments.iter().any(|segment| segment.is_empty()) {
+    return Err(format!("tag '{}' has an empty path segment", tag));
+  }
+  let normalized = segments.join("/");
+  if trimmed.len() < tag.trim().len() {
+    warnings.push(format!("[tag-hash] tag '{}' starts with '#', which is dropped; it is shown and linked as '{}'", tag, normalized));
+  }
+  Ok(normalized)
+}
+
+/// Titles are plain text everywhere they are shown, so markup in them shows
+/// up literally.
+pub fn check_title(title: &str, warnings: &mut Vec<String>) {
+  if title.contains('\n') {
+    warnings.push(format!(
+      "[title-multiline] title spans several lines; headings show it on one line and the data files hold {}",
+      crate::output::js_string(title)
+    ));
+  }
+  let html = Regex::new(r"</?[a-zA-Z][^>]*>").unwrap();
+  let markdown = Regex::new(r"`[^`]+`|\*\*|__|\[[^\]]+\]\([^)]*\)|(^|\s)[*_]\S").unwrap();
+  let category = if html.is_match(title) {
+    "title-html"
+  }
+  else if markdown.is_match(title) {
+    "title-markdown"
+  }
+  else {
+    return;
+  };
+  warnings.push(format!(
+    "[{}] title contains {} that is not rendered: the page heading and share tags show \"{}\" and the data files hold {}",
+    category,
+    if category == "title-html" { "HTML" } else { "markdown" },
+    title,
+    crate::output::js_string(title)
+  ));
+}
+
+/// Dates of an entry as parsed from the frontmatter.
+pub struct Dates {
+  pub date: NaiveDate,
+  /// The full value of `date` when it is a timestamp.
+  pub datetime: Option<DateTime<FixedOffset>>,
+  pub date_end: Option<NaiveDate>,
+}
+
+/// Parses `date` and `date_end`. `date` may also be an RFC 3339 timestamp
+/// such as `2024-01-05T14:30:00Z`; its date is the one in the timestamp's
+/// own offset.
+pub fn parse_dates(frontmatter: &FrontMatter, kind: &str) -> Result<Dates, String> {
+  let (date, datetime) = match NaiveDate::parse_from_str(&frontmatter.date, ISO_DATE_FORMAT) {
+    Ok(date) => (date, None),
+    Err(_) if frontmatter.date.len() > 10 => {
+      let datetime = DateTime::parse_from_rfc3339(&frontmatter.date)
+        .map_err(|e| format!("invalid date '{}': {}; timestamps need a UTC offset, like 2024-01-05T14:30:00Z", frontmatter.date, e))?;
+      (datetime.date_naive(), Some(datetime))
+    }
+    Err(e) => return Err(format!("invalid date '{}': {}", frontmatter.date, e)),
+  };
+
+  if kind == ARTICLE_KIND && (frontmatter.date_end.is_some() || frontmatter.ongoing) {
+    return Err("`date_end` and `ongoing` are not supported for articles".to_string());
+  }
+
+  let date_end = match &frontmatter.date_end {
+    Some(date_end) => {
+      if frontmatter.ongoing {
+        return Err("`date_end` and `ongoing: true` cannot both be set".to_string());
+      }
+      let end = NaiveDate::parse_from_str(date_end, ISO_DATE_FORMAT)
+        .map_err(|e| format!("invalid date_end '{}': {}", date_end, e))?;
+      if end < date {
+        return Err(format!("date_end {} is before date {}", date_end, frontmatter.date));
+      }
+      Some(end)
+    }
+    None => None,
+  };
+
+  Ok(Dates { date, datetime, date_end })
+}
+
+/// Rewrites `date` and `date_end` to the ISO form of their parsed values,
+/// so outputs reading the frontmatter agree with those formatting the
+/// parsed dates. Values written another way, like `2024-7-4`, get a
+/// warning. Timestamps are kept as written.
+pub fn normalize_dates(frontmatter: &mut FrontMatter, dates: &Dates, warnings: &mut Vec<String>) {
+  let mut normalize = |key: &str, value: &mut String, date: NaiveDate| {
+    let canonical = date.format(ISO_DATE_FORMAT).to_string();
+    if *value != canonical {
+      warnings.push(format!("[date-format] {} '{}' is read as {}; write it that way in the source", key, value, canonical));
+      *value = canonical;
+    }
+  };
+  if dates.datetime.is_none() {
+    normalize("date", &mut frontmatter.date, dates.date);
+  }
+  if let (Some(value), Some(date_end)) = (&mut frontmatter.date_end, dates.date_end) {
+    normalize("date_end", value, date_end);
+  }
+}
+
+/// The latest revision listing each anchor and its date, by anchor.
+pub fn latest_revisions(frontmatter: &FrontMatter) -> Result<BTreeMap<String, (NaiveDate, &Revision)>, String> {
+  let mut latest: BTreeMap<String, (NaiveDate, &Revision)> = BTreeMap::new();
+  for revision in &frontmatter.revisions {
+    let date = NaiveDate::parse_from_str(&revision.date, ISO_DATE_FORMAT)
+      .map_err(|e| format!("invalid revision date '{}': {}", revision.date, e))?;
+    for anchor in &revision.anchors {
+      if latest.get(anchor).is_none_or(|(latest, _)| date >= *latest) {
+        latest.insert(anchor.clone(), (date, revision));
+      }
+    }
+  }
+  Ok(latest)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(date: &str) -> Result<Dates, String> {
+    let source = format!("---\ntitle: Post\ndate: {}\ntags: []\n---\n", date);
+    let (frontmatter, _) = extract_frontmatter(&source, &BTreeMap::new()).unwrap();
+    parse_dates(&frontmatter, ARTICLE_KIND)
+  }
+
+  fn frontmatter(yaml: &str) -> FrontMatter {
+    extract_frontmatter(&format!("---\ntitle: Post\ndate: 2024-01-05\n{}\n---\n", yaml), &BTreeMap::new()).unwrap().0
+  }
+
+  #[test]
+  fn tags_and_authors_accept_every_yaml_form() {
+    assert_eq!(frontmatter("tags: rust").tags, ["rust"]);
+    assert_eq!(frontmatter("tags: [rust, wasm]").tags, ["rust", "wasm"]);
+    assert_eq!(frontmatter("tags:\n  - rust\n  - wasm").tags, ["rust", "wasm"]);
+
+    let names = |yaml: &str| -> Vec<(String, Option<String>)> {
+      frontmatter(&format!("tags: []\n{}", yaml)).authors.into_iter().map(|author| (author.name, author.url)).collect()
+    };
+    let jane = || ("Jane Doe".to_string(), None);
+    let ada = || ("Ada".to_string(), Some("https://ada.dev".to_string()));
+    assert_eq!(names("authors: Jane Doe"), [jane()]);
+    assert_eq!(names("authors:\n  name: Ada\n  url: https://ada.dev"), [ada()]);
+    assert_eq!(names("authors:\n  - Jane Doe\n  - name: Ada\n    url: https://ada.dev"), [jane(), ada()]);
+    assert!(names("").is_empty());
+  }
+
+  #[test]
+  fn comma_tags_are_split_with_a_warning() {
+    let mut warnings = Vec::new();
+    let tags = split_comma_tags(frontmatter("tags: rust, wasm,, web").tags, &mut warnings);
+    assert_eq!(tags, ["rust", "wasm", "web"]);
+    assert_eq!(warnings.len(), 1);
+
+    let mut warnings = Vec::new();
+    assert_eq!(split_comma_tags(frontmatter("tags: [rust, wasm]").tags, &mut warnings), ["rust", "wasm"]);
+    assert!(warnings.is_empty());
+  }
+
+  #[test]
+  fn frontmatter_fences_tolerate_editor_noise() {
+    let variants = [
+      "\u{feff}---\ntitle: Post\n---\nBody\n",
+      "\n\n  \n---\ntitle: Post\n---\nBody\n",
+      "---  \ntitle: Post\n--- \t\nBody\n",
+      "---\r\ntitle: Post\r\n---\r\nBody\r\n",
+    ];
+    for source in variants {
+      let (yaml, body) = split_frontmatter(source).unwrap();
+      assert_eq!(yaml.trim_end(), "title: Post", "{:?}", source);
+      assert_eq!(serde_yaml::from_str::<serde_yaml::Value>(yaml).unwrap()["title"], "Post", "{:?}", source);
+      assert_eq!(body.trim_end(), "Body", "{:?}", source);
+    }
+    // The body keeps its own line endings.
+    assert_eq!(split_frontmatter("---\r\ntitle: Post\r\n---\r\nA\r\nB\r\n").unwrap().1, "A\r\nB\r\n");
+    // A fence further down is body text, not the end of the frontmatter.
+    assert_eq!(split_frontmatter("---\ntitle: Post\n---\nA\n---\nB\n").unwrap().1, "A\n---\nB\n");
+  }
+
+  #[test]
+  fn frontmatter_must_open_and_close() {
+    assert_eq!(split_frontmatter("\n\n---\ntitle: Post\nBody\n").err().unwrap(), "frontmatter opened on line 3 has no closing `---`");
+    assert_eq!(split_frontmatter("Body\n---\ntitle: Post\n---\n").err().unwrap(), "missing frontmatter block");
+    assert_eq!(split_frontmatter("").err().unwrap(), "missing frontmatter block");
+    assert!(split_frontmatter("----\ntitle: Post\n----\n").is_err());
+  }
+
+  #[test]
+  fn aliased_keys_deserialize_into_their_fields() {
+    let aliases: BTreeMap<String, String> = [("published_at", "date"), ("categories", "tags"), ("summary", "social.description")]
+      .into_iter()
+      .map(|(alias, target)| (alias.to_string(), target.to_string()))
+      .collect();
+    let source = "---\ntitle: Post\npublished_at: 2024-01-05\ncategories: [rust, web]\nsummary: Short\nsocial:\n  title: Shared\n---\n";
+    let (frontmatter, _) = extract_frontmatter(source, &aliases).unwrap();
+    assert_eq!(frontmatter.date, "2024-01-05");
+    assert_eq!(frontmatter.tags, ["rust", "web"]);
+    let social = frontmatter.social.unwrap();
+    assert_eq!(social.description.as_deref(), Some("Short"));
+    assert_eq!(social.title.as_deref(), Some("Shared"));
+    assert!(frontmatter.extra.is_empty(), "{:?}", frontmatter.extra.keys().collect::<Vec<_>>());
+
+    let source = "---\ntitle: Post\ndate: 2024-01-05\npublished_at: 2024-01-06\ntags: []\n---\n";
+    let error = extract_frontmatter(source, &aliases).err().unwrap();
+    assert_eq!(error, "`published_at` is an alias of `date`, which is also set");
+  }
+
+  #[test]
+  fn plain_date_has_no_timestamp() {
+    let dates = parse("2024-01-05").unwrap();
+    assert_eq!(dates.date, NaiveDate::from_ymd_opt(2024, 1, 5).unwrap());
+    assert!(dates.datetime.is_none());
+  }
+
+  #[test]
+  fn rfc_3339_timestamp_keeps_its_offset() {
+    let dates = parse("2024-01-05T23:30:00-05:00").unwrap();
+    // The date is the one in the timestamp's own offset, not in UTC.
+    assert_eq!(dates.date, NaiveDate::from_ymd_opt(2024, 1, 5).unwrap());
+    assert_eq!(dates.datetime.unwrap().to_rfc3339(), "2024-01-05T23:30:00-05:00");
+
+    let dates = parse("2024-01-05T14:30:00Z").unwrap();
+    assert_eq!(dates.datetime.unwrap().to_rfc3339(), "2024-01-05T14:30:00+00:00");
+  }
+
+  #[test]
+  fn timestamp_without_offset_is_rejected() {
+    let error = parse("2024-01-05T14:30:00").err().unwrap();
+    assert!(error.contains("need a UTC offset"), "{}", error);
+  }
+}
@@ -0,0 +1,149 @@
+//! Stable opaque ids of published entries, for analytics that must survive
+//! renames and slug changes. An id is a UUIDv5 of the configured namespace
+//! and the path the source was first published at, recorded in
+//! `md_to_svelte.ids.json`. That file is the source of truth: ids are never
+//! derived again once recorded, so it should be committed.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::entry::Entry;
+use crate::output;
+
+pub const ID_LOCK_PATH: &str = "md_to_svelte.ids.json";
+
+/// The id of one source.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedId {
+  pub id: String,
+  /// Path the source was first published at, which the id was derived from.
+  pub origin: PathBuf,
+  /// Date of the entry, to recognize the source after a rename.
+  pub date: NaiveDate,
+}
+
+/// Ids by current source path.
+pub struct IdLock {
+  namespace: Uuid,
+  ids: BTreeMap<PathBuf, LockedId>,
+  changed: bool,
+}
+
+impl IdLock {
+  /// Reads the lock, empty when there is none. Refuses a lock listing the
+  /// same id twice.
+  pub fn read(namespace: Uuid) -> Result<IdLock, String> {
+    let path = Path::new(ID_LOCK_PATH);
+    if !path.exists() {
+      return Ok(IdLock { namespace, ids: BTreeMap::new(), changed: false });
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", ID_LOCK_PATH, e))?;
+    let ids: BTreeMap<PathBuf, LockedId> = serde_json::from_str(&text).map_err(|e| format!("Error reading {}: {}", ID_LOCK_PATH, e))?;
+    let mut sources: BTreeMap<&str, &Path> = BTreeMap::new();
+    for (source, locked) in &ids {
+      if let Some(other) = sources.insert(&locked.id, source) {
+        return Err(format!(
+          "Error reading {}: id {} is assigned to both {} and {}; give one of them another id",
+          ID_LOCK_PATH,
+          locked.id,
+          other.display(),
+          source.display()
+        ));
+      }
+    }
+    Ok(IdLock { namespace, ids, changed: false })
+  }
+
+  /// Writes the lock if ids were assigned or moved since it was read.
+  pub fn save(&self) -> std::io::Result<()> {
+    if !self.changed {
+      return Ok(());
+    }
+    std::fs::write(ID_LOCK_PATH, serde_json::to_string_pretty(&self.ids).unwrap() + "\n")
+  }
+
+  /// Sets the id of each published entry of the content type in `input_dir`.
+  /// An entry without one takes over the id of a source that is gone and
+  /// had the same date, if exactly one did, as it was most likely renamed;
+  /// otherwise it gets a new id. Drafts and unpublished entries get none.
+  pub fn assign(&mut self, input_dir: &str, entries: &mut [Entry]) {
+    let published: Vec<&mut Entry> = entries.iter_mut().filter(|entry| entry.frontmatter.held_back().is_none()).collect();
+    let current: BTreeSet<PathBuf> = published.iter().map(|entry| entry.path.clone()).collect();
+    let mut gone: Vec<PathBuf> = self.ids.keys()
+      .filter(|path| path.starts_with(input_dir) && !current.contains(*path) && !path.is_file())
+      .cloned()
+      .collect();
+    for entry in published {
+      if let Some(locked) = self.ids.get(&entry.path) {
+        entry.id = Some(locked.id.clone());
+        continue;
+      }
+      let renamed: Vec<usize> = gone.iter().enumerate()
+        .filter(|(_, path)| self.ids[*path].date == entry.date)
+        .map(|(i, _)| i)
+        .collect();
+      let locked = match renamed[..] {
+        [i] => {
+          let old_path = gone.remove(i);
+          info!("{} keeps the id of {}, taken to be its old name", entry.path.display(), old_path.display());
+          self.ids.remove(&old_path).unwrap()
+        }
+        _ => {
+          let id = Uuid::new_v5(&self.namespace, portable(&entry.path).as_bytes()).to_string();
+          if let Some((other, _)) = self.ids.iter().find(|(_, locked)| locked.id == id) {
+            error!("Error in {}: its new id {} is already assigned to {}", entry.path.display(), id, other.display());
+            continue;
+          }
+          LockedId { id, origin: entry.path.clone(), date: entry.date }
+        }
+      };
+      entry.id = Some(locked.id.clone());
+      self.ids.insert(entry.path.clone(), locked);
+      self.changed = true;
+    }
+  }
+
+  /// Gives `source` a new id, derived from its origin and the old id, and
+  /// returns the old and new id.
+  pub fn rekey(&mut self, source: &Path) -> Result<(String, String), String> {
+    let locked = self.ids.get(source).ok_or_else(|| format!("{} has no id in {} yet", source.display(), ID_LOCK_PATH))?;
+    let name = format!("{}\n{}", portable(&locked.origin), locked.id);
+    let id = Uuid::new_v5(&self.namespace, name.as_bytes()).to_string();
+    if let Some((other, _)) = self.ids.iter().find(|(_, locked)| locked.id == id) {
+      return Err(format!("The new id {} of {} is already assigned to {}", id, source.display(), other.display()));
+    }
+    let old = std::mem::replace(&mut self.ids.get_mut(source).unwrap().id, id.clone());
+    self.changed = true;
+    Ok((old, id))
+  }
+}
+
+/// Gives the page last built with `slug` a new id, for `--rekey`, and
+/// warns that the old one is gone.
+pub fn rekey_slug(namespace: Uuid, slug: &str) -> Result<(), String> {
+  let pages = output::read_manifest()?;
+  let sources: Vec<&Path> = pages.iter().filter(|page| page.slug == slug).map(|page| page.source.as_path()).collect();
+  let source = match sources[..] {
+    [source] => source,
+    [] => return Err(format!("No page with slug '{}' in the last build", slug)),
+    _ => return Err(format!(
+      "Slug '{}' is used by {}; rename one of them first",
+      slug,
+      sources.iter().map(|source| source.display().to_string()).collect::<Vec<_>>().join(", ")
+    )),
+  };
+  let mut lock = IdLock::read(namespace)?;
+  let (old, new) = lock.rekey(source)?;
+  lock.save().map_err(|e| format!("Error writing {}: {}", ID_LOCK_PATH, e))?;
+  warn!("[ids] {}: id changed from {} to {}; anything keyed by the old id, such as analytics, no longer matches", source.display(), old, new);
+  Ok(())
+}
+
+/// `path` with `/` separators, so ids derived on Windows match.
+fn portable(path: &Path) -> String {
+  path.to_string_lossy().replace('\\', "/")
+}
@@ -0,0 +1,172 @@
+//! Copies the images of each content type below the static root. Every copy
+//! goes to a temporary file renamed into place and is recorded in the cache
+//! directory as it completes, so images that did not change since are
+//! skipped. Ctrl-C while copying stops at the next image, and the next run
+//! carries on where it stopped.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crate::walk;
+
+/// File in the cache directory recording the images copied.
+pub const IMAGE_CACHE_FILE: &str = "images.json";
+/// Suffix of an image being copied; one left behind was cut short.
+const PARTIAL_SUFFIX: &str = ".partial";
+/// How often the record of copied images is written while copying.
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+static COPYING: AtomicBool = AtomicBool::new(false);
+static STOP: AtomicBool = AtomicBool::new(false);
+static HANDLER: Once = Once::new();
+
+/// Images below a directory of the content, copied to a static directory.
+pub struct ImageDir {
+  pub src: PathBuf,
+  pub dst: PathBuf,
+}
+
+/// Images copied by earlier runs: the size and modification time of the
+/// source each copy was made from, by copy.
+#[derive(Default, Serialize, Deserialize)]
+struct ImageCache {
+  copied: BTreeMap<PathBuf, String>,
+}
+
+impl ImageCache {
+  fn load(dir: Option<&Path>) -> ImageCache {
+    dir.and_then(|dir| std::fs::read_to_string(dir.join(IMAGE_CACHE_FILE)).ok())
+      .and_then(|text| serde_json::from_str(&text).ok())
+      .unwrap_or_default()
+  }
+
+  fn save(&self, dir: Option<&Path>) -> std::io::Result<()> {
+    let Some(dir) = dir else {
+      return Ok(());
+    };
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(IMAGE_CACHE_FILE), serde_json::to_string(self).unwrap())
+  }
+}
+
+/// Copies the images of `dirs` that are not ignored, keeping the directory
+/// layout, and returns how many were left when Ctrl-C stopped the copying.
+/// With a `cache_dir`, images whose copy is up to date are skipped.
+/// Temporary files of a copy cut short by an earlier run are removed.
+pub fn copy(dirs: &[ImageDir], respect_gitignore: bool, cache_dir: Option<&Path>) -> std::io::Result<Option<usize>> {
+  install_handler();
+  let mut cache = ImageCache::load(cache_dir);
+  let mut pending = Vec::new();
+  let mut current = BTreeSet::new();
+  for dir in dirs {
+    remove_partial(&dir.dst)?;
+    for source in walk::source_files(&dir.src, respect_gitignore) {
+      let target = dir.dst.join(source.strip_prefix(&dir.src).unwrap());
+      let stamp = stamp(&source)?;
+      let copied = cache.copied.get(&target) == Some(&stamp) && target.is_file();
+      if !copied {
+        pending.push((source, target.clone(), stamp));
+      }
+      current.insert(target);
+    }
+  }
+  // Images that are gone are forgotten.
+  cache.copied.retain(|target, _| current.contains(target));
+
+  COPYING.store(true, Ordering::Relaxed);
+  let mut saved = Instant::now();
+  let mut result = Ok(None);
+  for (i, (source, target, stamp)) in pending.iter().enumerate() {
+    if STOP.load(Ordering::Relaxed) {
+      result = Ok(Some(pending.len() - i));
+      break;
+    }
+    if let Err(e) = copy_file(source, target) {
+      result = Err(std::io::Error::new(e.kind(), format!("{}: {}", source.display(), e)));
+      break;
+    }
+    cache.copied.insert(target.clone(), stamp.clone());
+    if saved.elapsed() > SAVE_INTERVAL {
+      if let Err(e) = cache.save(cache_dir) {
+        result = Err(e);
+        break;
+      }
+      saved = Instant::now();
+    }
+  }
+  COPYING.store(false, Ordering::Relaxed);
+  cache.save(cache_dir)?;
+  result
+}
+
+/// Whether Ctrl-C stopped the copying, so the run should end.
+pub fn interrupted() -> bool {
+  STOP.load(Ordering::Relaxed)
+}
+
+/// Makes Ctrl-C while images are copied stop at the next image instead of
+/// killing the process. A second Ctrl-C, or one at any other time, exits
+/// at once as usual.
+fn install_handler() {
+  HANDLER.call_once(|| {
+    // The handler only touches atomics and exits, both safe in a signal
+    // handler.
+    let registered = unsafe {
+      signal_hook::low_level::register(signal_hook::consts::SIGINT, || {
+        if COPYING.load(Ordering::Relaxed) && !STOP.swap(true, Ordering::Relaxed) {
+          return;
+        }
+        signal_hook::low_level::exit(130);
+      })
+    };
+    if let Err(e) = registered {
+      debug!("Ctrl-C will not stop image copying gracefully: {}", e);
+    }
+  });
+}
+
+/// Copies `source` to a temporary file next to `target`, then renames it
+/// into place. The temporary file is removed when the copy fails.
+fn copy_file(source: &Path, target: &Path) -> std::io::Result<()> {
+  std::fs::create_dir_all(target.parent().unwrap())?;
+  let partial = partial_path(target);
+  let copied = std::fs::copy(source, &partial).and_then(|_| std::fs::rename(&partial, target));
+  if copied.is_err() {
+    let _ = std::fs::remove_file(&partial);
+  }
+  copied
+}
+
+fn partial_path(target: &Path) -> PathBuf {
+  target.with_file_name(format!(".{}{}", target.file_name().unwrap().to_string_lossy(), PARTIAL_SUFFIX))
+}
+
+/// Removes the temporary files of copies cut short, below `dir`.
+fn remove_partial(dir: &Path) -> std::io::Result<()> {
+  let Ok(read_dir) = std::fs::read_dir(dir) else {
+    return Ok(());
+  };
+  for entry in read_dir {
+    let path = entry?.path();
+    if path.is_dir() {
+      remove_partial(&path)?;
+      continue;
+    }
+    let name = path.file_name().unwrap().to_string_lossy();
+    if name.starts_with('.') && name.ends_with(PARTIAL_SUFFIX) {
+      std::fs::remove_file(&path)?;
+    }
+  }
+  Ok(())
+}
+
+/// Size and modification time of an image, which change when it does.
+fn stamp(path: &Path) -> std::io::Result<String> {
+  let metadata = std::fs::metadata(path)?;
+  let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+  Ok(format!("{}:{}", metadata.len(), modified.as_nanos()))
+}
@@ -0,0 +1,290 @@
+//! Converts Markdown content with YAML frontmatter into SvelteKit routes.
+//!
+//! The `md_to_svelte` binary builds whole content directories; embedders can
+//! render a single document with [`render_document`].
+
+#[macro_use]
+pub mod log;
+pub mod anchors;
+pub mod assets;
+pub mod cache;
+pub mod changes;
+pub mod config;
+pub mod doctor;
+pub mod export;
+pub mod ids;
+pub mod images;
+pub mod list;
+pub mod lock;
+pub mod origins;
+pub mod output;
+pub mod secrets;
+pub mod site;
+pub mod slug;
+pub mod timings;
+pub mod verify;
+pub mod watch;
+mod component;
+mod data;
+mod entry;
+mod feed;
+mod frontmatter;
+mod markdown;
+mod mathml;
+mod related;
+mod sanitize;
+mod sitemap;
+mod social;
+mod text;
+mod walk;
+
+pub use config::{AuthorsMode, ContentConfig, PlaintextConfig, UrlStyle};
+pub use frontmatter::{Author, FrontMatter};
+pub use markdown::Heading;
+pub use social::SocialMeta;
+
+use assets::Assets;
+use component::generate_svelte_component;
+use config::DEFAULT_SOURCE_COMMENT;
+use site::{default_content_types, ContentType, ARTICLE_KIND};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use entry::Entry;
+
+/// Knobs for rendering one document. Start from `RenderOptions::default()`
+/// and adjust with the builder methods.
+#[non_exhaustive]
+#[derive(Clone)]
+pub struct RenderOptions {
+  /// Enable GitHub-style tables.
+  pub tables: bool,
+  /// Kind of content, such as `article` or `project`. Articles and
+  /// projects differ in which frontmatter they accept and in their schema.
+  pub kind: String,
+  /// URL prefix that `images/...` references are rewritten to.
+  pub image_base: String,
+  /// Page layout and other per content type settings.
+  pub content: ContentConfig,
+  /// Slug of the document; the frontmatter `slug` is used when empty.
+  pub slug: String,
+  /// Values for `${NAME}` tokens in prose. Substitution is skipped when empty.
+  pub variables: BTreeMap<String, String>,
+  /// Author of documents whose frontmatter lists none.
+  pub default_author: Option<Author>,
+  /// Rewrite links to generated pages to this trailing-slash style.
+  pub url_style: Option<UrlStyle>,
+  /// URL paths of every content type, whose page links `url_style`
+  /// rewrites.
+  pub page_routes: Vec<String>,
+  /// Frontmatter keys read as another field, such as `publishDate` for
+  /// `date`.
+  pub frontmatter_aliases: BTreeMap<String, String>,
+  /// Reject frontmatter keys that are not fields, aliases or keys a computed
+  /// field reads.
+  pub strict_frontmatter: bool,
+  /// Export the page's authors inline or as ids resolved from `authors.ts`.
+  pub authors: AuthorsMode,
+  /// How the reading text of `--emit-plaintext` reads code, math, headings
+  /// and images.
+  pub plaintext: PlaintextConfig,
+  /// URL path the content type is served at, such as `/articles`.
+  pub route: String,
+  /// Absolute URL the site is served at, for the breadcrumb trail.
+  pub site_url: Option<String>,
+  /// Line naming the source after the header of each generated file.
+  pub source_comment: String,
+  /// Export the page's stable id as `<kind>Id`, such as `articleId`.
+  pub ids: bool,
+  /// Avatar and page stylesheet; the built-in ones by default.
+  pub assets: Arc<Assets>,
+}
+
+impl Default for RenderOptions {
+  fn default() -> RenderOptions {
+    RenderOptions {
+      tables: true,
+      kind: ARTICLE_KIND.to_string(),
+      image_base: "/images/articles".to_string(),
+      content: ContentConfig::default(),
+      slug: String::new(),
+      variables: BTreeMap::new(),
+      default_author: None,
+      url_style: None,
+      page_routes: default_content_types().iter().map(ContentType::route).collect(),
+      frontmatter_aliases: BTreeMap::new(),
+      strict_frontmatter: false,
+      authors: AuthorsMode::Inline,
+      plaintext: PlaintextConfig::default(),
+      route: "/articles".to_string(),
+      site_url: None,
+      source_comment: DEFAULT_SOURCE_COMMENT.to_string(),
+      ids: false,
+      assets: Arc::new(Assets::default()),
+    }
+  }
+}
+
+impl RenderOptions {
+  pub fn tables(mut self, tables: bool) -> RenderOptions {
+    self.tables = tables;
+    self
+  }
+
+  pub fn kind(mut self, kind: impl Into<String>) -> RenderOptions {
+    self.kind = kind.into();
+    self
+  }
+
+  pub fn image_base(mut self, image_base: impl Into<String>) -> RenderOptions {
+    self.image_base = image_base.into();
+    self
+  }
+
+  pub fn content(mut self, content: ContentConfig) -> RenderOptions {
+    self.content = content;
+    self
+  }
+
+  pub fn slug(mut self, slug: impl Into<String>) -> RenderOptions {
+    self.slug = slug.into();
+    self
+  }
+
+  pub fn variables(mut self, variables: BTreeMap<String, String>) -> RenderOptions {
+    self.variables = variables;
+    self
+  }
+
+  pub fn default_author(mut self, default_author: Option<Author>) -> RenderOptions {
+    self.default_author = default_author;
+    self
+  }
+
+  pub fn url_style(mut self, url_style: Option<UrlStyle>) -> RenderOptions {
+    self.url_style = url_style;
+    self
+  }
+
+  pub fn page_routes(mut self, page_routes: Vec<String>) -> RenderOptions {
+    self.page_routes = page_routes;
+    self
+  }
+
+  pub fn frontmatter_aliases(mut self, frontmatter_aliases: BTreeMap<String, String>) -> RenderOptions {
+    self.frontmatter_aliases = frontmatter_aliases;
+    self
+  }
+
+  pub fn strict_frontmatter(mut self, strict_frontmatter: bool) -> RenderOptions {
+    self.strict_frontmatter = strict_frontmatter;
+    self
+  }
+
+  pub fn authors(mut self, authors: AuthorsMode) -> RenderOptions {
+    self.authors = authors;
+    self
+  }
+
+  pub fn plaintext(mut self, plaintext: PlaintextConfig) -> RenderOptions {
+    self.plaintext = plaintext;
+    self
+  }
+
+  pub fn route(mut self, route: impl Into<String>) -> RenderOptions {
+    self.route = route.into();
+    self
+  }
+
+  pub fn site_url(mut self, site_url: Option<String>) -> RenderOptions {
+    self.site_url = site_url;
+    self
+  }
+
+  pub fn source_comment(mut self, source_comment: impl Into<String>) -> RenderOptions {
+    self.source_comment = source_comment.into();
+    self
+  }
+
+  pub fn ids(mut self, ids: bool) -> RenderOptions {
+    self.ids = ids;
+    self
+  }
+
+  pub fn assets(mut self, assets: Arc<Assets>) -> RenderOptions {
+    self.assets = assets;
+    self
+  }
+}
+
+/// Everything produced from a single document.
+#[non_exhaustive]
+pub struct RenderedDocument {
+  /// Kind of content the document was rendered as, from the options.
+  pub kind: String,
+  pub frontmatter: FrontMatter,
+  /// Rendered body HTML, as embedded in the component.
+  pub html: String,
+  /// First 8 hex digits of the SHA-256 of `html`.
+  pub checksum: String,
+  /// Source of the `+page.svelte` component.
+  pub component: String,
+  pub toc: Vec<Heading>,
+  /// Words outside code blocks, each CJK character counted as one.
+  pub word_count: usize,
+  /// Minutes to read the prose, rounded up.
+  pub reading_time: usize,
+  /// Image URLs referenced by the document, as written in the source.
+  pub assets: Vec<String>,
+  /// Open Graph and Twitter card values for the page head.
+  pub social: SocialMeta,
+  /// Problems that did not stop the document from rendering.
+  pub warnings: Vec<String>,
+}
+
+/// Renders a markdown document with frontmatter to a Svelte component without
+/// touching the filesystem. Everything it uses comes from `options`, and its
+/// warnings are returned rather than logged.
+pub fn render_document(markdown: &str, options: &RenderOptions) -> Result<RenderedDocument, String> {
+  let (entry, warnings) = Entry::parse(markdown, options)?;
+  let component = generate_svelte_component(&entry, options);
+  Ok(RenderedDocument {
+    kind: options.kind.clone(),
+    component,
+    html: entry.html,
+    checksum: entry.checksum,
+    toc: entry.toc,
+    word_count: entry.word_count,
+    reading_time: entry.reading_time,
+    assets: entry.assets,
+    social: entry.social,
+    frontmatter: entry.frontmatter,
+    warnings,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn render_document_takes_its_assets_from_the_options() {
+    let assets = Assets {
+      avatar_name: "avatar.svg".to_string(),
+      avatar: b"<svg/>".to_vec(),
+      page_css: ".custom-page { color: red; }\n".to_string(),
+      fingerprint: "custom".to_string(),
+      ..Assets::default()
+    };
+    let author = Author { name: "Jane".to_string(), url: None, primary: false };
+    let options = RenderOptions::default().assets(Arc::new(assets)).default_author(Some(author));
+    let document = render_document("---\ntitle: Post\ndate: 2024-1-5\ntags: []\n---\nText\n", &options).unwrap();
+    assert!(document.component.contains("<img src=\"/md_to_svelte/avatar.svg\""), "{}", document.component);
+    assert!(document.component.contains(".custom-page { color: red; }"), "{}", document.component);
+    assert!(document.warnings.iter().any(|warning| warning.starts_with("[date-format]")), "{:?}", document.warnings);
+
+    let document = render_document("---\ntitle: Post\ndate: 2024-01-05\ntags: []\n---\nText\n", &RenderOptions::default().default_author(options.default_author.clone())).unwrap();
+    assert!(document.component.contains("<img src=\"/md_to_svelte/avatar.png\""), "{}", document.component);
+    assert!(!document.component.contains(".custom-page"));
+    assert!(document.warnings.is_empty(), "{:?}", document.warnings);
+  }
+}
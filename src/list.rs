@@ -0,0 +1,91 @@
+//! Prints an inventory of every entry, drafts included, without writing any
+//! output.
+
+
+use crate::cache::BuildCache;
+use crate::config::Config;
+use crate::entry::{Entry, ISO_DATE_FORMAT};
+use crate::site::{self, BuildOptions};
+
+/// Column the table is ordered by. Dates and word counts are listed largest
+/// first, the rest in ascending order.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum SortKey {
+  Type,
+  Slug,
+  Title,
+  Date,
+  Words,
+}
+
+const COLUMNS: [&str; 7] = ["TYPE", "SLUG", "TITLE", "DATE", "TAGS", "DRAFT", "WORDS"];
+
+/// Parses every source like a build and prints one row per entry. Entries
+/// that fail to parse are reported and left out.
+pub fn list(config: &Config, build_options: &BuildOptions, sort: SortKey) {
+  let variables = site::env_variables(&config.env_allowlist);
+  let content_types = site::existing_content_types(config);
+  let mut entries: Vec<(&str, Entry)> = Vec::new();
+  for content_type in &content_types {
+    let options = site::render_options(content_type, config.content_config(&content_type.name))
+      .variables(variables.clone())
+      .default_author(config.default_author.clone())
+      .url_style(config.url_style)
+      .page_routes(config.page_routes())
+      .frontmatter_aliases(config.frontmatter_aliases.clone())
+      .strict_frontmatter(config.strict_frontmatter);
+    let loaded = site::load_entries(content_type, &options, build_options, &mut BuildCache::default(), None);
+    entries.extend(loaded.into_iter().map(|entry| (content_type.name.as_str(), entry)));
+  }
+
+  entries.sort_by(|(a_type, a), (b_type, b)| {
+    let by_type = a_type.cmp(b_type).then(a.frontmatter.slug.cmp(&b.frontmatter.slug));
+    match sort {
+      SortKey::Type => by_type,
+      SortKey::Slug => a.frontmatter.slug.cmp(&b.frontmatter.slug).then(a_type.cmp(b_type)),
+      SortKey::Title => a.frontmatter.title.to_lowercase().cmp(&b.frontmatter.title.to_lowercase()).then(by_type),
+      SortKey::Date => b.date.cmp(&a.date).then(by_type),
+      SortKey::Words => b.word_count.cmp(&a.word_count).then(by_type),
+    }
+  });
+
+  let rows: Vec<[String; 7]> = entries.iter()
+    .map(|(name, entry)| [
+      name.to_string(),
+      entry.frontmatter.slug.clone(),
+      entry.frontmatter.title.clone(),
+      entry.date.format(ISO_DATE_FORMAT).to_string(),
+      entry.frontmatter.tags.join(", "),
+      if entry.frontmatter.draft {
+        "yes".to_string()
+      }
+      else if !entry.frontmatter.published {
+        "unpublished".to_string()
+      }
+      else {
+        String::new()
+      },
+      entry.word_count.to_string(),
+    ])
+    .collect();
+  let mut widths = COLUMNS.map(|column| column.chars().count());
+  for row in &rows {
+    for (width, cell) in widths.iter_mut().zip(row) {
+      *width = (*width).max(cell.chars().count());
+    }
+  }
+  print_row(&COLUMNS.map(String::from), &widths);
+  for row in &rows {
+    print_row(row, &widths);
+  }
+}
+
+/// Cells padded to the column widths, with the word count right-aligned.
+fn print_row(row: &[String; 7], widths: &[usize; 7]) {
+  let cells: Vec<String> = row.iter()
+    .zip(widths)
+    .enumerate()
+    .map(|(i, (cell, width))| if i == row.len() - 1 { format!("{:>width$}", cell) } else { format!("{:<width$}", cell) })
+    .collect();
+  println!("{}", cells.join("  ").trim_end());
+}
@@ -0,0 +1,181 @@
+//! Keeps two runs in the same project from writing output at the same time.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{ErrorKind, Read, Seek, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Lock file in the project root.
+pub const LOCK_PATH: &str = ".md_to_svelte.lock";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An exclusive lock on the project, held until dropped. The operating system
+/// releases it when the process exits for any reason, so a lock file left by
+/// a crashed run is never mistaken for a held lock.
+pub struct ProjectLock {
+  file: File,
+}
+
+impl Drop for ProjectLock {
+  fn drop(&mut self) {
+    let _ = self.file.set_len(0);
+    let _ = self.file.unlock();
+  }
+}
+
+/// Takes the project lock, retrying for up to `wait` when another run holds
+/// it. The holder's PID is written into the file for the error message, and
+/// is the lock itself on filesystems without locking, where the PID of a
+/// process that is no longer running marks a stale lock.
+pub fn acquire(path: &Path, wait: Option<Duration>) -> Result<ProjectLock, String> {
+  let mut file = OpenOptions::new()
+    .read(true)
+    .write(true)
+    .create(true)
+    .truncate(false)
+    .open(path)
+    .map_err(|e| format!("Error opening lock file {}: {}", path.display(), e))?;
+
+  let deadline = wait.map(|wait| Instant::now() + wait);
+  loop {
+    let held = match file.try_lock() {
+      Ok(()) => false,
+      Err(TryLockError::WouldBlock) => true,
+      Err(TryLockError::Error(e)) if e.kind() == ErrorKind::Unsupported => pid_lock_held(&mut file, path),
+      Err(TryLockError::Error(e)) => return Err(format!("Error locking {}: {}", path.display(), e)),
+    };
+    if !held {
+      break;
+    }
+    if deadline.is_some_and(|deadline| Instant::now() < deadline) {
+      std::thread::sleep(POLL_INTERVAL);
+      continue;
+    }
+    let holder = match recorded_pid(&mut file) {
+      Some(pid) => format!(" (process {})", pid),
+      None => String::new(),
+    };
+    let hint = if wait.is_some() { "" } else { "; use --wait-for-lock to wait for it" };
+    return Err(format!("Another md_to_svelte run{} holds {}{}", holder, path.display(), hint));
+  }
+
+  file.set_len(0)
+    .and_then(|_| file.rewind())
+    .and_then(|_| write!(file, "{}", std::process::id()))
+    .map_err(|e| format!("Error writing lock file {}: {}", path.display(), e))?;
+  Ok(ProjectLock { file })
+}
+
+/// Whether the PID in the lock file names another running process, for
+/// filesystems without locking. A lock of a process that is gone is stale
+/// and taken over with a warning.
+fn pid_lock_held(file: &mut File, path: &Path) -> bool {
+  let Some(pid) = recorded_pid(file).filter(|pid| *pid != std::process::id()) else {
+    return false;
+  };
+  if process_alive(pid) {
+    return true;
+  }
+  warn!("Taking over stale lock {} of process {}, which is no longer running", path.display(), pid);
+  false
+}
+
+/// The PID the holder of the lock wrote into the file, if any.
+fn recorded_pid(file: &mut File) -> Option<u32> {
+  let mut text = String::new();
+  file.rewind().and_then(|_| file.read_to_string(&mut text)).ok()?;
+  text.trim().parse().ok().filter(|pid| *pid > 0)
+}
+
+/// Whether a process with this PID is running. Assumed to be where that
+/// can't be checked.
+fn process_alive(pid: u32) -> bool {
+  #[cfg(unix)]
+  {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+      return false;
+    };
+    // Signal 0 only checks whether the process exists; EPERM means it
+    // does but belongs to another user.
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+  }
+  #[cfg(not(unix))]
+  {
+    let _ = pid;
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::process::Command;
+
+  #[test]
+  fn second_lock_fails_at_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(LOCK_PATH);
+    let _held = acquire(&path, None).unwrap();
+    let started = Instant::now();
+    let e = acquire(&path, None).err().unwrap();
+    assert!(started.elapsed() < POLL_INTERVAL);
+    assert!(e.contains(&format!("(process {})", std::process::id())), "{}", e);
+    assert!(e.ends_with("use --wait-for-lock to wait for it"), "{}", e);
+  }
+
+  #[test]
+  fn waiting_gives_up_after_the_timeout() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(LOCK_PATH);
+    let _held = acquire(&path, None).unwrap();
+    let started = Instant::now();
+    let e = acquire(&path, Some(Duration::from_millis(300))).err().unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert!(!e.contains("--wait-for-lock"), "{}", e);
+  }
+
+  #[test]
+  fn waiting_takes_the_lock_once_released() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(LOCK_PATH);
+    let held = acquire(&path, None).unwrap();
+    let release = std::thread::spawn(move || {
+      std::thread::sleep(Duration::from_millis(200));
+      drop(held);
+    });
+    assert!(acquire(&path, Some(Duration::from_secs(10))).is_ok());
+    release.join().unwrap();
+  }
+
+  #[test]
+  fn dropping_releases_the_lock() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(LOCK_PATH);
+    drop(acquire(&path, None).unwrap());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    assert!(acquire(&path, None).is_ok());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn pid_lock_of_a_finished_process_is_stale() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(LOCK_PATH);
+    let mut running = Command::new("sleep").arg("10").spawn().unwrap();
+    let mut finished = Command::new("true").spawn().unwrap();
+    finished.wait().unwrap();
+
+    std::fs::write(&path, running.id().to_string()).unwrap();
+    let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    assert!(pid_lock_held(&mut file, &path));
+    std::fs::write(&path, finished.id().to_string()).unwrap();
+    assert!(!pid_lock_held(&mut file, &path));
+    std::fs::write(&path, std::process::id().to_string()).unwrap();
+    assert!(!pid_lock_held(&mut file, &path));
+
+    running.kill().unwrap();
+    running.wait().unwrap();
+  }
+}
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  Error = 0,
+  Warn = 1,
+  Info = 2,
+  Debug = 3,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static FAILED: AtomicBool = AtomicBool::new(false);
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn set_level(level: Level) {
+  LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+  level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn mark_failed() {
+  FAILED.store(true, Ordering::Relaxed);
+}
+
+/// Whether any error was reported during this run.
+pub fn failed() -> bool {
+  FAILED.load(Ordering::Relaxed)
+}
+
+/// Warnings are kept even when they are not printed so `--deny-warnings`
+/// can list them at the end of the build.
+pub fn record_warning(message: String) {
+  WARNINGS.lock().unwrap().push(message);
+}
+
+pub fn warnings() -> Vec<String> {
+  WARNINGS.lock().unwrap().clone()
+}
+
+/// Forgets earlier errors and warnings, for runs that build more than once.
+pub fn reset() {
+  FAILED.store(false, Ordering::Relaxed);
+  WARNINGS.lock().unwrap().clear();
+}
+
+/// Errors are always printed and make the process exit non-zero.
+#[macro_export]
+macro_rules! error {
+  ($($arg:tt)*) => {{
+    $crate::log::mark_failed();
+    eprintln!($($arg)*);
+  }};
+}
+
+#[macro_export]
+macro_rules! warn {
+  ($($arg:tt)*) => {{
+    let message = format!($($arg)*);
+    if $crate::log::enabled($crate::log::Level::Warn) {
+      eprintln!("Warning: {}", message);
+    }
+    $crate::log::record_warning(message);
+  }};
+}
+
+#[macro_export]
+macro_rules! info {
+  ($($arg:tt)*) => {{
+    if $crate::log::enabled($crate::log::Level::Info) {
+      println!($($arg)*);
+    }
+  }};
+}
+
+#[macro_export]
+macro_rules! debug {
+  ($($arg:tt)*) => {{
+    if $crate::log::enabled($crate::log::Level::Debug) {
+      println!($($arg)*);
+    }
+  }};
+}
@@ -1,577 +1,337 @@
-use pulldown_cmark::{html, Parser, Options};
-use regex::Regex;
-use serde::{Serialize, Deserialize};
-use std::fs::File;
-use std::io::{Write, Result};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use md_to_svelte::assets::Assets;
+use md_to_svelte::changes::ChangeSet;
+use md_to_svelte::config::{self, Config};
+use md_to_svelte::doctor::{self, DoctorOptions};
+use md_to_svelte::export;
+use md_to_svelte::ids;
+use md_to_svelte::list::{self, SortKey};
+use md_to_svelte::lock;
+use md_to_svelte::output;
+use md_to_svelte::secrets::SecretScanner;
+use md_to_svelte::site::{self, BuildOptions};
+use md_to_svelte::slug;
+use md_to_svelte::verify;
+use md_to_svelte::watch;
+use md_to_svelte::{error, info, log};
+use std::path::PathBuf;
 use std::path::Path;
-use walkdir::WalkDir;
-use serde_json::json;
-use chrono::NaiveDate;
-
-struct ContentType {
-  input_dir: &'static str,
-  output_dir: &'static str,
-  static_dir: &'static str,
-  is_article: bool,
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(version, about = "Converts Markdown content into SvelteKit routes")]
+struct Cli {
+  #[command(subcommand)]
+  command: Option<Command>,
+  /// Only print errors
+  #[arg(short, long, global = true)]
+  quiet: bool,
+  /// Also print debug messages, such as files skipped by ignore rules
+  #[arg(short, long, global = true, conflicts_with = "quiet")]
+  verbose: bool,
+  /// Path to the configuration file, which must exist when given [default:
+  /// md_to_svelte.toml, optional]
+  #[arg(long, global = true, value_name = "PATH")]
+  config: Option<PathBuf>,
+  /// Remove generated pages whose source file no longer exists
+  #[arg(long)]
+  prune: bool,
+  /// Command run once after each build with every generated file appended,
+  /// e.g. "npx prettier --write"
+  #[arg(long, value_name = "COMMAND")]
+  format_command: Option<String>,
+  /// Keep running and rebuild whenever a source changes
+  #[arg(long)]
+  watch: bool,
+  /// Parse every source again and leave the cache directory untouched
+  #[arg(long)]
+  no_cache: bool,
+  /// Only regenerate the pages of sources git reports as changed since
+  /// this ref; everything else comes from the previous build
+  #[arg(long, value_name = "GIT_REF", conflicts_with_all = ["watch", "no_cache"])]
+  changed_since: Option<String>,
+  /// Print a suggested Content-Security-Policy listing the external
+  /// origins the generated pages load scripts, styles and frames from
+  #[arg(long)]
+  csp_report: bool,
+  /// Print how long each source took to parse and write, slowest first
+  #[arg(long)]
+  timings: bool,
+  /// Also write the text of every published article in reading order to
+  /// <DIR>/<slug>.txt, for text-to-speech
+  #[arg(long, value_name = "DIR")]
+  emit_plaintext: Option<PathBuf>,
+  /// Also build entries marked `draft: true`
+  #[arg(long, global = true)]
+  drafts: bool,
+  /// Read markdown files that are not valid UTF-8, replacing invalid bytes,
+  /// instead of skipping them
+  #[arg(long, global = true)]
+  lossy_utf8: bool,
+  /// Wait up to this many seconds for another run to finish instead of
+  /// exiting immediately
+  #[arg(long, global = true, value_name = "SECONDS")]
+  wait_for_lock: Option<u64>,
+  /// Fail when the secrets scan finds anything
+  #[arg(long, global = true)]
+  strict_secrets: bool,
+  /// Write man pages for the tool and its subcommands into a directory and exit
+  #[arg(long, value_name = "DIR")]
+  generate_man: Option<PathBuf>,
+  /// Check the configuration file and exit
+  #[arg(long)]
+  validate_config: bool,
+  /// Give the page last built with this slug a new stable id and exit,
+  /// see id_namespace
+  #[arg(long, value_name = "SLUG")]
+  rekey: Option<String>,
+  /// Directory every output must stay inside, after resolving `..` and
+  /// symlinks
+  #[arg(long, global = true, value_name = "DIR", default_value = ".")]
+  output_root: PathBuf,
+  /// Fail the build if any warning was reported, or with
+  /// `--deny-warnings=<CATEGORY>,...` only warnings of those categories
+  #[arg(long, value_name = "CATEGORY", num_args = 0..=1, require_equals = true, value_delimiter = ',')]
+  deny_warnings: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct Author {
-  name: String,
-  url: Option<String>,
+#[derive(Subcommand)]
+enum Command {
+  /// Write all published content as a portable bundle of JSON, HTML and text
+  Export {
+    /// Directory to write the bundle into
+    dir: PathBuf,
+  },
+  /// Report syntax left over from other site generators in the sources
+  Doctor {
+    /// Show the mechanical fixes as diffs
+    #[arg(long)]
+    fix: bool,
+    /// Write the fixes back to the source files
+    #[arg(long, requires = "fix")]
+    write: bool,
+    /// Checks to leave out
+    #[arg(long, value_parser = doctor::detector_names())]
+    skip: Vec<String>,
+  },
+  /// Print a table of every entry, drafts included, without writing output
+  List {
+    /// Column to order the table by
+    #[arg(long, value_enum, default_value = "date")]
+    sort: SortKey,
+  },
+  /// Print a shell completion script
+  Completions {
+    shell: Shell,
+  },
+  /// Work with the configuration file
+  Config {
+    #[command(subcommand)]
+    command: ConfigCommand,
+  },
+  /// Work with page slugs
+  Slugs {
+    #[command(subcommand)]
+    command: SlugsCommand,
+  },
+  /// Check that every page of the last build is still in place
+  Verify {
+    /// Also compile every generated component with the site's Svelte
+    /// compiler, which needs Node.js and svelte in node_modules
+    #[arg(long)]
+    compile: bool,
+  },
 }
 
-#[derive(Deserialize)]
-struct FrontMatter {
-  #[serde(default)]
-  slug: String,
-  title: String,
-  #[serde(default)]
-  authors: Vec<Author>,
-  date: String,
-  tags: Vec<String>,
+#[derive(Subcommand)]
+enum ConfigCommand {
+  /// Validate the configuration and print the settings a build would use,
+  /// defaults included, as TOML
+  Check,
 }
 
-fn main() {
-  let content_types = vec![
-    ContentType {
-      input_dir: "data/articles",
-      output_dir: "src/routes/articles",
-      static_dir: "static/images/articles",
-      is_article: true,
-    },
-    ContentType {
-      input_dir: "data/projects",
-      output_dir: "src/routes/projects",
-      static_dir: "static/images/projects",
-      is_article: false,
-    },
-  ];
+#[derive(Subcommand)]
+enum SlugsCommand {
+  /// Pin the slug of every source, drafts included, in
+  /// md_to_svelte.slugs.json, so later changes to the slug rules keep
+  /// published URLs
+  Lock,
+}
 
-  for content_type in content_types {
-    let frontmatters = process_content(&content_type);
-    generate_data(&frontmatters, Path::new(content_type.output_dir), content_type.is_article)
-      .unwrap_or_else(|e| eprintln!("Error generating data: {}", e));
+fn main() -> ExitCode {
+  let cli = Cli::parse();
+  if cli.quiet {
+    log::set_level(log::Level::Error);
+  }
+  else if cli.verbose {
+    log::set_level(log::Level::Debug);
+  }
+  // These only describe the CLI and must work without a valid config.
+  if let Some(Command::Completions { shell }) = &cli.command {
+    clap_complete::generate(*shell, &mut Cli::command(), "md_to_svelte", &mut std::io::stdout());
+    return ExitCode::SUCCESS;
+  }
+  if let Some(dir) = &cli.generate_man {
+    return match std::fs::create_dir_all(dir).and_then(|_| clap_mangen::generate_to(Cli::command(), dir)) {
+      Ok(()) => ExitCode::SUCCESS,
+      Err(e) => {
+        error!("Error writing man pages to {}: {}", dir.display(), e);
+        ExitCode::FAILURE
+      }
+    };
+  }
 
-    let input_images = Path::new(content_type.input_dir).join("images");
-    if input_images.exists() {
-      std::fs::create_dir_all(content_type.static_dir)
-        .unwrap_or_else(|e| eprintln!("Error creating directory {}: {}", content_type.static_dir, e));
-      copy_dir_all(&input_images, Path::new(content_type.static_dir))
-        .unwrap_or_else(|e| eprintln!("Error copying images: {}", e));
+  let config_path = cli.config.as_deref().unwrap_or(Path::new(config::DEFAULT_CONFIG_PATH));
+  let config = match Config::load(config_path, cli.config.is_some()) {
+    Ok(config) => config,
+    Err(e) => {
+      error!("{}", e);
+      return ExitCode::FAILURE;
     }
+  };
+  if let Err(e) = config.check_output_root(&cli.output_root) {
+    error!("{}", e);
+    return ExitCode::FAILURE;
+  }
+  let assets = match Assets::load(config.assets_dir.as_deref()) {
+    Ok(assets) => Arc::new(assets),
+    Err(e) => {
+      error!("{}", e);
+      return ExitCode::FAILURE;
+    }
+  };
+  if let Some(Command::Config { command: ConfigCommand::Check }) = &cli.command {
+    print!("{}", config.effective());
+    return ExitCode::SUCCESS;
+  }
+  if cli.validate_config {
+    info!("Config {} is valid", config_path.display());
+    return ExitCode::SUCCESS;
   }
-}
-
-fn process_content(content_type: &ContentType) -> Vec<FrontMatter> {
-  WalkDir::new(content_type.input_dir)
-    .into_iter()
-    .filter_map(|entry| entry.ok())
-    .filter(|e| e.path().extension().map_or(false, |ext| ext == "md"))
-    .map(|entry| {
-      let input_path = entry.path();
-      let relative_path = input_path.strip_prefix(content_type.input_dir).unwrap();
-      let file_stem = relative_path.file_stem().unwrap().to_str().unwrap();
-      let output_path = Path::new(content_type.output_dir).join(file_stem).join("+page.svelte");
-
-      let content = std::fs::read_to_string(input_path)
-        .unwrap_or_else(|e| panic!("Error reading file {}: {}", input_path.display(), e));
-      let (mut frontmatter, markdown) = extract_frontmatter(&content);
-      frontmatter.slug = file_stem.to_string();
-      let html_content = markdown_to_html(&markdown);
-      let svelte_content = generate_svelte_component(&frontmatter, &html_content, content_type.is_article);
 
-      std::fs::create_dir_all(output_path.parent().unwrap())
-        .unwrap_or_else(|e| panic!("Error creating directory for {}: {}", output_path.display(), e));
-      std::fs::write(&output_path, svelte_content)
-        .unwrap_or_else(|e| panic!("Error writing to {}: {}", output_path.display(), e));
+  // Held until main returns, or released by the OS if the process dies.
+  let _lock = match lock::acquire(Path::new(lock::LOCK_PATH), cli.wait_for_lock.map(Duration::from_secs)) {
+    Ok(lock) => lock,
+    Err(e) => {
+      error!("{}", e);
+      return ExitCode::FAILURE;
+    }
+  };
 
-      frontmatter
-    })
-    .collect()
-}
+  if let Some(slug) = &cli.rekey {
+    let Some(namespace) = config.id_namespace() else {
+      error!("--rekey needs id_namespace in {}", config_path.display());
+      return ExitCode::FAILURE;
+    };
+    ids::rekey_slug(namespace, slug).unwrap_or_else(|e| error!("{}", e));
+    return if log::failed() { ExitCode::FAILURE } else { ExitCode::SUCCESS };
+  }
 
-fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
-  std::fs::create_dir_all(&dst)?;
-  for entry in std::fs::read_dir(src)? {
-    let entry = entry?;
-    let ty = entry.file_type()?;
-    if ty.is_dir() {
-      copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
+  // The config was validated, so the patterns compile.
+  let secrets = (config.secrets.scan || cli.strict_secrets).then(|| SecretScanner::new(&config.secrets).unwrap());
+  let changed_since = match &cli.changed_since {
+    Some(git_ref) => {
+      let content_types = config.content_types();
+      let dirs: Vec<&Path> = content_types.iter().map(|content_type| Path::new(&content_type.input_dir)).collect();
+      match ChangeSet::since(git_ref, &dirs) {
+        Ok(changes) => Some(changes),
+        Err(e) => {
+          error!("{}", e);
+          return ExitCode::FAILURE;
+        }
+      }
+    }
+    None => None,
+  };
+  let slug_lock = match slug::read_lock() {
+    Ok(slug_lock) => slug_lock,
+    Err(e) => {
+      error!("{}", e);
+      return ExitCode::FAILURE;
     }
-    else {
-      std::fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
+  };
+  let mut build_options = BuildOptions {
+    secrets,
+    strict_secrets: cli.strict_secrets,
+    prune: cli.prune,
+    drafts: cli.drafts,
+    respect_gitignore: config.respect_gitignore,
+    allow_case_collisions: config.allow_case_collisions,
+    on_slug_collision: config.on_slug_collision,
+    route_options: config.route_options,
+    format_command: cli.format_command.clone(),
+    lossy_utf8: cli.lossy_utf8,
+    markdown_extensions: config.markdown_extensions.clone(),
+    author_rules: config.author_rules.clone(),
+    slugs: config.slugs.clone(),
+    slug_lock,
+    cache_dir: (!cli.no_cache).then(|| config.cache_dir.clone()),
+    csp_report: cli.csp_report,
+    changed_since,
+    timings: cli.timings,
+    slow_file_ms: config.slow_file_ms,
+    emit_plaintext: cli.emit_plaintext.clone(),
+    assets,
+  };
+  if cli.command.is_none() {
+    if let Err(e) = output::preflight(&config.output_dirs()) {
+      error!("{}", e);
+      return ExitCode::FAILURE;
     }
   }
-  Ok(())
-}
-
-fn extract_frontmatter(content: &str) -> (FrontMatter, String) {
-  let re = Regex::new(r"(?s)^---\n(.*?)\n---\n(.*)$").unwrap();
-  let captures = re.captures(content).unwrap();
-
-  let frontmatter: FrontMatter = serde_yaml::from_str(&captures[1]).unwrap();
-  let markdown = captures[2].to_string();
-
-  (frontmatter, markdown)
-}
-
-fn markdown_to_html(markdown: &str) -> String {
-  let latex_regex = Regex::new(r"(\$\$.*?\$\$|\$.*?\$)").unwrap();
-  let escaped_markdown = latex_regex.replace_all(markdown, |caps: &regex::Captures| {
-    let latex = &caps[1];
-    latex.replace("_", "\\_")
-  }).to_string();
-
-  let mut options = Options::empty();
-  options.insert(Options::ENABLE_TABLES);
-  let parser = Parser::new_ext(&escaped_markdown, options);
-  let mut html_output = String::new();
-  html::push_html(&mut html_output, parser);
-
-  let block_math_regex = Regex::new(r"(?s)<p>\$\$(.*?)\$\$</p>").unwrap();
-  html_output = block_math_regex.replace_all(&html_output, |caps: &regex::Captures| {
-    let math = &caps[1].trim().replace("*", "\\*");
-    format!("\\[{}\\]", math)
-  }).to_string();
-
-  let inline_math_regex = Regex::new(r"\$([^$]+)\$").unwrap();
-  html_output = inline_math_regex.replace_all(&html_output, |caps: &regex::Captures| {
-    let math = &caps[1].replace("*", "\\*");
-    format!("\\({}\\)", math)
-  }).to_string();
-
-  let list_regex = Regex::new(r"(<[ou]l>(?:\s*<li>.*?</li>\s*)+</[ou]l>)").unwrap();
-  html_output = list_regex.replace_all(&html_output, |caps: &regex::Captures| {
-    format!("<div style=\"margin-left: 2em;\">{}</div>", &caps[1])
-  }).to_string();
-
-  let re = Regex::new(r#"<pre><code>([\s\S]*?)</code></pre>"#).unwrap();
-  html_output = re.replace_all(&html_output, |caps: &regex::Captures| {
-    let code = &caps[1];
-    let language = if code.starts_with("python") {
-      "language-python"
+  match &cli.command {
+    None if cli.watch => watch::watch(&config, &build_options),
+    None => site::build(&config, &build_options),
+    Some(Command::Export { dir }) => {
+      export::export(&config, &build_options, dir)
+        .unwrap_or_else(|e| error!("Error exporting to {}: {}", dir.display(), e));
     }
-    else if code.starts_with("vhdl") {
-      "language-vhdl"
+    Some(Command::Doctor { fix, write, skip }) => {
+      let options = DoctorOptions {
+        fix: *fix,
+        write: *write,
+        skip: skip.iter().cloned().collect(),
+      };
+      doctor::doctor(&config, &options)
+        .unwrap_or_else(|e| error!("Error checking sources: {}", e));
     }
-    else if code.starts_with("cpp") {
-      "language-cpp"
+    Some(Command::List { sort }) => {
+      build_options.drafts = true;
+      list::list(&config, &build_options, *sort);
     }
-    else if code.starts_with("c") {
-      "language-c"
+    Some(Command::Slugs { command: SlugsCommand::Lock }) => {
+      build_options.drafts = true;
+      slug::lock(&config, &build_options)
+        .unwrap_or_else(|e| error!("Error writing {}: {}", slug::SLUG_LOCK_PATH, e));
     }
-    else {
-      "language-none"
-    };
-    format!("<pre class=\"code-block\"><code class=\"{}\">{}</code></pre>", language, code)
-  }).to_string();
-  html_output
-}
-
-fn generate_data(frontmatters: &Vec<FrontMatter>, output_dir: &Path, is_article: bool) -> std::io::Result<()> {
-  let file_name = if is_article { "articleData.ts" } else { "projectData.ts" };
-  let output_path = output_dir.join(file_name);
-  let mut file = File::create(output_path)?;
-
-  let var_name = if is_article { "articles" } else { "projects" };
-  writeln!(file, "export const {} = [", var_name)?;
-  for frontmatter in frontmatters {
-    writeln!(file, "  {{")?;
-    writeln!(file, "    slug: '{}',", frontmatter.slug)?;
-    writeln!(file, "    title: '{}',", frontmatter.title.replace("'", "\\'"))?;
-    writeln!(file, "    authors: [")?;
-    for author in &frontmatter.authors {
-      write!(file, "      {{ name: '{}', ", author.name.replace("'", "\\'"))?;
-      if let Some(url) = &author.url {
-        writeln!(file, "url: '{}' }},", url.replace("'", "\\'"))?;
-      } else {
-        writeln!(file, "url: null }},")?;
-      }
+    Some(Command::Verify { compile }) => {
+      verify::verify(&config.content_types(), *compile, build_options.cache_dir.as_deref())
+        .unwrap_or_else(|e| error!("{}", e));
     }
-    writeln!(file, "    ],")?;
-    writeln!(file, "    date: '{}',", frontmatter.date)?;
-    writeln!(file, "    tags: {:?}", frontmatter.tags)?;
-    writeln!(file, "  }},")?;
+    Some(Command::Completions { .. } | Command::Config { .. }) => unreachable!(),
   }
-  writeln!(file, "];")?;
-
-  Ok(())
-}
-
-fn generate_svelte_component(frontmatter: &FrontMatter, html_content: &str, is_article: bool) -> String {
-  let tags_json = serde_json::to_string(&frontmatter.tags).unwrap();
-  let authors_json = serde_json::to_string(&frontmatter.authors).unwrap();
-  let date = NaiveDate::parse_from_str(&frontmatter.date, "%Y-%m-%d").unwrap();
-  let formatted_date = date.format("%B %d, %Y").to_string();
-
-  let image_path = if is_article { "images/articles" } else { "images/projects" };
-  let content_json = json!(html_content.replace(&format!("src=\"images/"), &format!("src=\"/{}/", image_path)));
-  let profile_image = include_str!("static/profile_image.svg");
-
-  format!(
-    r#"<script>
-    import {{ onMount }} from 'svelte';
-    import Prism from 'prismjs';
-    import 'prismjs/themes/prism-okaidia.css';
-    import 'prismjs/components/prism-python';
-    import 'prismjs/components/prism-vhdl';
-    import 'prismjs/components/prism-c';
-    import 'prismjs/components/prism-cpp';
-
-    export const title = '{}';
-    export const date = '{}';
-    export const tags = {};
-    export const authors = {};
-
-    let content = {};
-
-    onMount(() => {{
-      Prism.highlightAll();
-
-      window.MathJax = {{
-        tex: {{
-          inlineMath: [['\\(', '\\)']],
-          displayMath: [['\\[', '\\]'], ['$$', '$$']],
-          processEscapes: true,
-          processEnvironments: true
-        }},
-        options: {{
-          skipHtmlTags: ['script', 'noscript', 'style', 'textarea', 'pre']
-        }}
-      }};
-
-      const script = document.createElement('script');
-      script.src = 'https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-chtml.js';
-      script.async = true;
-      document.head.appendChild(script);
-
-      script.onload = () => {{
-        setTimeout(() => {{
-          MathJax.typesetPromise().catch((err) => {{
-            console.error('MathJax error:', err);
-          }});
-        }}, 100);
-      }};
-    }});
-  </script>
-
-  <div class="title">
-    <h1 class="title">{{title}}</h1>
-
-    <div class="meta">
-      <div class="profile" itemprop="author" itemtype="http://schema.org/Person" style="height:48px">
-        <!-- svelte-ignore a11y-img-redundant-alt -->
-        <img itemprop="image" src='data:image/png;base64,{profile_image}'>
-        <span class="mono authors">
-          {{#each authors as author, index}}
-            {{#if author.url}}
-              <a itemprop="name" href="{{author.url}}">{{author.name}}</a>
-            {{:else}}
-              <span itemprop="name">{{author.name}}</span>
-            {{/if}}
-            {{#if index < authors.length - 1}}<span class="ampersand">&amp;</span>{{/if}}
-          {{/each}}
-          <p class="subtitle">{{date}}</p>
-        </span>
-      </div>
-    </div>
-    <hr>
 
-    <div class="content">
-      {{@html content}}
-    </div>
-  </div>
-
-  <style>
-    .authors .ampersand {{
-      display: inline-block;
-      padding-right: 0.5em;
-    }}
-    * {{
-      margin: 0;
-      padding: 0;
-      box-sizing: border-box;
-      color: inherit;
-      text-decoration: inherit;
-    }}
-
-    html {{
-      background: var(--bg-0);
-      color: var(--text-0);
-      width: 100%;
-      text-rendering: optimizeLegibility;
-      font-feature-settings: "kern" 1;
-      font-feature-settings: "liga" 1;
-      min-width: 100vw;
-      overflow-x: hidden;
-      -webkit-text-size-adjust: 100%;
-    }}
-
-    @media all and (min-width:640px) {{
-      html {{
-        font-size: 16.5px;
-      }}
-    }}
-
-    @media all and (min-width:720px) {{
-      html {{
-        font-size: 17px;
-      }}
-    }}
-
-    @media all and (min-width:960px) {{
-      html {{
-        font-size: 18px;
-      }}
-    }}
-
-    body {{
-      background-color: #fffdf0;
-      max-width: 944px;
-      margin: 0 auto;
-      padding: 0 24px;
-      font-family: 'Berkeley Mono', monospace;
-    }}
-
-    header, h1, h2, h3, .sans {{
-      font-family: 'Berkeley Mono', monospace;
-      font-size: 18px;
-    }}
-
-    code, .mono, summary {{
-      font-family: 'Berkeley Mono', monospace;
-      font-weight: 500;
-    }}
-
-    .img-right {{
-      float: right;
-      height: 300px;
-      padding-left: 2em;
-    }}
-
-    body > header {{
-      display: flex;
-      justify-content: space-between;
-      align-items: center;
-      margin: 2em 0;
-    }}
-
-    nav a {{
-      margin-left: 1.5em;
-      letter-spacing: 0.07em;
-      font-size: .9rem;
-    }}
-
-    .m {{
-      margin-left: 11%;
-      position: relative;
-    }}
-
-    .r {{
-      text-align: end;
-    }}
-
-    h1 {{
-      font-size: 6em;
-    }}
-
-    .red {{
-      color: #EF5350;
-    }}
-
-    article {{
-      margin: 0 0 1rem -24px;
-      padding-left: 20px;
-      position: relative;
-      border-left: solid 4px;
-    }}
-
-    article > a {{
-      letter-spacing: 0.05em;
-    }}
-
-    article > div {{
-      font-size: .9rem;
-    }}
-
-    article > time {{
-      color: var(--text-1);
-      font-size: .9rem;
-      display: block;
-      margin-bottom: 4px;
-    }}
-
-    article > div {{
-      color: var(--text-1);
-    }}
-
-    article a {{
-      color: var(--text-0);
-      position: relative;
-    }}
-
-    article h1 {{
-      font-size: 2rem;
-      margin-bottom: 12px;
-      font-weight: 700;
-    }}
-
-    @media screen and (min-width: 1248px) {{
-      time {{
-        position: absolute;
-        left: 0;
-        top: 0;
-        transform: translateX(calc(-100% - 24px));
-      }}
-    }}
-
-    @media screen and (max-width: 1248px) {{
-      .shapes {{
-        display: none;
-      }}
-    }}
-
-    @media screen and (max-width: 1200px) {{
-      .m {{
-        margin-left: 0;
-      }}
-      .r {{
-        text-align: left;
-      }}
-
-      hgroup {{
-        margin-left: 0;
-        margin-right: 0;
-      }}
-
-      h1 {{
-        font-size: 4em;
-        line-height: 100%;
-      }}
-
-      h2 {{
-        font-size: 2em;
-        line-height: 100%;
-      }}
-    }}
-
-    body {{
-      font-family: 'Berkeley Mono', monospace;
-    }}
-
-    code, pre {{
-      font-family: 'Berkeley Mono', monospace;
-    }}
-
-    .header {{
-      display: flex;
-      justify-content: space-between;
-      align-items: center;
-      margin-top: 2rem;
-      margin-bottom: 2rem;
-    }}
-
-    .header__logo {{
-      font-family: sans-serif;
-      font-size: 1.125rem;
-    }}
-
-    .header__nav-link {{
-      margin-left: 1.5rem;
-      font-size: 0.875rem;
-      letter-spacing: 0.05em;
-      font-family: sans-serif;
-    }}
-
-    .main {{
-      max-width: 56rem;
-      margin-left: auto;
-      margin-right: auto;
-      padding-left: 1.5rem;
-      padding-right: 1.5rem;
-    }}
-
-    h1 {{
-      font-size: 2rem;
-      margin-bottom: 12px;
-    }}
-
-    .profile > img {{
-      display: inline;
-      object-fit: cover;
-      height: 48px;
-      width: 48px;
-      border-radius: 100%;
-      margin-right: 8px;
-      background: var(--bg-1);
-    }}
-
-    img:not(.profile img) {{
-      margin-top: 12px;
-      margin-bottom: 12px;
-    }}
-
-    pre {{
-      margin-top: 12px;
-      margin-bottom: 12px;
-    }}
-
-    .authors {{
-      position: absolute;
-      margin-top: 4px;
-      color: var(--text-1);
-      font-size: 16px;
-    }}
-
-    .subtitle {{
-      color: rgba(0, 0, 0, 66%);
-      font-size: 16px;
-    }}
-
-    hr {{
-      width: 164px;
-      border: 2.5px solid;
-      margin-top: 12px;
-      margin-bottom: 32px;
-    }}
-
-    h3, h2 {{
-      line-height: 24px;
-    }}
-
-    h2 {{
-      font-size: 1.2em;
-    }}
-
-    h1, h2, h3 {{
-      position: relative;
-      margin: 1.2rem 0 0 2rem 0;
-      margin-bottom: 12px;
-      margin-top: 12px;
-    }}
-
-    :not(.hgroup) h2:before {{
-      content: '\#';
-      position: absolute;
-      margin-left: -19px;
-    }}
+  if let Some(categories) = &cli.deny_warnings {
+    deny_warnings(categories);
+  }
 
-    table {{
-      border-collapse: separate;
-      border-spacing: 10px;
-    }}
+  if log::failed() { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
 
-    th, td {{
-      padding: 10px;
-      margin-bottom: 12px;
-    }}
-  </style>
-  "#,
-    frontmatter.title,
-    formatted_date,
-    tags_json,
-    authors_json,
-    content_json,
-  )
+/// Turns the recorded warnings into a failure. Categories are matched
+/// against the `[category]` tag of each warning; none means every warning.
+fn deny_warnings(categories: &[String]) {
+  let warnings: Vec<String> = log::warnings()
+    .into_iter()
+    .filter(|warning| categories.is_empty() || categories.iter().any(|category| warning.contains(&format!("[{}]", category))))
+    .collect();
+  if !warnings.is_empty() {
+    error!("Build failed with {} warnings (--deny-warnings):", warnings.len());
+    for warning in warnings {
+      error!("  {}", warning);
+    }
+  }
 }
@@ -0,0 +1,876 @@
+use pulldown_cmark::{html, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config::{ContentMode, UrlStyle};
+use crate::frontmatter::Reference;
+use crate::output::page_url;
+use crate::sanitize::{sanitize, MarkupFilter};
+use crate::slug::slugify;
+use crate::text::TextCount;
+use crate::RenderOptions;
+
+/// Placeholders in configured templates are written `{name}`.
+pub const TOKEN_PATTERN: &str = r"\{(\w+)\}";
+/// Placeholders a reference format may use.
+pub const REFERENCE_TOKENS: [&str; 3] = ["author", "title", "url"];
+/// Placeholders a revision badge may use.
+pub const REVISION_TOKENS: [&str; 2] = ["date", "note"];
+/// Extensions of the files a `![[...]]` embed shows as an image.
+const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "avif", "bmp"];
+/// Block tags markdown is commonly wrapped in, and that `:::` containers
+/// may name.
+pub const BLOCK_TAGS: [&str; 12] = [
+  "article", "aside", "blockquote", "details", "div", "figure", "footer", "header", "main", "nav", "section", "summary",
+];
+
+/// A heading of the rendered document, in document order.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heading {
+  pub id: String,
+  pub text: String,
+  pub level: u8,
+  /// Position of the heading in the rendered HTML, from 0 to 1.
+  pub offset_ratio: f64,
+}
+
+/// Rendered HTML plus what was learned about the document while rendering.
+pub struct Markdown {
+  pub html: String,
+  pub toc: Vec<Heading>,
+  /// Words outside code blocks, each CJK character counted as one.
+  pub word_count: usize,
+  /// Minutes to read the prose, rounded up.
+  pub reading_time: usize,
+  /// Image URLs as written in the source.
+  pub assets: Vec<String>,
+  /// Plain text of the document with blocks separated by blank lines.
+  pub text: String,
+  /// Reading text for `--emit-plaintext`, read as `options.plaintext` says.
+  pub plaintext: String,
+}
+
+/// How [`reading_text`] reads what is not prose. The default reads
+/// everything as written.
+#[derive(Default)]
+pub struct TextOptions<'a> {
+  /// Said in place of each code block; `None` reads the code.
+  pub code: Option<&'a str>,
+  /// Said in place of each formula; `None` reads the TeX source.
+  pub math: Option<&'a str>,
+  /// Put before the text of every heading.
+  pub heading_prefix: &'a str,
+  /// Leave images out rather than reading their alt text.
+  pub omit_images: bool,
+}
+
+/// Applies `replace` to the prose of a markdown document, leaving fenced code
+/// blocks and inline code spans untouched.
+fn replace_in_prose(markdown: &str, mut replace: impl FnMut(&str) -> String) -> String {
+  let mut output = String::with_capacity(markdown.len());
+  let mut fence: Option<&str> = None;
+
+  for line in markdown.split_inclusive('\n') {
+    let trimmed = line.trim_start();
+    if let Some(marker) = fence {
+      if trimmed.starts_with(marker) {
+        fence = None;
+      }
+      output.push_str(line);
+      continue;
+    }
+    if let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+      fence = Some(marker);
+      output.push_str(line);
+      continue;
+    }
+
+    // Odd segments between backticks are inline code.
+    for (i, segment) in line.split('`').enumerate() {
+      if i > 0 {
+        output.push('`');
+      }
+      if i % 2 == 1 {
+        output.push_str(segment);
+        continue;
+      }
+      output.push_str(&replace(segment));
+    }
+  }
+  output
+}
+
+/// Replaces `${NAME}` tokens in prose with their values.
+pub fn substitute_variables(markdown: &str, variables: &BTreeMap<String, String>, warnings: &mut Vec<String>) -> String {
+  let token_regex = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+  replace_in_prose(markdown, |segment| {
+    token_regex.replace_all(segment, |caps: &regex::Captures| {
+      match variables.get(&caps[1]) {
+        Some(value) => value.clone(),
+        None => {
+          warnings.push(format!("unknown variable ${{{}}} left as is", &caps[1]));
+          caps[0].to_string()
+        }
+      }
+    }).into_owned()
+  })
+}
+
+/// Turns Obsidian image embeds, `![[diagram.png]]` or
+/// `![[diagram.png|alt text]]`, into markdown images of
+/// `images/diagram.png`. A size after the bar (`|300`, `|300x200`) is
+/// dropped and the file stem becomes the alt text. Embeds of notes are left
+/// as written with a warning; `[[note]]` links are not embeds and are left
+/// alone.
+pub fn convert_wiki_embeds(markdown: &str, warnings: &mut Vec<String>) -> String {
+  let embed_regex = Regex::new(r"!\[\[([^\[\]|]+)(?:\|([^\[\]]*))?\]\]").unwrap();
+  let size_regex = Regex::new(r"^\d+(x\d+)?$").unwrap();
+  replace_in_prose(markdown, |segment| {
+    embed_regex.replace_all(segment, |caps: &regex::Captures| {
+      let target = caps[1].trim();
+      let path = std::path::Path::new(target);
+      let is_image = path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+      if !is_image {
+        warnings.push(format!("[wiki-embed] {} embeds a note, which is not supported; left as written", &caps[0]));
+        return caps[0].to_string();
+      }
+      let alt = caps.get(2)
+        .map(|alt| alt.as_str().trim())
+        .filter(|alt| !alt.is_empty() && !size_regex.is_match(alt))
+        .or(path.file_stem().and_then(|stem| stem.to_str()))
+        .unwrap_or_default();
+      format!("![{}](<images/{}>)", alt, target.trim_start_matches("images/"))
+    }).into_owned()
+  })
+}
+
+/// Turns `:::div.grid` ... `:::` containers into the named block element with
+/// those classes. Blank lines are put inside both tags, so the markdown in
+/// between is rendered in full. Containers may nest; one left open is
+/// closed at the end of the document with a warning.
+pub fn convert_containers(markdown: &str, warnings: &mut Vec<String>) -> String {
+  let open_regex = Regex::new(r"^:::\s*([a-z]+)((?:\.[\w-]+)*)\s*$").unwrap();
+  let mut output = String::with_capacity(markdown.len());
+  let mut open: Vec<(&str, usize)> = Vec::new();
+  let mut fence: Option<&str> = None;
+
+  for (i, line) in markdown.split_inclusive('\n').enumerate() {
+    let trimmed = line.trim_start();
+    if let Some(marker) = fence {
+      if trimmed.starts_with(marker) {
+        fence = None;
+      }
+      output.push_str(line);
+      continue;
+    }
+    if let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+      fence = Some(marker);
+      output.push_str(line);
+      continue;
+    }
+
+    if line.trim_end() == ":::" {
+      if let Some((tag, _)) = open.pop() {
+        output.push_str(&format!("\n</{}>\n\n", tag));
+        continue;
+      }
+    }
+    else if let Some(caps) = open_regex.captures(line.trim_end()) {
+      let tag = caps.get(1).unwrap().as_str();
+      if let Some(&tag) = BLOCK_TAGS.iter().find(|block_tag| **block_tag == tag) {
+        let classes: Vec<&str> = caps[2].split('.').filter(|class| !class.is_empty()).collect();
+        if classes.is_empty() {
+          output.push_str(&format!("<{}>\n\n", tag));
+        }
+        else {
+          output.push_str(&format!("<{} class=\"{}\">\n\n", tag, classes.join(" ")));
+        }
+        open.push((tag, i + 1));
+        continue;
+      }
+      warnings.push(format!("[container] line {}: `:::{}` is not one of {}; left as written", i + 1, tag, BLOCK_TAGS.join(", ")));
+    }
+    output.push_str(line);
+  }
+  while let Some((tag, line)) = open.pop() {
+    warnings.push(format!("[container] `:::{}` opened on line {} is never closed; closed at the end", tag, line));
+    output.push_str(&format!("\n</{}>\n", tag));
+  }
+  output
+}
+
+/// Turns `[@key]` citations into numbered links and appends a References
+/// section listing the cited entries in order of first citation.
+pub fn cite_references(markdown: &str, references: &[Reference], format: &str, warnings: &mut Vec<String>) -> String {
+  let citation_regex = Regex::new(r"\[@([\w:.-]+)\]").unwrap();
+  let mut cited: Vec<&Reference> = Vec::new();
+  let mut output = replace_in_prose(markdown, |segment| {
+    citation_regex.replace_all(segment, |caps: &regex::Captures| {
+      let Some(reference) = references.iter().find(|reference| reference.key == caps[1]) else {
+        warnings.push(format!("citation [@{}] has no entry in references, left as is", &caps[1]));
+        return caps[0].to_string();
+      };
+      let number = match cited.iter().position(|cited| cited.key == reference.key) {
+        Some(index) => index + 1,
+        None => {
+          cited.push(reference);
+          cited.len()
+        }
+      };
+      format!("<sup class=\"citation\"><a href=\"#ref-{}\">[{}]</a></sup>", slugify(&reference.key), number)
+    }).into_owned()
+  });
+
+  if !cited.is_empty() {
+    output.push_str("\n\n<section class=\"references\">\n<h2>References</h2>\n<ol>\n");
+    for reference in cited {
+      output.push_str(&format!("<li id=\"ref-{}\">{}</li>\n", slugify(&reference.key), format_reference(reference, format)));
+    }
+    output.push_str("</ol>\n</section>\n");
+  }
+  output
+}
+
+/// Fills a reference format. A token whose value is missing is dropped
+/// together with the text that follows it up to the next token.
+fn format_reference(reference: &Reference, format: &str) -> String {
+  let token_regex = Regex::new(TOKEN_PATTERN).unwrap();
+  let mut output = String::new();
+  let mut skip_literal = false;
+  let mut last = 0;
+  for caps in token_regex.captures_iter(format) {
+    let token = caps.get(0).unwrap();
+    if !skip_literal {
+      output.push_str(&format[last..token.start()]);
+    }
+    last = token.end();
+    let value = match &caps[1] {
+      "author" => reference.author.as_deref().map(escape_html),
+      "title" => Some(escape_html(&reference.title)),
+      "url" => reference.url.as_deref().map(|url| format!("<a href=\"{0}\">{0}</a>", escape_html(url))),
+      _ => None,
+    };
+    skip_literal = value.is_none();
+    output.push_str(&value.unwrap_or_default());
+  }
+  if !skip_literal {
+    output.push_str(&format[last..]);
+  }
+  output.trim().to_string()
+}
+
+/// Fills a revision badge template with the formatted date and the note,
+/// an empty string when the revision has none.
+pub fn revision_badge(template: &str, date: &str, note: Option<&str>) -> String {
+  let token_regex = Regex::new(TOKEN_PATTERN).unwrap();
+  token_regex.replace_all(template, |caps: &regex::Captures| {
+    match &caps[1] {
+      "date" => escape_html(date),
+      "note" => escape_html(note.unwrap_or_default()),
+      _ => caps[0].to_string(),
+    }
+  }).into_owned()
+}
+
+/// Text of the first `# ` heading and the byte range of its source, for
+/// documents that take their title from it.
+pub fn first_h1(markdown: &str) -> Option<(String, std::ops::Range<usize>)> {
+  let mut heading: Option<(String, std::ops::Range<usize>)> = None;
+  for (event, range) in Parser::new(markdown).into_offset_iter() {
+    match event {
+      Event::Start(Tag::Heading(HeadingLevel::H1, ..)) => heading = Some((String::new(), range)),
+      Event::Text(text) | Event::Code(text) => {
+        if let Some((title, _)) = &mut heading {
+          title.push_str(&text);
+        }
+      }
+      Event::End(Tag::Heading(HeadingLevel::H1, ..)) => return heading.map(|(title, range)| (title.trim().to_string(), range)),
+      _ => {}
+    }
+  }
+  None
+}
+
+/// Renders a configured markdown or HTML snippet without any of the
+/// document transforms.
+pub fn render_snippet(markdown: &str) -> String {
+  let mut html_output = String::new();
+  html::push_html(&mut html_output, Parser::new(markdown));
+  html_output
+}
+
+fn escape_html(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// An image reference in the `images/...` form when it names one of the
+/// content type's images as `./images/...`, or as `/images/...` with
+/// `root_images`.
+pub(crate) fn image_path(url: &str, root_images: bool) -> String {
+  if let Some(path) = url.strip_prefix("./images/") {
+    return format!("images/{}", path);
+  }
+  match url.strip_prefix("/images/") {
+    Some(path) if root_images => format!("images/{}", path),
+    _ => url.to_string(),
+  }
+}
+
+/// Fences marked `math`, as on GitHub, hold display math.
+fn is_math_fence(info: &str) -> bool {
+  info.split_whitespace().next() == Some("math")
+}
+
+/// Splits a fence info string naming a file, `rust:src/main.rs` or
+/// `rust filename="src/main.rs"`, into the language and the file name.
+fn code_filename(info: &str) -> Option<(String, String)> {
+  let first = info.split_whitespace().next()?;
+  if let Some((language, filename)) = first.split_once(':').filter(|(_, filename)| !filename.is_empty()) {
+    return Some((language.to_string(), filename.to_string()));
+  }
+  let attribute = Regex::new(r#"(?:^|\s)filename=(?:"([^"]*)"|(\S+))"#).unwrap();
+  let caps = attribute.captures(info)?;
+  let filename = caps.get(1).or(caps.get(2))?.as_str();
+  let language = if first.starts_with("filename=") { "" } else { first };
+  Some((language.to_string(), filename.to_string()))
+}
+
+/// Splits inline code written `rust:let x = 5` into the language and the
+/// code, when the prefix is one of `languages` and code follows it.
+fn inline_code_language<'a>(code: &'a str, languages: &'a [String]) -> Option<(&'a str, &'a str)> {
+  let (prefix, rest) = code.split_once(':')?;
+  let language = languages.iter().find(|language| language.as_str() == prefix)?;
+  (!rest.trim().is_empty()).then_some((language.as_str(), rest))
+}
+
+/// Renders a document. `badges` holds markup appended to the headings with
+/// those ids; the TOC text leaves it out.
+pub fn markdown_to_html(markdown: &str, options: &RenderOptions, number_headings: bool, badges: &BTreeMap<String, String>) -> Markdown {
+  let latex_regex = Regex::new(r"(\$\$.*?\$\$|\$.*?\$)").unwrap();
+  let escaped_markdown = latex_regex.replace_all(markdown, |caps: &regex::Captures| {
+    let latex = &caps[1];
+    latex.replace("_", "\\_")
+  }).to_string();
+
+  let mut parser_options = Options::empty();
+  if options.tables {
+    parser_options.insert(Options::ENABLE_TABLES);
+  }
+  if options.content.tasklists {
+    parser_options.insert(Options::ENABLE_TASKLISTS);
+  }
+  // Languages of inline code spans, in document order, with the prefix
+  // already taken off the code.
+  let mut code_languages: Vec<Option<String>> = Vec::new();
+  let events: Vec<Event> = Parser::new_ext(&escaped_markdown, parser_options)
+    .map(|event| match event {
+      Event::Start(Tag::Image(kind, url, title)) => {
+        Event::Start(Tag::Image(kind, image_path(&url, options.content.root_images).into(), title))
+      }
+      Event::Code(code) => match inline_code_language(&code, &options.content.inline_code_languages) {
+        Some((language, code)) => {
+          code_languages.push(Some(language.to_string()));
+          Event::Code(code.to_string().into())
+        }
+        None => {
+          code_languages.push(None);
+          Event::Code(code)
+        }
+      },
+      event => event,
+    })
+    .collect();
+  let (mut toc, count, assets) = scan_events(&events);
+  let text = reading_text(&events, &TextOptions::default());
+  let plaintext = reading_text(&events, &options.plaintext.text_options());
+  let numbers = if number_headings {
+    heading_numbers(&toc, options.content.appendix_heading.as_deref())
+  }
+  else {
+    vec![None; toc.len()]
+  };
+
+  // Give every heading the id its TOC entry links to, and its number if
+  // any, task list items a real checkbox, and ```math fences the display
+  // math delimiters instead of a code block, and fences naming a file a
+  // header with the file name above the code. Revision badges go at the end
+  // of their heading.
+  let ids: Vec<String> = toc.iter().map(|heading| heading.id.clone()).collect();
+  let mut headings = ids.iter().zip(&numbers);
+  let mut badge: Option<&String> = None;
+  let mut code_languages = code_languages.into_iter();
+  let mut markup = (options.content.content_mode == ContentMode::Markup).then(|| MarkupFilter::new(&options.content));
+  let events = events.into_iter().flat_map(|event| match event {
+    Event::Start(Tag::Heading(level, None, classes)) => {
+      let (id, number) = headings.next().unzip();
+      badge = id.and_then(|id| badges.get(id));
+      let mut events = vec![Event::Start(Tag::Heading(level, id.map(String::as_str), classes))];
+      if let Some(number) = number.and_then(Option::as_ref) {
+        events.push(Event::Html(format!("<span class=\"heading-number\">{}</span> ", number).into()));
+      }
+      events
+    }
+    Event::End(Tag::Heading(..)) => match badge.take() {
+      Some(badge) => vec![Event::Html(format!(" {}", badge).into()), event],
+      None => vec![event],
+    },
+    Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) if is_math_fence(&info) => vec![Event::Html("\\[".into())],
+    Event::End(Tag::CodeBlock(CodeBlockKind::Fenced(info))) if is_math_fence(&info) => vec![Event::Html("\\]\n".into())],
+    Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => match code_filename(&info) {
+      Some((language, filename)) => vec![
+        Event::Html(format!("<div class=\"code-filename\">{}</div>\n", escape_html(&filename)).into()),
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(language.into()))),
+      ],
+      None => vec![Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))],
+    },
+    Event::Code(code) => match code_languages.next().flatten() {
+      Some(language) => vec![Event::Html(format!("<code class=\"language-{}\">{}</code>", language, escape_html(&code)).into())],
+      None => vec![Event::Code(code)],
+    },
+    // "markup" mode writes the HTML out as component markup, so raw HTML
+    // Svelte would compile as a script, style or directive is shown as text.
+    Event::Html(raw) if markup.as_mut().is_some_and(|markup| !markup.allows(&raw)) => vec![Event::Text(raw)],
+    Event::TaskListMarker(checked) => {
+      let checked = if checked { " checked" } else { "" };
+      vec![Event::Html(format!("<input type=\"checkbox\" class=\"task-checkbox\" disabled{}> ", checked).into())]
+    }
+    event => vec![event],
+  });
+  let mut html_output = String::new();
+  html::push_html(&mut html_output, events);
+  for (heading, number) in toc.iter_mut().zip(&numbers) {
+    if let Some(number) = number {
+      heading.text = format!("{} {}", number, heading.text);
+    }
+  }
+
+  let block_math_regex = Regex::new(r"(?s)<p>\$\$(.*?)\$\$</p>").unwrap();
+  html_output = block_math_regex.replace_all(&html_output, |caps: &regex::Captures| {
+    let math = &caps[1].trim().replace("*", "\\*");
+    format!("\\[{}\\]", math)
+  }).to_string();
+
+  let inline_math_regex = Regex::new(r"\$([^$]+)\$").unwrap();
+  html_output = inline_math_regex.replace_all(&html_output, |caps: &regex::Captures| {
+    let math = &caps[1].replace("*", "\\*");
+    format!("\\({}\\)", math)
+  }).to_string();
+
+  let list_regex = Regex::new(r"(<[ou]l>(?:\s*<li>.*?</li>\s*)+</[ou]l>)").unwrap();
+  html_output = list_regex.replace_all(&html_output, |caps: &regex::Captures| {
+    format!("<div style=\"margin-left: 2em;\">{}</div>", &caps[1])
+  }).to_string();
+
+  let re = Regex::new(r#"<pre><code>([\s\S]*?)</code></pre>"#).unwrap();
+  html_output = re.replace_all(&html_output, |caps: &regex::Captures| {
+    let code = &caps[1];
+    let language = if code.starts_with("python") {
+      "language-python"
+    }
+    else if code.starts_with("vhdl") {
+      "language-vhdl"
+    }
+    else if code.starts_with("cpp") {
+      "language-cpp"
+    }
+    else if code.starts_with("c") {
+      "language-c"
+    }
+    else {
+      "language-none"
+    };
+    format!("<pre class=\"code-block\"><code class=\"{}\">{}</code></pre>", language, code)
+  }).to_string();
+
+  html_output = html_output.replace("src=\"./images/", "src=\"images/");
+  if options.content.root_images {
+    html_output = html_output.replace("src=\"/images/", "src=\"images/");
+  }
+  html_output = html_output.replace("src=\"images/", &format!("src=\"{}/", options.image_base));
+  if let Some(style) = options.url_style {
+    html_output = normalize_page_links(&html_output, &options.page_routes, style, options.content.permalink().split('/').count());
+  }
+  if options.content.sanitize {
+    html_output = sanitize(&html_output, &options.content);
+  }
+  set_offset_ratios(&mut toc, &html_output);
+
+  let reading_time = count.reading_minutes(options.content.words_per_minute(), options.content.cjk_characters_per_minute());
+  Markdown { html: html_output, toc, word_count: count.total(), reading_time, assets, text, plaintext }
+}
+
+/// Hierarchical numbers for h2 to h4, such as `2.3`. An h1 starts the
+/// count over and is not numbered itself. Headings after the one whose text
+/// is `appendix` are lettered instead (`A`, `A.1`); the marker itself is not
+/// numbered.
+fn heading_numbers(toc: &[Heading], appendix: Option<&str>) -> Vec<Option<String>> {
+  let mut counters = [0usize; 3];
+  let mut in_appendix = false;
+  toc.iter().map(|heading| {
+    if appendix == Some(heading.text.trim()) {
+      in_appendix = true;
+      counters = [0; 3];
+      return None;
+    }
+    if heading.level == 1 {
+      counters = [0; 3];
+      return None;
+    }
+    if heading.level > 4 {
+      return None;
+    }
+    let depth = heading.level as usize - 2;
+    counters[depth] += 1;
+    counters[depth + 1..].fill(0);
+    let parts: Vec<String> = counters[..=depth].iter().enumerate().map(|(i, &counter)| {
+      if i == 0 && in_appendix {
+        // Past Z the letters repeat; no real appendix gets that long.
+        char::from(b'A' + ((counter.max(1) - 1) % 26) as u8).to_string()
+      }
+      else {
+        counter.to_string()
+      }
+    }).collect();
+    Some(parts.join("."))
+  }).collect()
+}
+
+/// Rewrites links to generated pages below `routes`, written with or
+/// without a trailing slash, to `style`. Page paths below a route have
+/// `segments` segments, as many as the permalink pattern.
+fn normalize_page_links(html: &str, routes: &[String], style: UrlStyle, segments: usize) -> String {
+  let routes: Vec<String> = routes.iter().map(|route| regex::escape(route)).collect();
+  let link = Regex::new(&format!(r#"href="((?:{})(?:/[^"/?#]+){{{}}})/?([?#][^"]*)?""#, routes.join("|"), segments)).unwrap();
+  link.replace_all(html, |caps: &regex::Captures| {
+    let (route, slug) = caps[1].rsplit_once('/').unwrap();
+    format!("href=\"{}{}\"", page_url(route, slug, style), caps.get(2).map_or("", |rest| rest.as_str()))
+  }).to_string()
+}
+
+/// Records where each heading starts in the final HTML. Ratios are rounded
+/// to four places so the exported numbers don't depend on float formatting.
+fn set_offset_ratios(toc: &mut [Heading], html: &str) {
+  let mut position = 0;
+  for heading in toc {
+    let tag = format!("<h{} id=\"{}\"", heading.level, heading.id);
+    if let Some(offset) = html[position..].find(&tag) {
+      position += offset;
+      heading.offset_ratio = (position as f64 / html.len() as f64 * 10000.0).round() / 10000.0;
+    }
+  }
+}
+
+/// Collects the headings, amount of prose and referenced images.
+fn scan_events(events: &[Event]) -> (Vec<Heading>, TextCount, Vec<String>) {
+  let mut toc: Vec<Heading> = Vec::new();
+  let mut count = TextCount::default();
+  let mut assets = Vec::new();
+  let mut heading: Option<(u8, String)> = None;
+  let mut in_code_block = false;
+
+  for event in events {
+    match event {
+      Event::Start(Tag::Heading(level, _, _)) => heading = Some((*level as u8, String::new())),
+      Event::End(Tag::Heading(..)) => {
+        if let Some((level, text)) = heading.take() {
+          let id = unique_id(&text, &toc);
+          toc.push(Heading { id, text, level, offset_ratio: 0.0 });
+        }
+      }
+      Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+      Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+      Event::Start(Tag::Image(_, url, _)) => assets.push(url.to_string()),
+      Event::Text(content) | Event::Code(content) => {
+        if let Some((_, heading_text)) = &mut heading {
+          heading_text.push_str(content);
+        }
+        if !in_code_block {
+          count.add(content);
+        }
+      }
+      _ => {}
+    }
+  }
+  (toc, count, assets)
+}
+
+/// Plain text of a document in reading order, taken from the parser events
+/// so entities come out decoded and raw HTML is left out. Blocks are
+/// separated by blank lines.
+pub(crate) fn reading_text(events: &[Event], options: &TextOptions) -> String {
+  let math_regex = Regex::new(r"(?s)\$\$.*?\$\$|\$.*?\$").unwrap();
+  let mut text = String::new();
+  // Consecutive text events and line breaks, joined so math split across
+  // them is found.
+  let mut run = String::new();
+  let mut in_code_block = false;
+  let mut skipping = false;
+
+  for event in events {
+    if !matches!(event, Event::Text(_) | Event::SoftBreak | Event::HardBreak) && !run.is_empty() {
+      match options.math {
+        Some(placeholder) => text.push_str(&math_regex.replace_all(&run, regex::NoExpand(placeholder))),
+        None => text.push_str(&run),
+      }
+      run.clear();
+    }
+    match event {
+      Event::Start(Tag::Heading(..)) => text.push_str(options.heading_prefix),
+      Event::Start(Tag::CodeBlock(kind)) => {
+        in_code_block = true;
+        let math = matches!(kind, CodeBlockKind::Fenced(info) if is_math_fence(info));
+        if let Some(placeholder) = if math { options.math } else { options.code } {
+          text.push_str(placeholder);
+          skipping = true;
+        }
+      }
+      Event::End(Tag::CodeBlock(_)) => {
+        in_code_block = false;
+        skipping = false;
+      }
+      Event::Start(Tag::Image(..)) if options.omit_images => skipping = true,
+      Event::End(Tag::Image(..)) if options.omit_images => skipping = false,
+      Event::Text(_) | Event::Code(_) if skipping => {}
+      Event::Text(content) if !in_code_block => run.push_str(content),
+      Event::Text(content) | Event::Code(content) => text.push_str(content),
+      Event::SoftBreak => run.push(' '),
+      Event::HardBreak => run.push('\n'),
+      Event::End(Tag::TableCell) => text.push(' '),
+      _ => {}
+    }
+    if let Event::End(Tag::Paragraph | Tag::Heading(..) | Tag::CodeBlock(_) | Tag::Item | Tag::TableRow | Tag::TableHead) = event {
+      text.truncate(text.trim_end().len());
+      text.push_str("\n\n");
+    }
+  }
+  text.truncate(text.trim_end().len());
+  text
+}
+
+/// Slugifies heading text, suffixing `-1`, `-2`, ... on repeats.
+fn unique_id(text: &str, toc: &[Heading]) -> String {
+  let mut base = slugify(text);
+  if base.is_empty() {
+    base = "section".to_string();
+  }
+  let mut id = base.clone();
+  let mut n = 1;
+  while toc.iter().any(|heading| heading.id == id) {
+    id = format!("{}-{}", base, n);
+    n += 1;
+  }
+  id
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::ContentConfig;
+
+  fn render(markdown: &str, options: &RenderOptions) -> Markdown {
+    markdown_to_html(markdown, options, false, &BTreeMap::new())
+  }
+
+  #[test]
+  fn offset_ratios_increase_through_the_document() {
+    let mut markdown = String::new();
+    for title in ["Intro", "Setup", "Setup", "Usage", "Summary"] {
+      markdown.push_str(&format!("## {}\n\n{}\n\n", title, "Some prose. ".repeat(20)));
+    }
+    let toc = render(&markdown, &RenderOptions::default()).toc;
+    let ratios: Vec<f64> = toc.iter().map(|heading| heading.offset_ratio).collect();
+    assert_eq!(ratios.len(), 5);
+    assert_eq!(ratios[0], 0.0);
+    assert!(ratios.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", ratios);
+    assert!(ratios[4] < 1.0, "{:?}", ratios);
+    assert!(serde_json::to_string(&toc[1]).unwrap().contains("\"offsetRatio\":"));
+  }
+
+  #[test]
+  fn task_lists_render_disabled_checkboxes() {
+    let markdown = "- [x] Done\n- [ ] Todo\n- Plain\n";
+    let options = RenderOptions::default().content(ContentConfig { tasklists: true, ..ContentConfig::default() });
+    let html = render(markdown, &options).html;
+    assert!(html.contains("<li><input type=\"checkbox\" class=\"task-checkbox\" disabled checked> Done</li>"), "{}", html);
+    assert!(html.contains("<li><input type=\"checkbox\" class=\"task-checkbox\" disabled> Todo</li>"), "{}", html);
+    assert!(html.contains("<li>Plain</li>"), "{}", html);
+
+    let html = render(markdown, &RenderOptions::default()).html;
+    assert!(!html.contains("checkbox"), "{}", html);
+    assert!(html.contains("<li>[x] Done</li>"), "{}", html);
+  }
+
+  #[test]
+  fn math_fence_is_display_math() {
+    let html = render("Before\n\n```math\n\\frac{a*b}{2} < 1\n```\n\nAfter $x*y$\n", &RenderOptions::default()).html;
+    assert!(html.contains("\\[\\frac{a*b}{2} &lt; 1\n\\]"), "{}", html);
+    assert!(!html.contains("<pre>"), "{}", html);
+    assert!(!html.contains("language-math"), "{}", html);
+    assert!(html.contains("After \\(x\\*y\\)"), "{}", html);
+
+    let html = render("```rust\nlet math = 1;\n```\n", &RenderOptions::default()).html;
+    assert!(html.contains("<pre><code class=\"language-rust\">"), "{}", html);
+  }
+
+  #[test]
+  fn relative_and_root_image_paths() {
+    let markdown = "![a](./images/x.png) ![b](/images/x.png) ![c](images/x.png)\n\n<img src=\"./images/y.png\">\n";
+    let options = RenderOptions::default().image_base("/images/articles");
+    let rendered = render(markdown, &options);
+    assert_eq!(rendered.html.matches("src=\"/images/articles/x.png\"").count(), 2, "{}", rendered.html);
+    assert!(rendered.html.contains("src=\"/images/x.png\""), "{}", rendered.html);
+    assert!(rendered.html.contains("src=\"/images/articles/y.png\""), "{}", rendered.html);
+    assert_eq!(rendered.assets, ["images/x.png", "/images/x.png", "images/x.png"]);
+
+    let options = options.content(ContentConfig { root_images: true, ..ContentConfig::default() });
+    let rendered = render(markdown, &options);
+    assert_eq!(rendered.html.matches("src=\"/images/articles/x.png\"").count(), 3, "{}", rendered.html);
+    assert_eq!(rendered.assets, ["images/x.png", "images/x.png", "images/x.png"]);
+  }
+
+  #[test]
+  fn containers_render_lists_code_and_math() {
+    let mut warnings = Vec::new();
+    let markdown = convert_containers(":::div.grid.wide\n- one\n- two\n\n```rust\n:::\n```\n$$a*b$$\n:::\nAfter\n", &mut warnings);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    let html = render(&markdown, &RenderOptions::default()).html;
+    assert!(html.starts_with("<div class=\"grid wide\">\n"), "{}", html);
+    assert!(html.contains("<li>one</li>\n<li>two</li>"), "{}", html);
+    assert!(html.contains("language-rust\">:::\n</code>"), "{}", html);
+    assert!(html.contains("\\[a\\*b\\]"), "{}", html);
+    assert!(html.contains("</div>\n<p>After</p>"), "{}", html);
+  }
+
+  #[test]
+  fn containers_nest_and_are_closed_at_the_end() {
+    let mut warnings = Vec::new();
+    let markdown = convert_containers(":::section\n:::aside.note\n*inner*\n:::\n**outer**\n", &mut warnings);
+    let html = render(&markdown, &RenderOptions::default()).html;
+    assert!(html.contains("<section>\n<aside class=\"note\">\n<p><em>inner</em></p>\n</aside>\n<p><strong>outer</strong></p>\n</section>"), "{}", html);
+    assert_eq!(warnings, ["[container] `:::section` opened on line 1 is never closed; closed at the end"]);
+
+    let mut warnings = Vec::new();
+    assert_eq!(convert_containers(":::span.x\ntext\n", &mut warnings), ":::span.x\ntext\n");
+    assert!(warnings[0].starts_with("[container] line 1: `:::span` is not one of article, aside"), "{:?}", warnings);
+  }
+
+  #[test]
+  fn block_tags_need_blank_lines_around_markdown() {
+    let markdown = "<div class=\"grid\">\n\n- one\n\n```rust\nlet x = 1;\n```\n\n$$a*b$$\n\n</div>\n";
+    let html = render(markdown, &RenderOptions::default()).html;
+    assert!(html.contains("<li>one</li>"), "{}", html);
+    assert!(html.contains("language-rust"), "{}", html);
+    assert!(html.contains("\\[a\\*b\\]"), "{}", html);
+
+    // Without them the list is passed through as typed.
+    let html = render("<div class=\"grid\">\n- one\n</div>\n", &RenderOptions::default()).html;
+    assert!(html.contains("- one"), "{}", html);
+    assert!(!html.contains("<li>"), "{}", html);
+  }
+
+  #[test]
+  fn code_fences_name_their_file() {
+    assert_eq!(code_filename("rust:Cargo.toml"), Some(("rust".to_string(), "Cargo.toml".to_string())));
+    assert_eq!(code_filename("toml filename=\"my file.toml\""), Some(("toml".to_string(), "my file.toml".to_string())));
+    assert_eq!(code_filename("filename=build.sh"), Some((String::new(), "build.sh".to_string())));
+    assert_eq!(code_filename("rust"), None);
+    assert_eq!(code_filename("rust:"), None);
+
+    let html = render("```rust:Cargo.toml\n[package]\n```\n", &RenderOptions::default()).html;
+    assert!(html.starts_with("<div class=\"code-filename\">Cargo.toml</div>\n"), "{}", html);
+    assert!(html.contains("language-rust"), "{}", html);
+    assert!(!html.contains("language-rust:"), "{}", html);
+  }
+
+  #[test]
+  fn inline_code_takes_a_listed_language_prefix() {
+    let content = ContentConfig { inline_code_languages: vec!["rust".to_string()], ..ContentConfig::default() };
+    let options = RenderOptions::default().content(content);
+    let html = render("Write `rust:let x = 5;`, `std::io`, `python:pass`, `rust:` and `plain`.", &options).html;
+    assert!(html.contains(r#"<code class="language-rust">let x = 5;</code>"#), "{}", html);
+    for plain in ["std::io", "python:pass", "rust:", "plain"] {
+      assert!(html.contains(&format!("<code>{}</code>", plain)), "{}: {}", plain, html);
+    }
+
+    let html = render("Write `rust:let x = 5;`.", &RenderOptions::default()).html;
+    assert!(html.contains("<code>rust:let x = 5;</code>"), "{}", html);
+  }
+
+  #[test]
+  fn wiki_embeds_of_images_become_images() {
+    let mut warnings = Vec::new();
+    let markdown = "![[diagram.png]] ![[diagram.png|300]] ![[images/My chart.JPG|A *chart*]]\n\n[[Note]] ![[Some note]] `![[code.png]]`\n";
+    let converted = convert_wiki_embeds(markdown, &mut warnings);
+    assert_eq!(
+      converted,
+      "![diagram](<images/diagram.png>) ![diagram](<images/diagram.png>) ![A *chart*](<images/My chart.JPG>)\n\n[[Note]] ![[Some note]] `![[code.png]]`\n",
+    );
+    assert_eq!(warnings, ["[wiki-embed] ![[Some note]] embeds a note, which is not supported; left as written"]);
+  }
+
+  fn numbered(markdown: &str, appendix: Option<&str>) -> Markdown {
+    let content = ContentConfig { appendix_heading: appendix.map(str::to_string), ..ContentConfig::default() };
+    markdown_to_html(markdown, &RenderOptions::default().content(content), true, &BTreeMap::new())
+  }
+
+  fn toc_text(markdown: &Markdown) -> Vec<&str> {
+    markdown.toc.iter().map(|heading| heading.text.as_str()).collect()
+  }
+
+  #[test]
+  fn nested_headings_count_and_reset() {
+    let markdown = numbered("## A\n### B\n#### C\n#### D\n### E\n## F\n### G\n##### H\n#### I\n", None);
+    assert_eq!(toc_text(&markdown), ["1 A", "1.1 B", "1.1.1 C", "1.1.2 D", "1.2 E", "2 F", "2.1 G", "H", "2.1.1 I"]);
+  }
+
+  #[test]
+  fn h1_restarts_the_count_unnumbered() {
+    let markdown = numbered("## A\n### B\n# Part two\n## C\n### D\n", None);
+    assert_eq!(toc_text(&markdown), ["1 A", "1.1 B", "Part two", "1 C", "1.1 D"]);
+    assert!(markdown.html.contains("<h1 id=\"part-two\">Part two</h1>"), "{}", markdown.html);
+  }
+
+  #[test]
+  fn appendix_marker_switches_to_letters() {
+    let markdown = numbered("## Intro\n## Body\n### Detail\n## Appendices\n## Data\n### Tables\n## Proofs\n", Some("Appendices"));
+    assert_eq!(toc_text(&markdown), ["1 Intro", "2 Body", "2.1 Detail", "Appendices", "A Data", "A.1 Tables", "B Proofs"]);
+
+    let markdown = numbered("## Intro\n## Appendices\n## Data\n", None);
+    assert_eq!(toc_text(&markdown), ["1 Intro", "2 Appendices", "3 Data"]);
+  }
+
+  #[test]
+  fn numbers_are_shown_but_not_in_ids() {
+    let markdown = numbered("## Memory ordering\n### Fences\n", None);
+    let ids: Vec<&str> = markdown.toc.iter().map(|heading| heading.id.as_str()).collect();
+    assert_eq!(ids, ["memory-ordering", "fences"]);
+    assert_eq!(toc_text(&markdown), ["1 Memory ordering", "1.1 Fences"]);
+    assert!(
+      markdown.html.contains("<h2 id=\"memory-ordering\"><span class=\"heading-number\">1</span> Memory ordering</h2>"),
+      "{}",
+      markdown.html
+    );
+
+    let plain = render("## Memory ordering\n", &RenderOptions::default());
+    assert_eq!(toc_text(&plain), ["Memory ordering"]);
+    assert!(!plain.html.contains("heading-number"), "{}", plain.html);
+  }
+
+  #[test]
+  fn stripped_title_heading_is_not_counted() {
+    let source = "---\ndate: 2024-01-05\ntags: []\nnumber_headings: true\n---\n# Guide\n\n## Setup\n### Install\n## Usage\n";
+    for strip_title_heading in [false, true] {
+      let content = ContentConfig { strip_title_heading, ..ContentConfig::default() };
+      let (entry, _) = crate::entry::Entry::parse(source, &RenderOptions::default().content(content)).unwrap();
+      assert_eq!(entry.frontmatter.title, "Guide");
+      let toc: Vec<&str> = entry.toc.iter().map(|heading| heading.text.as_str()).collect();
+      let expected: &[&str] = if strip_title_heading { &["1 Setup", "1.1 Install", "2 Usage"] } else { &["Guide", "1 Setup", "1.1 Install", "2 Usage"] };
+      assert_eq!(toc, expected);
+      assert_eq!(entry.html.contains("<h1"), !strip_title_heading, "{}", entry.html);
+    }
+  }
+}
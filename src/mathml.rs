@@ -0,0 +1,704 @@
+//! TeX math to MathML, for pages that show math without loading MathJax.
+//! Covers the common subset: scripts, fractions, roots, Greek letters,
+//! operators and relations, accents, fonts, `\left`/`\right` and
+//! matrix-like environments. Anything else is an error, so callers can
+//! fall back.
+
+/// `tex` as a `<math>` element, shown as a block with `display`.
+pub fn to_mathml(tex: &str, display: bool) -> Result<String, String> {
+  let mut parser = Parser { tokens: tokenize(tex)?, pos: 0, display };
+  let nodes = parser.expression()?;
+  if let Some(token) = parser.peek() {
+    return Err(format!("unexpected {}", describe(token)));
+  }
+  let attribute = if display { " display=\"block\"" } else { "" };
+  Ok(format!("<math{}>{}</math>", attribute, row(nodes)))
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+  /// `\name`, or `\` and one other character such as `\{`.
+  Command(String),
+  Letter(char),
+  Number(String),
+  /// Argument of `\text` and its variants, with its spaces.
+  Text(String),
+  Other(char),
+  Open,
+  Close,
+  Sup,
+  Sub,
+  Align,
+  Prime,
+}
+
+fn describe(token: &Token) -> String {
+  match token {
+    Token::Command(name) => format!("\\{}", name),
+    Token::Letter(c) | Token::Other(c) => c.to_string(),
+    Token::Number(number) => number.clone(),
+    Token::Text(text) => format!("\\text{{{}}}", text),
+    Token::Open => "{".to_string(),
+    Token::Close => "}".to_string(),
+    Token::Sup => "^".to_string(),
+    Token::Sub => "_".to_string(),
+    Token::Align => "&".to_string(),
+    Token::Prime => "'".to_string(),
+  }
+}
+
+const TEXT_COMMANDS: [&str; 5] = ["text", "textrm", "textit", "textbf", "mbox"];
+
+fn tokenize(tex: &str) -> Result<Vec<Token>, String> {
+  let mut tokens = Vec::new();
+  let mut chars = tex.chars().peekable();
+  while let Some(c) = chars.next() {
+    let token = match c {
+      c if c.is_whitespace() => continue,
+      '\\' => {
+        let mut name = String::new();
+        while let Some(&next) = chars.peek().filter(|next| next.is_ascii_alphabetic()) {
+          name.push(next);
+          chars.next();
+        }
+        if name.is_empty() {
+          name.push(chars.next().ok_or("\\ at the end")?);
+        }
+        if !TEXT_COMMANDS.contains(&name.as_str()) {
+          tokens.push(Token::Command(name));
+          continue;
+        }
+        while chars.next_if(|next| next.is_whitespace()).is_some() {}
+        if chars.next() != Some('{') {
+          return Err(format!("\\{} needs a {{...}} argument", name));
+        }
+        let mut text = String::new();
+        let mut depth = 0;
+        loop {
+          let next = chars.next().ok_or(format!("unclosed \\{}", name))?;
+          match next {
+            '}' if depth == 0 => break,
+            '}' => depth -= 1,
+            '{' => depth += 1,
+            _ => {}
+          }
+          text.push(next);
+        }
+        Token::Text(text)
+      }
+      '{' => Token::Open,
+      '}' => Token::Close,
+      '^' => Token::Sup,
+      '_' => Token::Sub,
+      '&' => Token::Align,
+      '\'' => Token::Prime,
+      c if c.is_ascii_digit() => {
+        let mut number = c.to_string();
+        loop {
+          let mut ahead = chars.clone();
+          match ahead.next() {
+            Some(next) if next.is_ascii_digit() => number.push(next),
+            Some('.') if ahead.peek().is_some_and(char::is_ascii_digit) => number.push('.'),
+            _ => break,
+          }
+          chars.next();
+        }
+        Token::Number(number)
+      }
+      c if c.is_alphabetic() => Token::Letter(c),
+      c => Token::Other(c),
+    };
+    tokens.push(token);
+  }
+  Ok(tokens)
+}
+
+/// A parsed atom and whether scripts go above and below it in display
+/// math, as for `\sum`.
+struct Atom {
+  markup: String,
+  limits: bool,
+}
+
+impl Atom {
+  fn new(markup: String) -> Atom {
+    Atom { markup, limits: false }
+  }
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  pos: usize,
+  display: bool,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.pos)
+  }
+
+  fn next(&mut self) -> Option<Token> {
+    let token = self.tokens.get(self.pos).cloned();
+    self.pos += 1;
+    token
+  }
+
+  fn expect(&mut self, expected: Token) -> Result<(), String> {
+    match self.next() {
+      Some(token) if token == expected => Ok(()),
+      Some(token) => Err(format!("expected {}, found {}", describe(&expected), describe(&token))),
+      None => Err(format!("expected {} at the end", describe(&expected))),
+    }
+  }
+
+  /// Atoms with their scripts, up to the end, or to a `}`, `&`, `\\`,
+  /// `\right` or `\end` left for the caller.
+  fn expression(&mut self) -> Result<Vec<String>, String> {
+    let mut nodes = Vec::new();
+    while let Some(token) = self.peek() {
+      match token {
+        Token::Close | Token::Align => break,
+        Token::Command(name) if matches!(name.as_str(), "\\" | "cr" | "right" | "end") => break,
+        _ => {}
+      }
+      let atom = self.atom()?;
+      nodes.push(self.scripts(atom)?);
+    }
+    Ok(nodes)
+  }
+
+  /// `atom` with the sub- and superscripts and primes that follow it.
+  fn scripts(&mut self, atom: Atom) -> Result<String, String> {
+    let mut sub = None;
+    let mut sup = None;
+    let mut primes = String::new();
+    loop {
+      match self.peek() {
+        Some(Token::Sub) if sub.is_none() => {
+          self.pos += 1;
+          sub = Some(self.argument()?);
+        }
+        Some(Token::Sup) if sup.is_none() => {
+          self.pos += 1;
+          sup = Some(self.argument()?);
+        }
+        Some(Token::Prime) if sup.is_none() => {
+          self.pos += 1;
+          primes.push('′');
+        }
+        Some(Token::Sub) | Some(Token::Sup) => return Err("double script".to_string()),
+        _ => break,
+      }
+    }
+    if !primes.is_empty() {
+      let primes = format!("<mo>{}</mo>", primes);
+      sup = Some(match sup {
+        Some(sup) => format!("<mrow>{}{}</mrow>", primes, sup),
+        None => primes,
+      });
+    }
+    let base = atom.markup;
+    let (under, over, both) = if atom.limits && self.display { ("munder", "mover", "munderover") } else { ("msub", "msup", "msubsup") };
+    Ok(match (sub, sup) {
+      (None, None) => base,
+      (Some(sub), None) => format!("<{0}>{1}{2}</{0}>", under, base, sub),
+      (None, Some(sup)) => format!("<{0}>{1}{2}</{0}>", over, base, sup),
+      (Some(sub), Some(sup)) => format!("<{0}>{1}{2}{3}</{0}>", both, base, sub, sup),
+    })
+  }
+
+  /// The argument of a command or script: a group or a single token. Only
+  /// the first digit of a number is taken, as in `x^23`.
+  fn argument(&mut self) -> Result<String, String> {
+    if let Some(Token::Number(number)) = self.peek() {
+      if number.len() > 1 {
+        let (first, rest) = number.split_at(1);
+        let first = first.to_string();
+        self.tokens[self.pos] = Token::Number(rest.to_string());
+        return Ok(format!("<mn>{}</mn>", first));
+      }
+    }
+    match self.peek() {
+      Some(Token::Sub) | Some(Token::Sup) | Some(Token::Align) | Some(Token::Close) | None => Err("missing argument".to_string()),
+      _ => Ok(self.atom()?.markup),
+    }
+  }
+
+  fn atom(&mut self) -> Result<Atom, String> {
+    let token = self.next().ok_or("missing argument")?;
+    let markup = match token {
+      Token::Open => {
+        let nodes = self.expression()?;
+        self.expect(Token::Close)?;
+        row(nodes)
+      }
+      Token::Letter(c) => format!("<mi>{}</mi>", c),
+      Token::Number(number) => format!("<mn>{}</mn>", number),
+      Token::Text(text) => format!("<mtext>{}</mtext>", escape(&text)),
+      Token::Other('~') => "<mspace width=\"0.3333em\"></mspace>".to_string(),
+      Token::Other(c) => format!("<mo>{}</mo>", escape(&operator(c).to_string())),
+      Token::Prime => "<mo>′</mo>".to_string(),
+      // A script with nothing before it, as in `{}^{14}C`.
+      Token::Sub | Token::Sup => {
+        self.pos -= 1;
+        "<mrow></mrow>".to_string()
+      }
+      Token::Command(name) => return self.command(&name),
+      Token::Close | Token::Align => return Err(format!("unexpected {}", describe(&token))),
+    };
+    Ok(Atom::new(markup))
+  }
+
+  fn command(&mut self, name: &str) -> Result<Atom, String> {
+    if let Some((c, kind)) = symbol(name) {
+      return Ok(match kind {
+        Symbol::Identifier => Atom::new(format!("<mi>{}</mi>", c)),
+        Symbol::Upright => Atom::new(format!("<mi mathvariant=\"normal\">{}</mi>", c)),
+        Symbol::Operator => Atom::new(format!("<mo>{}</mo>", escape(&c.to_string()))),
+        Symbol::Large => Atom { markup: format!("<mo>{}</mo>", c), limits: true },
+      });
+    }
+    if FUNCTIONS.contains(&name) {
+      return Ok(Atom { markup: format!("<mi>{}</mi>", name), limits: LIMIT_FUNCTIONS.contains(&name) });
+    }
+    let markup = match name {
+      "frac" | "dfrac" | "tfrac" => {
+        let numerator = self.argument()?;
+        format!("<mfrac>{}{}</mfrac>", numerator, self.argument()?)
+      }
+      "binom" => {
+        let top = self.argument()?;
+        format!("<mrow><mo>(</mo><mfrac linethickness=\"0\">{}{}</mfrac><mo>)</mo></mrow>", top, self.argument()?)
+      }
+      "sqrt" if self.peek() == Some(&Token::Other('[')) => {
+        self.pos += 1;
+        let mut index = Vec::new();
+        while self.peek() != Some(&Token::Other(']')) {
+          if self.peek().is_none() {
+            return Err("unclosed [ of \\sqrt".to_string());
+          }
+          let atom = self.atom()?;
+          index.push(self.scripts(atom)?);
+        }
+        self.pos += 1;
+        format!("<mroot>{}{}</mroot>", self.argument()?, row(index))
+      }
+      "sqrt" => format!("<msqrt>{}</msqrt>", self.argument()?),
+      "left" => {
+        let open = self.delimiter()?;
+        let nodes = self.expression()?;
+        self.expect(Token::Command("right".to_string()))?;
+        let close = self.delimiter()?;
+        format!("<mrow>{}{}{}</mrow>", open, nodes.concat(), close)
+      }
+      "begin" => self.environment()?,
+      "hat" | "widehat" => accent(self.argument()?, "^"),
+      "bar" | "overline" => accent(self.argument()?, "‾"),
+      "vec" => accent(self.argument()?, "→"),
+      "tilde" | "widetilde" => accent(self.argument()?, "~"),
+      "dot" => accent(self.argument()?, "˙"),
+      "ddot" => accent(self.argument()?, "¨"),
+      "underline" => format!("<munder accentunder=\"true\">{}<mo>_</mo></munder>", self.argument()?),
+      "operatorname" => format!("<mi>{}</mi>", escape(&self.letters()?)),
+      "mathrm" => restyle(&self.argument()?, |c| c.is_ascii_alphabetic().then_some(c), true),
+      "mathit" => self.argument()?,
+      "mathbf" | "boldsymbol" => restyle(&self.argument()?, bold, false),
+      "mathbb" => restyle(&self.argument()?, double_struck, false),
+      "mathcal" => restyle(&self.argument()?, script, false),
+      "," | ":" | ">" => "<mspace width=\"0.1667em\"></mspace>".to_string(),
+      ";" => "<mspace width=\"0.2778em\"></mspace>".to_string(),
+      " " => "<mspace width=\"0.3333em\"></mspace>".to_string(),
+      "quad" => "<mspace width=\"1em\"></mspace>".to_string(),
+      "qquad" => "<mspace width=\"2em\"></mspace>".to_string(),
+      "!" | "displaystyle" | "textstyle" | "limits" | "nolimits" => String::new(),
+      _ => return Err(format!("unsupported command \\{}", name)),
+    };
+    Ok(Atom::new(markup))
+  }
+
+  /// The delimiter after `\left` or `\right`; `.` is none.
+  fn delimiter(&mut self) -> Result<String, String> {
+    let c = match self.next() {
+      Some(Token::Other('.')) => return Ok(String::new()),
+      Some(Token::Other(c)) => operator(c),
+      Some(Token::Command(name)) => match symbol(&name) {
+        Some((c, Symbol::Operator)) => c,
+        _ => return Err(format!("\\{} is not a delimiter", name)),
+      },
+      Some(token) => return Err(format!("{} is not a delimiter", describe(&token))),
+      None => return Err("missing delimiter".to_string()),
+    };
+    Ok(format!("<mo>{}</mo>", escape(&c.to_string())))
+  }
+
+  /// The letters of a `{...}` argument, for `\operatorname` and
+  /// environment names.
+  fn letters(&mut self) -> Result<String, String> {
+    self.expect(Token::Open)?;
+    let mut letters = String::new();
+    loop {
+      match self.next() {
+        Some(Token::Close) => return Ok(letters),
+        Some(Token::Letter(c)) | Some(Token::Other(c @ ('*' | '-'))) => letters.push(c),
+        Some(token) => return Err(format!("unexpected {} in a name", describe(&token))),
+        None => return Err("unclosed {".to_string()),
+      }
+    }
+  }
+
+  /// `\begin{name}` ... `\end{name}` of a matrix, `cases` or an alignment,
+  /// as a table with cells split at `&` and rows at `\\`.
+  fn environment(&mut self) -> Result<String, String> {
+    let name = self.letters()?;
+    let (open, close, align) = match name.as_str() {
+      "matrix" | "smallmatrix" => ("", "", None),
+      "pmatrix" => ("(", ")", None),
+      "bmatrix" => ("[", "]", None),
+      "Bmatrix" => ("{", "}", None),
+      "vmatrix" => ("|", "|", None),
+      "Vmatrix" => ("‖", "‖", None),
+      "cases" => ("{", "", Some(["left", "left"])),
+      "aligned" | "align" | "align*" | "split" => ("", "", Some(["right", "left"])),
+      "gathered" | "gather" | "gather*" => ("", "", None),
+      _ => return Err(format!("unsupported environment {}", name)),
+    };
+    let mut rows = Vec::new();
+    let mut cells = Vec::new();
+    loop {
+      cells.push(self.expression()?);
+      match self.next() {
+        Some(Token::Align) => {}
+        Some(Token::Command(command)) if command == "\\" || command == "cr" => rows.push(std::mem::take(&mut cells)),
+        Some(Token::Command(command)) if command == "end" => {
+          let end = self.letters()?;
+          if end != name {
+            return Err(format!("\\begin{{{}}} ended by \\end{{{}}}", name, end));
+          }
+          rows.push(cells);
+          break;
+        }
+        Some(token) => return Err(format!("unexpected {} in {}", describe(&token), name)),
+        None => return Err(format!("missing \\end{{{}}}", name)),
+      }
+    }
+    // A `\\` after the last row ends it rather than starting another.
+    if rows.len() > 1 && rows.last().is_some_and(|cells| cells.len() == 1 && cells[0].is_empty()) {
+      rows.pop();
+    }
+    let mut table = String::from("<mtable>");
+    for cells in rows {
+      table.push_str("<mtr>");
+      for (i, cell) in cells.into_iter().enumerate() {
+        match align {
+          Some(align) => table.push_str(&format!("<mtd style=\"text-align: {}\">{}</mtd>", align[i % 2], row(cell))),
+          None => table.push_str(&format!("<mtd>{}</mtd>", row(cell))),
+        }
+      }
+      table.push_str("</mtr>");
+    }
+    table.push_str("</mtable>");
+    let fence = |c: &str| if c.is_empty() { String::new() } else { format!("<mo>{}</mo>", c) };
+    Ok(format!("<mrow>{}{}{}</mrow>", fence(open), table, fence(close)))
+  }
+}
+
+fn row(nodes: Vec<String>) -> String {
+  if nodes.len() == 1 {
+    nodes.into_iter().next().unwrap()
+  }
+  else {
+    format!("<mrow>{}</mrow>", nodes.concat())
+  }
+}
+
+fn accent(base: String, mark: &str) -> String {
+  format!("<mover accent=\"true\">{}<mo>{}</mo></mover>", base, mark)
+}
+
+/// `markup` with each single-letter or digit `<mi>` and `<mn>` mapped
+/// through `map`; letters it does not map are made upright with
+/// `upright`, and left as they are otherwise.
+fn restyle(markup: &str, map: impl Fn(char) -> Option<char>, upright: bool) -> String {
+  let single = regex::Regex::new(r"<(mi|mn)>([A-Za-z0-9]+)</(?:mi|mn)>").unwrap();
+  single.replace_all(markup, |caps: &regex::Captures| {
+    let element = &caps[1];
+    if upright {
+      return format!("<{0} mathvariant=\"normal\">{1}</{0}>", element, &caps[2]);
+    }
+    let mapped: Option<String> = caps[2].chars().map(&map).collect();
+    match mapped {
+      Some(text) => format!("<{0}>{1}</{0}>", element, text),
+      None => caps[0].to_string(),
+    }
+  }).into_owned()
+}
+
+fn offset(c: char, base: u32) -> Option<char> {
+  char::from_u32(base + c as u32)
+}
+
+fn bold(c: char) -> Option<char> {
+  match c {
+    'A'..='Z' => offset(c, 0x1D400 - 'A' as u32),
+    'a'..='z' => offset(c, 0x1D41A - 'a' as u32),
+    '0'..='9' => offset(c, 0x1D7CE - '0' as u32),
+    _ => None,
+  }
+}
+
+fn double_struck(c: char) -> Option<char> {
+  match c {
+    'C' => Some('ℂ'),
+    'H' => Some('ℍ'),
+    'N' => Some('ℕ'),
+    'P' => Some('ℙ'),
+    'Q' => Some('ℚ'),
+    'R' => Some('ℝ'),
+    'Z' => Some('ℤ'),
+    'A'..='Z' => offset(c, 0x1D538 - 'A' as u32),
+    'a'..='z' => offset(c, 0x1D552 - 'a' as u32),
+    '0'..='9' => offset(c, 0x1D7D8 - '0' as u32),
+    _ => None,
+  }
+}
+
+fn script(c: char) -> Option<char> {
+  match c {
+    'B' => Some('ℬ'),
+    'E' => Some('ℰ'),
+    'F' => Some('ℱ'),
+    'H' => Some('ℋ'),
+    'I' => Some('ℐ'),
+    'L' => Some('ℒ'),
+    'M' => Some('ℳ'),
+    'R' => Some('ℛ'),
+    'A'..='Z' => offset(c, 0x1D49C - 'A' as u32),
+    _ => None,
+  }
+}
+
+/// The character shown for an operator typed as itself.
+fn operator(c: char) -> char {
+  match c {
+    '-' => '−',
+    '*' => '∗',
+    c => c,
+  }
+}
+
+fn escape(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+enum Symbol {
+  /// A variable, shown in italics.
+  Identifier,
+  /// A constant such as `\Gamma`, shown upright.
+  Upright,
+  Operator,
+  /// A large operator such as `\sum`, with limits above and below in
+  /// display math.
+  Large,
+}
+
+/// Functions shown upright by name, such as `\sin`.
+const FUNCTIONS: [&str; 32] = [
+  "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh", "coth",
+  "log", "ln", "lg", "exp", "lim", "liminf", "limsup", "max", "min", "sup", "inf", "det", "gcd", "arg", "deg", "dim", "ker", "hom", "Pr",
+];
+const LIMIT_FUNCTIONS: [&str; 9] = ["lim", "liminf", "limsup", "max", "min", "sup", "inf", "det", "gcd"];
+
+fn symbol(name: &str) -> Option<(char, Symbol)> {
+  use Symbol::*;
+  let symbol = match name {
+    "alpha" => ('α', Identifier),
+    "beta" => ('β', Identifier),
+    "gamma" => ('γ', Identifier),
+    "delta" => ('δ', Identifier),
+    "epsilon" => ('ϵ', Identifier),
+    "varepsilon" => ('ε', Identifier),
+    "zeta" => ('ζ', Identifier),
+    "eta" => ('η', Identifier),
+    "theta" => ('θ', Identifier),
+    "vartheta" => ('ϑ', Identifier),
+    "iota" => ('ι', Identifier),
+    "kappa" => ('κ', Identifier),
+    "lambda" => ('λ', Identifier),
+    "mu" => ('μ', Identifier),
+    "nu" => ('ν', Identifier),
+    "xi" => ('ξ', Identifier),
+    "pi" => ('π', Identifier),
+    "varpi" => ('ϖ', Identifier),
+    "rho" => ('ρ', Identifier),
+    "varrho" => ('ϱ', Identifier),
+    "sigma" => ('σ', Identifier),
+    "varsigma" => ('ς', Identifier),
+    "tau" => ('τ', Identifier),
+    "upsilon" => ('υ', Identifier),
+    "phi" => ('ϕ', Identifier),
+    "varphi" => ('φ', Identifier),
+    "chi" => ('χ', Identifier),
+    "psi" => ('ψ', Identifier),
+    "omega" => ('ω', Identifier),
+    "ell" => ('ℓ', Identifier),
+    "Gamma" => ('Γ', Upright),
+    "Delta" => ('Δ', Upright),
+    "Theta" => ('Θ', Upright),
+    "Lambda" => ('Λ', Upright),
+    "Xi" => ('Ξ', Upright),
+    "Pi" => ('Π', Upright),
+    "Sigma" => ('Σ', Upright),
+    "Upsilon" => ('Υ', Upright),
+    "Phi" => ('Φ', Upright),
+    "Psi" => ('Ψ', Upright),
+    "Omega" => ('Ω', Upright),
+    "infty" => ('∞', Upright),
+    "partial" => ('∂', Upright),
+    "nabla" => ('∇', Upright),
+    "emptyset" | "varnothing" => ('∅', Upright),
+    "hbar" => ('ℏ', Upright),
+    "Re" => ('ℜ', Upright),
+    "Im" => ('ℑ', Upright),
+    "aleph" => ('ℵ', Upright),
+    "sum" => ('∑', Large),
+    "prod" => ('∏', Large),
+    "coprod" => ('∐', Large),
+    "int" => ('∫', Large),
+    "iint" => ('∬', Large),
+    "iiint" => ('∭', Large),
+    "oint" => ('∮', Large),
+    "bigcup" => ('⋃', Large),
+    "bigcap" => ('⋂', Large),
+    "bigoplus" => ('⨁', Large),
+    "bigotimes" => ('⨂', Large),
+    "bigvee" => ('⋁', Large),
+    "bigwedge" => ('⋀', Large),
+    "+" => ('+', Operator),
+    "cdot" => ('⋅', Operator),
+    "times" => ('×', Operator),
+    "div" => ('÷', Operator),
+    "pm" => ('±', Operator),
+    "mp" => ('∓', Operator),
+    "ast" => ('∗', Operator),
+    "star" => ('⋆', Operator),
+    "circ" => ('∘', Operator),
+    "bullet" => ('∙', Operator),
+    "oplus" => ('⊕', Operator),
+    "otimes" => ('⊗', Operator),
+    "cup" => ('∪', Operator),
+    "cap" => ('∩', Operator),
+    "setminus" => ('∖', Operator),
+    "wedge" | "land" => ('∧', Operator),
+    "vee" | "lor" => ('∨', Operator),
+    "neg" | "lnot" => ('¬', Operator),
+    "le" | "leq" => ('≤', Operator),
+    "ge" | "geq" => ('≥', Operator),
+    "ne" | "neq" => ('≠', Operator),
+    "ll" => ('≪', Operator),
+    "gg" => ('≫', Operator),
+    "approx" => ('≈', Operator),
+    "equiv" => ('≡', Operator),
+    "sim" => ('∼', Operator),
+    "simeq" => ('≃', Operator),
+    "cong" => ('≅', Operator),
+    "propto" => ('∝', Operator),
+    "perp" => ('⊥', Operator),
+    "parallel" => ('∥', Operator),
+    "mid" => ('∣', Operator),
+    "in" => ('∈', Operator),
+    "notin" => ('∉', Operator),
+    "ni" => ('∋', Operator),
+    "subset" => ('⊂', Operator),
+    "subseteq" => ('⊆', Operator),
+    "supset" => ('⊃', Operator),
+    "supseteq" => ('⊇', Operator),
+    "forall" => ('∀', Operator),
+    "exists" => ('∃', Operator),
+    "to" | "rightarrow" => ('→', Operator),
+    "leftarrow" | "gets" => ('←', Operator),
+    "leftrightarrow" => ('↔', Operator),
+    "Rightarrow" | "implies" => ('⇒', Operator),
+    "Leftarrow" => ('⇐', Operator),
+    "Leftrightarrow" | "iff" => ('⇔', Operator),
+    "mapsto" => ('↦', Operator),
+    "ldots" | "dots" => ('…', Operator),
+    "cdots" => ('⋯', Operator),
+    "vdots" => ('⋮', Operator),
+    "ddots" => ('⋱', Operator),
+    "langle" => ('⟨', Operator),
+    "rangle" => ('⟩', Operator),
+    "lfloor" => ('⌊', Operator),
+    "rfloor" => ('⌋', Operator),
+    "lceil" => ('⌈', Operator),
+    "rceil" => ('⌉', Operator),
+    "{" | "lbrace" => ('{', Operator),
+    "}" | "rbrace" => ('}', Operator),
+    "|" | "Vert" => ('‖', Operator),
+    "vert" => ('|', Operator),
+    "%" => ('%', Operator),
+    "#" => ('#', Operator),
+    "$" => ('$', Operator),
+    "&" => ('&', Operator),
+    "_" => ('_', Operator),
+    "*" => ('∗', Operator),
+    _ => return None,
+  };
+  Some(symbol)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn inline(tex: &str) -> String {
+    to_mathml(tex, false).unwrap()
+  }
+
+  #[test]
+  fn scripts_fractions_and_roots() {
+    assert_eq!(inline("x_1^2"), "<math><msubsup><mi>x</mi><mn>1</mn><mn>2</mn></msubsup></math>");
+    assert_eq!(inline("x^23"), "<math><mrow><msup><mi>x</mi><mn>2</mn></msup><mn>3</mn></mrow></math>");
+    assert_eq!(inline("f'(x)"), "<math><mrow><msup><mi>f</mi><mo>′</mo></msup><mo>(</mo><mi>x</mi><mo>)</mo></mrow></math>");
+    assert_eq!(inline(r"\frac{a}{b+1}"), "<math><mfrac><mi>a</mi><mrow><mi>b</mi><mo>+</mo><mn>1</mn></mrow></mfrac></math>");
+    assert_eq!(inline(r"\sqrt[3]{x}"), "<math><mroot><mi>x</mi><mn>3</mn></mroot></math>");
+    assert_eq!(inline(r"a < b"), "<math><mrow><mi>a</mi><mo>&lt;</mo><mi>b</mi></mrow></math>");
+  }
+
+  #[test]
+  fn large_operators_take_limits_in_display_math() {
+    assert_eq!(inline(r"\sum_{i=0}^n i"), "<math><mrow><msubsup><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>0</mn></mrow><mi>n</mi></msubsup><mi>i</mi></mrow></math>");
+    assert_eq!(
+      to_mathml(r"\lim_{x \to 0} \sin x", true).unwrap(),
+      "<math display=\"block\"><mrow><munder><mi>lim</mi><mrow><mi>x</mi><mo>→</mo><mn>0</mn></mrow></munder><mi>sin</mi><mi>x</mi></mrow></math>"
+    );
+  }
+
+  #[test]
+  fn symbols_fonts_and_text() {
+    assert_eq!(inline(r"\alpha \Gamma \le \infty"), "<math><mrow><mi>α</mi><mi mathvariant=\"normal\">Γ</mi><mo>≤</mo><mi mathvariant=\"normal\">∞</mi></mrow></math>");
+    assert_eq!(inline(r"\mathbb{R}^n"), "<math><msup><mi>ℝ</mi><mi>n</mi></msup></math>");
+    assert_eq!(inline(r"\mathbf{v}"), "<math><mi>𝐯</mi></math>");
+    assert_eq!(inline(r"\operatorname{argmax} \mathrm{d}x"), "<math><mrow><mi>argmax</mi><mi mathvariant=\"normal\">d</mi><mi>x</mi></mrow></math>");
+    assert_eq!(inline(r"x \text{ if } y"), "<math><mrow><mi>x</mi><mtext> if </mtext><mi>y</mi></mrow></math>");
+  }
+
+  #[test]
+  fn delimiters_and_environments() {
+    assert_eq!(inline(r"\left( x \right."), "<math><mrow><mo>(</mo><mi>x</mi></mrow></math>");
+    assert_eq!(
+      inline(r"\begin{pmatrix} 1 & 0 \\ 0 & 1 \\ \end{pmatrix}"),
+      "<math><mrow><mo>(</mo><mtable><mtr><mtd><mn>1</mn></mtd><mtd><mn>0</mn></mtd></mtr><mtr><mtd><mn>0</mn></mtd><mtd><mn>1</mn></mtd></mtr></mtable><mo>)</mo></mrow></math>"
+    );
+    assert!(inline(r"f(x) = \begin{cases} 1 & x > 0 \\ 0 & \text{otherwise} \end{cases}").contains("<mtd style=\"text-align: left\"><mtext>otherwise</mtext></mtd>"));
+  }
+
+  #[test]
+  fn unsupported_tex_is_an_error() {
+    assert_eq!(to_mathml(r"\overbrace{x}", false).err().unwrap(), "unsupported command \\overbrace");
+    assert_eq!(to_mathml(r"\begin{tikzpicture}\end{tikzpicture}", false).err().unwrap(), "unsupported environment tikzpicture");
+    assert_eq!(to_mathml(r"a \\ b", false).err().unwrap(), "unexpected \\\\");
+    assert_eq!(to_mathml(r"\frac{a}", false).err().unwrap(), "missing argument");
+    assert_eq!(to_mathml(r"{x", false).err().unwrap(), "expected } at the end");
+    assert_eq!(to_mathml(r"x^1^2", false).err().unwrap(), "double script");
+  }
+}
@@ -0,0 +1,63 @@
+//! Third-party origins the generated pages load scripts, styles and frames
+//! from, for security review and Content-Security-Policy lists.
+
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Origins by the CSP directive that governs them (`script-src`,
+/// `style-src`, `frame-src`).
+pub type Origins = BTreeMap<String, BTreeSet<String>>;
+
+/// Adds the external origins referenced by `text`, generated markup or
+/// script. Quotes may be backslash-escaped, as in HTML embedded in a
+/// JavaScript string.
+pub fn collect(text: &str, origins: &mut Origins) {
+  let patterns = [
+    ("script-src", r#"<script\b[^>]*?\ssrc=\\?["']([^"'\\]+)"#),
+    ("script-src", r#"\.src\s*=\s*\\?["']([^"'\\]+)"#),
+    ("frame-src", r#"<iframe\b[^>]*?\ssrc=\\?["']([^"'\\]+)"#),
+    ("style-src", r#"<link\b[^>]*?\shref=\\?["']([^"'\\]+\.css[^"'\\]*)"#),
+    ("style-src", r#"@import\s+(?:url\()?\\?["']([^"'\\]+)"#),
+  ];
+  let patterns = patterns.map(|(directive, pattern)| (directive, Regex::new(pattern).unwrap()));
+  for (directive, pattern) in &patterns {
+    for caps in pattern.captures_iter(text) {
+      if let Some(origin) = origin(&caps[1]) {
+        origins.entry(directive.to_string()).or_default().insert(origin);
+      }
+    }
+  }
+}
+
+/// `scheme://host[:port]` of an absolute or protocol-relative URL; `None`
+/// for URLs on the site itself.
+fn origin(url: &str) -> Option<String> {
+  let (scheme, rest) = match url.split_once("://") {
+    Some((scheme, rest)) if scheme == "http" || scheme == "https" => (scheme, rest),
+    Some(_) => return None,
+    None => ("https", url.strip_prefix("//")?),
+  };
+  let host = rest.split(['/', '?', '#']).next().filter(|host| !host.is_empty())?;
+  Some(format!("{}://{}", scheme, host))
+}
+
+/// Merges the origins of one page into the site-wide set.
+pub fn merge(all: &mut Origins, page: &Origins) {
+  for (directive, origins) in page {
+    all.entry(directive.clone()).or_default().extend(origins.iter().cloned());
+  }
+}
+
+/// A suggested Content-Security-Policy allowing the pages' own origin and
+/// the collected ones.
+pub fn csp(origins: &Origins) -> String {
+  ["script-src", "style-src", "frame-src"]
+    .iter()
+    .map(|directive| {
+      let mut sources = vec!["'self'".to_string()];
+      sources.extend(origins.get(*directive).into_iter().flatten().cloned());
+      format!("{} {}", directive, sources.join(" "))
+    })
+    .collect::<Vec<_>>()
+    .join("; ")
+}
@@ -0,0 +1,516 @@
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::config::UrlStyle;
+use crate::origins::Origins;
+
+/// First line of every generated Svelte component.
+pub const SVELTE_HEADER: &str = "<!-- Generated by md_to_svelte. Do not edit. -->";
+/// First line of every generated TypeScript data file.
+pub const TS_HEADER: &str = "// Generated by md_to_svelte. Do not edit.";
+
+/// The header of a generated Svelte component or HTML file, followed by the
+/// `source_comment` line naming `source`, unless either is empty.
+pub fn svelte_header(comment: &str, source: &str) -> String {
+  if comment.is_empty() || source.is_empty() {
+    return SVELTE_HEADER.to_string();
+  }
+  format!("{}\n<!-- {} -->", SVELTE_HEADER, comment.replace("{source}", source))
+}
+
+/// The header of a generated TypeScript file, followed by the
+/// `source_comment` line naming `source`, unless either is empty.
+pub fn ts_header(comment: &str, source: &str) -> String {
+  if comment.is_empty() || source.is_empty() {
+    return TS_HEADER.to_string();
+  }
+  format!("{}\n// {}", TS_HEADER, comment.replace("{source}", source))
+}
+
+/// First line of every generated stylesheet.
+pub const CSS_HEADER: &str = "/* Generated by md_to_svelte. Do not edit. */";
+
+/// First line of every generated XML file, which therefore has no XML
+/// declaration; UTF-8 needs none.
+pub const XML_HEADER: &str = "<!-- Generated by md_to_svelte. Do not edit. -->";
+
+/// A single-quoted JavaScript string literal holding `value`. Line breaks
+/// and backslashes are escaped so multiline values stay on one line, and
+/// `</` so the literal can't close a `<script>` block.
+pub(crate) fn js_string(value: &str) -> String {
+  let mut literal = String::from("'");
+  for c in value.chars() {
+    match c {
+      '\\' => literal.push_str("\\\\"),
+      '\'' => literal.push_str("\\'"),
+      '\n' => literal.push_str("\\n"),
+      '\r' => literal.push_str("\\r"),
+      '\u{2028}' => literal.push_str("\\u2028"),
+      '\u{2029}' => literal.push_str("\\u2029"),
+      _ => literal.push(c),
+    }
+  }
+  literal.push('\'');
+  literal.replace("</", "<\\/")
+}
+
+/// `value` with the characters XML reserves replaced by entities.
+pub(crate) fn escape_xml(value: &str) -> String {
+  value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+/// Record of what the last build produced, for auditing.
+pub const MANIFEST_PATH: &str = ".md_to_svelte/manifest.json";
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Name of the component written into every slug directory.
+pub const PAGE_FILE: &str = "+page.svelte";
+
+/// Directory inside a slug directory holding the reader version of the page.
+pub const READER_DIR: &str = "reader";
+
+/// Name of the page options module written with `route_options`.
+pub const ROUTE_OPTIONS_FILE: &str = "+page.ts";
+
+/// URL of a page in the given style. Every emitted internal URL goes
+/// through this.
+pub fn page_url(route: &str, slug: &str, style: UrlStyle) -> String {
+  let url = format!("{}/{}", route.trim_end_matches('/'), slug);
+  match style {
+    UrlStyle::NoSlash => url,
+    UrlStyle::Slash => format!("{}/", url),
+  }
+}
+
+/// Whether `path` holds a file this tool wrote and may therefore replace or
+/// remove. Anything else in the output tree belongs to the user.
+pub fn is_generated(path: &Path) -> bool {
+  let Ok(content) = std::fs::read_to_string(path) else {
+    return false;
+  };
+  content.starts_with(SVELTE_HEADER)
+    || content.starts_with(TS_HEADER)
+    || content.starts_with(CSS_HEADER)
+    || content.starts_with(XML_HEADER)
+}
+
+/// Fails if `path` exists but was not generated by this tool.
+pub fn check_owned(path: &Path) -> std::io::Result<()> {
+  if path.exists() && !is_generated(path) {
+    return Err(Error::new(
+      ErrorKind::AlreadyExists,
+      format!("refusing to overwrite {}, it was not generated by md_to_svelte (delete it if a version older than the generated-file header wrote it)", path.display()),
+    ));
+  }
+  Ok(())
+}
+
+/// Writes a generated file, creating its directory and refusing to replace
+/// files the user owns. The content goes to a temporary file that is then
+/// renamed over the target, so a failed write never leaves half a file.
+pub fn write_generated(path: &Path, content: &str) -> std::io::Result<()> {
+  check_owned(path)?;
+  // Unchanged files are left alone so dev servers only reload what changed.
+  if std::fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+    return Ok(());
+  }
+  std::fs::create_dir_all(path.parent().unwrap())?;
+  let temp = path.with_file_name(format!(".{}.tmp", path.file_name().unwrap().to_string_lossy()));
+  std::fs::write(&temp, content)?;
+  std::fs::rename(&temp, path)
+}
+
+/// Warns about slug directories that already hold hand-written files, which
+/// the build would mix generated output into.
+pub fn check_slug_collisions<'a>(output_dir: &Path, slugs: impl Iterator<Item = &'a str>) {
+  for slug in slugs {
+    let dir = output_dir.join(slug);
+    let Ok(read_dir) = std::fs::read_dir(&dir) else {
+      continue;
+    };
+    let user_files: Vec<String> = read_dir
+      .filter_map(|entry| entry.ok())
+      .filter(|entry| !is_generated(&entry.path()) && !is_generated(&entry.path().join(PAGE_FILE)))
+      .map(|entry| entry.file_name().to_string_lossy().into_owned())
+      .collect();
+    if !user_files.is_empty() {
+      warn!(
+        "{} collides with an existing hand-written route ({}); generated files will be written next to it",
+        dir.display(),
+        user_files.join(", ")
+      );
+    }
+  }
+}
+
+/// Warns about slug directories that exist on disk with different casing,
+/// which a case-insensitive filesystem would treat as the same directory.
+pub fn check_slug_case<'a>(output_dir: &Path, slugs: impl Iterator<Item = &'a str>) {
+  let Ok(read_dir) = std::fs::read_dir(output_dir) else {
+    return;
+  };
+  let existing: Vec<String> = read_dir
+    .filter_map(|entry| entry.ok())
+    .filter_map(|entry| entry.file_name().into_string().ok())
+    .collect();
+  for slug in slugs {
+    for name in &existing {
+      if name != slug && name.eq_ignore_ascii_case(slug) {
+        warn!("{} already exists as {} with different casing", output_dir.join(slug).display(), output_dir.join(name).display());
+      }
+    }
+  }
+}
+
+/// Warns about SvelteKit routes beside the generated ones that claim the same
+/// URLs: dynamic segments (`[slug]`, `[...rest]`), which SvelteKit ranks
+/// below the generated static routes, and slug directories inside layout
+/// groups (`(group)/slug`), which SvelteKit rejects as conflicting. Slugs
+/// that would themselves be read as a dynamic segment or group are reported
+/// too.
+pub fn check_route_conflicts(output_dir: &Path, slugs: &[&str]) {
+  if slugs.is_empty() {
+    return;
+  }
+  for slug in slugs {
+    if slug.contains(['[', ']']) {
+      warn!("{}: slug '{}' would be read by SvelteKit as a dynamic segment; rename the source or set `slug:`", output_dir.display(), slug);
+    }
+    else if is_group(slug) {
+      warn!("{}: slug '{}' would be read by SvelteKit as a layout group; rename the source or set `slug:`", output_dir.display(), slug);
+    }
+  }
+
+  let mut dirs = Vec::new();
+  route_dirs(output_dir, false, &mut dirs);
+  dirs.sort();
+  for (dir, in_group) in dirs {
+    let name = dir.file_name().unwrap().to_string_lossy();
+    if name.contains('[') && !slugs.contains(&name.as_ref()) {
+      warn!(
+        "{} is a dynamic route overlapping the generated pages in {}; SvelteKit serves the generated pages for their URLs and the dynamic route only for other slugs",
+        dir.display(),
+        output_dir.display()
+      );
+    }
+    else if in_group && slugs.contains(&name.as_ref()) {
+      warn!(
+        "{} conflicts with the generated {}: both serve the same URL and SvelteKit refuses to build",
+        dir.display(),
+        output_dir.join(name.as_ref()).display()
+      );
+    }
+  }
+}
+
+fn is_group(name: &str) -> bool {
+  name.starts_with('(') && name.ends_with(')')
+}
+
+/// Route directories at the URL level of `dir`, looking through layout
+/// groups, which add no URL segment.
+fn route_dirs(dir: &Path, in_group: bool, dirs: &mut Vec<(PathBuf, bool)>) {
+  let Ok(read_dir) = std::fs::read_dir(dir) else {
+    return;
+  };
+  for entry in read_dir.filter_map(|entry| entry.ok()) {
+    let path = entry.path();
+    if !path.is_dir() {
+      continue;
+    }
+    if is_group(&entry.file_name().to_string_lossy()) {
+      route_dirs(&path, true, dirs);
+    }
+    else {
+      dirs.push((path, in_group));
+    }
+  }
+}
+
+/// Generated files anywhere below `output_dir`: at its root, in the page
+/// directories a permalink may nest, and in their reader directories.
+pub fn generated_files(output_dir: &Path) -> Vec<PathBuf> {
+  let mut files = Vec::new();
+  collect_generated(output_dir, &mut files);
+  files.sort();
+  files
+}
+
+fn collect_generated(dir: &Path, files: &mut Vec<PathBuf>) {
+  let Ok(read_dir) = std::fs::read_dir(dir) else {
+    return;
+  };
+  for entry in read_dir.filter_map(|entry| entry.ok()) {
+    let path = entry.path();
+    if path.is_dir() {
+      collect_generated(&path, files);
+    }
+    else if is_generated(&path) {
+      files.push(path);
+    }
+  }
+}
+
+/// Runs `command` once with every file in `files` appended as arguments,
+/// for formatters such as `npx prettier --write`. The command is split on
+/// whitespace; it is not run through a shell.
+pub fn run_format_command(command: &str, files: &[PathBuf]) -> Result<(), String> {
+  let mut words = command.split_whitespace();
+  let program = words.next().ok_or("format command is empty")?;
+  let status = std::process::Command::new(program)
+    .args(words)
+    .args(files)
+    .status()
+    .map_err(|e| format!("Error running format command '{}': {}", command, e))?;
+  if !status.success() {
+    return Err(format!("Error running format command '{}': {}", command, status));
+  }
+  Ok(())
+}
+
+/// Removes generated page directories under `output_dir` whose permalink is
+/// no longer produced. Only generated files are deleted; a directory that
+/// still holds anything else is kept. Directories between the output
+/// directory and the pages, such as `2024/`, are removed once pruning
+/// empties them.
+pub fn prune(output_dir: &Path, permalinks: &BTreeSet<String>) -> std::io::Result<()> {
+  prune_dir(output_dir, Path::new(""), permalinks).map(|_| ())
+}
+
+/// Prunes the directory `relative` to `output_dir` and returns whether it
+/// removed a page directory.
+fn prune_dir(output_dir: &Path, relative: &Path, permalinks: &BTreeSet<String>) -> std::io::Result<bool> {
+  let Ok(read_dir) = std::fs::read_dir(output_dir.join(relative)) else {
+    return Ok(false);
+  };
+  let mut pruned = false;
+  for entry in read_dir {
+    let dir = entry?.path();
+    let Some(name) = dir.file_name().and_then(|name| name.to_str()) else {
+      continue;
+    };
+    let permalink = relative.join(name).to_string_lossy().replace('\\', "/");
+    if !dir.is_dir() || permalinks.contains(&permalink) {
+      continue;
+    }
+    let page = is_generated(&dir.join(PAGE_FILE));
+    let reader = dir.join(READER_DIR);
+    if page && is_generated(&reader.join(PAGE_FILE)) {
+      std::fs::remove_file(reader.join(PAGE_FILE))?;
+      let _ = std::fs::remove_dir(&reader);
+    }
+    // Pages a permalink nests below this directory.
+    let pruned_below = prune_dir(output_dir, &relative.join(name), permalinks)?;
+    if !page {
+      // A directory such as `2024/`, or a hand-written route, which is only
+      // removed if pruning emptied it.
+      if pruned_below && std::fs::read_dir(&dir)?.next().is_none() {
+        std::fs::remove_dir(&dir)?;
+        pruned = true;
+      }
+      continue;
+    }
+    for file in std::fs::read_dir(&dir)? {
+      let file = file?.path();
+      if file.is_file() && is_generated(&file) {
+        std::fs::remove_file(&file)?;
+      }
+    }
+    if std::fs::read_dir(&dir)?.next().is_none() {
+      std::fs::remove_dir(&dir)?;
+      info!("Pruned {}", dir.display());
+      pruned = true;
+    }
+    else {
+      warn!("Kept {} after pruning, it contains files not generated by md_to_svelte", dir.display());
+    }
+  }
+  Ok(pruned)
+}
+
+/// Checks, before a build writes anything, that every directory it writes
+/// to can be created and written to, listing each one that can't. Also
+/// warns when a filesystem clearly lacks room for the previous build's
+/// output, where `df` is available to tell.
+pub fn preflight(dirs: &[(String, PathBuf)]) -> Result<(), String> {
+  let mut problems = Vec::new();
+  for (name, dir) in dirs {
+    if let Err(e) = probe(dir) {
+      problems.push(format!("{}: {}: {}{}", name, dir.display(), e, permission_context(dir)));
+    }
+  }
+  if !problems.is_empty() {
+    return Err(format!("Error: cannot write to every output directory, nothing was built:\n  {}", problems.join("\n  ")));
+  }
+
+  for (_, dir) in dirs {
+    let needed: u64 = generated_files(dir).iter()
+      .filter_map(|file| std::fs::metadata(file).ok())
+      .map(|metadata| metadata.len())
+      .sum();
+    if let Some(available) = available_space(dir).filter(|available| *available < needed) {
+      warn!(
+        "{} has {} KiB free, but the previous build wrote {} KiB there",
+        dir.display(),
+        available / 1024,
+        needed / 1024
+      );
+    }
+  }
+  Ok(())
+}
+
+/// Creates `dir` and a file in it, then removes both again, leaving the
+/// tree as it was.
+fn probe(dir: &Path) -> std::io::Result<()> {
+  let created: Vec<&Path> = dir.ancestors().take_while(|path| !path.as_os_str().is_empty() && !path.exists()).collect();
+  std::fs::create_dir_all(dir)?;
+  let path = dir.join(".md_to_svelte-probe");
+  let result = std::fs::write(&path, "").and_then(|_| std::fs::remove_file(&path));
+  for dir in created {
+    let _ = std::fs::remove_dir(dir);
+  }
+  result
+}
+
+/// Owner and mode of the closest existing path on the way to `dir`, to
+/// explain a permission error.
+#[cfg(unix)]
+fn permission_context(dir: &Path) -> String {
+  use std::os::unix::fs::MetadataExt;
+  let Some((existing, metadata)) = dir.ancestors()
+    .map(|path| if path.as_os_str().is_empty() { Path::new(".") } else { path })
+    .find_map(|path| Some((path, std::fs::metadata(path).ok()?)))
+  else {
+    return String::new();
+  };
+  format!(" ({} is owned by uid {} with mode {:o})", existing.display(), metadata.uid(), metadata.mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn permission_context(_dir: &Path) -> String {
+  String::new()
+}
+
+/// Free bytes on the filesystem holding `dir`, from `df`, or `None` when
+/// that can't be told.
+fn available_space(dir: &Path) -> Option<u64> {
+  let output = std::process::Command::new("df").arg("-Pk").arg(dir).output().ok()?;
+  let stdout = String::from_utf8(output.stdout).ok()?;
+  let kilobytes: u64 = stdout.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+  Some(kilobytes * 1024)
+}
+
+/// What the manifest of an earlier build recorded about one page.
+#[derive(Deserialize)]
+pub struct ManifestEntry {
+  #[serde(default, rename = "type")]
+  pub content_type: String,
+  #[serde(default)]
+  pub slug: String,
+  #[serde(default)]
+  pub permalink: String,
+  #[serde(default)]
+  pub source: PathBuf,
+  pub url: String,
+  #[serde(default)]
+  pub related: Vec<String>,
+  #[serde(default)]
+  pub origins: Origins,
+}
+
+/// Reads the pages recorded by the previous build.
+pub fn read_manifest() -> Result<Vec<ManifestEntry>, String> {
+  #[derive(Deserialize)]
+  struct Manifest {
+    version: u32,
+    entries: Vec<ManifestEntry>,
+  }
+  let text = std::fs::read_to_string(MANIFEST_PATH)
+    .map_err(|e| format!("Error reading {}: {}", MANIFEST_PATH, e))?;
+  let manifest: Manifest = serde_json::from_str(&text)
+    .map_err(|e| format!("Error reading {}: {}", MANIFEST_PATH, e))?;
+  if manifest.version != MANIFEST_VERSION {
+    return Err(format!("Error reading {}: written by another version", MANIFEST_PATH));
+  }
+  Ok(manifest.entries)
+}
+
+/// Writes the build manifest. The directory belongs to this tool, so no
+/// ownership check is needed. `build` describes how the pages were built,
+/// `files` lists the site-wide files such as sitemap and feed chunks, and
+/// `locale` is the configured `content_locale`.
+pub fn write_manifest(entries: Vec<serde_json::Value>, locale: Option<&str>, origins: &Origins, build: serde_json::Value, files: &[PathBuf]) -> std::io::Result<()> {
+  let manifest = serde_json::json!({
+    "version": MANIFEST_VERSION,
+    "build": build,
+    "locale": locale,
+    "files": files,
+    "entries": entries,
+    "origins": origins,
+  });
+  let path = Path::new(MANIFEST_PATH);
+  std::fs::create_dir_all(path.parent().unwrap())?;
+  std::fs::write(path, serde_json::to_string_pretty(&manifest).unwrap())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+  use regex::Regex;
+
+  /// Panics unless `xml` is well formed: one root element, every element
+  /// closed in order, and no stray `<` or `&` in text or attribute values.
+  pub(crate) fn assert_well_formed(xml: &str) {
+    let markup = Regex::new(r#"<!--.*?-->|<\?.*?\?>|<(/?)([A-Za-z][\w:.-]*)((?:\s+[\w:.-]+="[^"<]*")*)\s*(/?)>"#).unwrap();
+    let entity = Regex::new(r"&(?:amp|lt|gt|quot|apos|#\d+|#x[0-9a-fA-F]+);").unwrap();
+    let attribute = Regex::new(r#"[\w:.-]+="|""#).unwrap();
+    let check_text = |text: &str, outside: bool| {
+      assert!(!text.contains('<') && !text.contains('>'), "stray markup {:?}", text);
+      assert!(!entity.replace_all(text, "").contains('&'), "stray & in {:?}", text);
+      assert!(!outside || text.trim().is_empty(), "text outside the root element: {:?}", text);
+    };
+    let mut open: Vec<&str> = Vec::new();
+    let mut roots = 0;
+    let mut end = 0;
+    for caps in markup.captures_iter(xml) {
+      let whole = caps.get(0).unwrap();
+      check_text(&xml[end..whole.start()], open.is_empty());
+      end = whole.end();
+      let Some(name) = caps.get(2) else {
+        continue;
+      };
+      check_text(&attribute.replace_all(&caps[3], ""), false);
+      if &caps[1] == "/" {
+        assert_eq!(open.pop(), Some(name.as_str()), "unexpected </{}>", name.as_str());
+      }
+      else {
+        if open.is_empty() {
+          roots += 1;
+        }
+        if caps[4].is_empty() {
+          open.push(name.as_str());
+        }
+      }
+    }
+    check_text(&xml[end..], true);
+    assert!(open.is_empty(), "unclosed {:?}", open);
+    assert_eq!(roots, 1, "{} root elements", roots);
+  }
+
+  #[test]
+  fn js_string_escapes_what_ends_a_literal() {
+    assert_eq!(js_string("plain"), "'plain'");
+    assert_eq!(js_string("It's a \\ path"), r"'It\'s a \\ path'");
+    assert_eq!(js_string("one\r\ntwo\u{2028}three"), r"'one\r\ntwo\u2028three'");
+    assert_eq!(js_string("</script>"), r"'<\/script>'");
+  }
+
+  #[test]
+  #[should_panic(expected = "unexpected </b>")]
+  fn well_formed_check_rejects_misnested_elements() {
+    assert_well_formed("<a><b><c></b></c></a>");
+  }
+}
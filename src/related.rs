@@ -0,0 +1,73 @@
+//! Links between entries of the same content type. This needs every entry
+//! parsed first, so it runs as a separate pass after loading.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::entry::Entry;
+
+#[derive(Serialize, Clone)]
+pub struct Related {
+  pub slug: String,
+  pub title: String,
+  /// URL of the related page, for links the page emits itself.
+  #[serde(skip)]
+  pub url: String,
+}
+
+/// Fills `related` on every entry: first the slugs listed in its frontmatter,
+/// in the order written, then other entries sharing the most tags (newest
+/// first, then by slug) until `limit` is reached. `held_back` are the slugs
+/// of drafts and unpublished entries left out of this build, which may not
+/// be linked to, with the reason.
+pub fn resolve_related(entries: &mut [Entry], held_back: &BTreeMap<String, &str>, limit: usize) {
+  let mut resolved = Vec::with_capacity(entries.len());
+  for entry in entries.iter() {
+    let slug = &entry.frontmatter.slug;
+    let path = entry.path.display();
+    let mut related: Vec<&Entry> = Vec::new();
+
+    for target in &entry.frontmatter.related {
+      if target == slug {
+        warn!("{}: related entry '{}' is the entry itself, ignored", path, target);
+        continue;
+      }
+      if related.iter().any(|other| &other.frontmatter.slug == target) {
+        warn!("{}: related entry '{}' is listed more than once", path, target);
+        continue;
+      }
+      match entries.iter().find(|other| &other.frontmatter.slug == target) {
+        Some(other) => related.push(other),
+        None if held_back.contains_key(target) => error!("Error in {}: related entry '{}' is {}", path, target, held_back[target]),
+        None => error!("Error in {}: related entry '{}' does not exist", path, target),
+      }
+    }
+
+    let tags: BTreeSet<&String> = entry.frontmatter.tags.iter().collect();
+    let mut candidates: Vec<(usize, &Entry)> = entries.iter()
+      .filter(|other| &other.frontmatter.slug != slug)
+      .filter(|other| !related.iter().any(|chosen| chosen.frontmatter.slug == other.frontmatter.slug))
+      .map(|other| (other.frontmatter.tags.iter().filter(|tag| tags.contains(tag)).count(), other))
+      .filter(|(shared, _)| *shared > 0)
+      .collect();
+    candidates.sort_by(|(a_shared, a), (b_shared, b)| {
+      b_shared.cmp(a_shared)
+        .then(b.date.cmp(&a.date))
+        .then(a.frontmatter.slug.cmp(&b.frontmatter.slug))
+    });
+    let free = limit.saturating_sub(related.len());
+    related.extend(candidates.into_iter().take(free).map(|(_, other)| other));
+
+    resolved.push(related.into_iter()
+      .map(|other| Related {
+        slug: other.frontmatter.slug.clone(),
+        title: other.frontmatter.title.clone(),
+        url: other.url.clone(),
+      })
+      .collect::<Vec<_>>());
+  }
+
+  for (entry, related) in entries.iter_mut().zip(resolved) {
+    entry.related = related;
+  }
+}
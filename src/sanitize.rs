@@ -0,0 +1,132 @@
+//! Strips raw HTML in rendered markdown down to an allowlist.
+
+use ammonia::Builder;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+use crate::config::ContentConfig;
+
+/// Raw HTML tags allowed when `allowed_tags` is not set: inline semantics
+/// with no way to run script or pull in other documents.
+pub const SAFE_TAGS: [&str; 21] = [
+  "abbr", "b", "br", "cite", "del", "details", "dfn", "i", "ins", "kbd", "mark", "q", "s", "samp", "small", "sub",
+  "summary", "sup", "time", "u", "var",
+];
+
+/// Tags the markdown renderer itself emits, always allowed.
+const MARKDOWN_TAGS: [&str; 31] = [
+  "a", "blockquote", "br", "code", "del", "div", "em", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "img", "input", "li",
+  "ol", "p", "pre", "section", "span", "strong", "sup", "table", "tbody", "td", "th", "thead", "tr", "ul",
+];
+
+/// Tags that are dropped along with their content and can never be allowed.
+pub const FORBIDDEN_TAGS: [&str; 2] = ["script", "style"];
+
+/// Attributes the markdown renderer itself emits, always allowed.
+const MARKDOWN_ATTRIBUTES: [(&str, &[&str]); 8] = [
+  ("a", &["href", "title"]),
+  ("img", &["src", "alt", "title"]),
+  ("input", &["type", "checked", "disabled"]),
+  ("div", &["style"]),
+  ("ol", &["start"]),
+  ("th", &["style"]),
+  ("td", &["style"]),
+  ("span", &["title"]),
+];
+
+/// Attributes allowed on raw HTML when `allowed_attributes` is not set.
+const SAFE_ATTRIBUTES: [(&str, &[&str]); 2] = [("abbr", &["title"]), ("time", &["datetime"])];
+
+/// Tags the renderer emits and the raw HTML tags allowed in `content`.
+fn allowed_tags(content: &ContentConfig) -> HashSet<&str> {
+  let mut tags: HashSet<&str> = MARKDOWN_TAGS.into_iter().collect();
+  match &content.allowed_tags {
+    Some(allowed) => tags.extend(allowed.iter().map(String::as_str)),
+    None => tags.extend(SAFE_TAGS),
+  }
+  tags
+}
+
+/// Decides, for the raw HTML of a document in order, what can go into
+/// `content_mode = "markup"` as written: only tags [`sanitize`] would keep,
+/// so never `<script>`, `<style>` or `<svelte:...>`, and no Svelte
+/// directives such as `on:click`. The closing tags of anything refused are
+/// refused too, so the markup stays balanced.
+pub struct MarkupFilter<'a> {
+  tags: HashSet<&'a str>,
+  refused: Vec<String>,
+  tag: Regex,
+  directive: Regex,
+}
+
+impl<'a> MarkupFilter<'a> {
+  pub fn new(content: &'a ContentConfig) -> MarkupFilter<'a> {
+    MarkupFilter {
+      tags: allowed_tags(content),
+      refused: Vec::new(),
+      tag: Regex::new(r"<(/?)([A-Za-z][A-Za-z0-9:-]*)([^<>]*)").unwrap(),
+      directive: Regex::new(r"\s(?:on|bind|use|class|style|transition|in|out|animate|let):").unwrap(),
+    }
+  }
+
+  /// Whether `raw` can be written out as markup; if not it is shown as text.
+  pub fn allows(&mut self, raw: &str) -> bool {
+    let tags: Vec<(bool, String, bool)> = self.tag.captures_iter(raw)
+      .map(|caps| (caps[1].is_empty(), caps[2].to_ascii_lowercase(), caps[3].trim_end().ends_with('/')))
+      .collect();
+    let allowed = tags.iter().all(|(_, name, _)| self.tags.contains(name.as_str())) && !self.directive.is_match(raw);
+    if !allowed {
+      for (opening, name, self_closing) in tags {
+        if !opening {
+          if let Some(i) = self.refused.iter().rposition(|refused| *refused == name) {
+            self.refused.remove(i);
+          }
+        }
+        else if !self_closing && !VOID_TAGS.contains(&name.as_str()) {
+          self.refused.push(name);
+        }
+      }
+      return false;
+    }
+    let mut closes_refused = false;
+    for (opening, name, _) in &tags {
+      if let Some(i) = self.refused.iter().rposition(|refused| refused == name).filter(|_| !opening) {
+        self.refused.remove(i);
+        closes_refused = true;
+      }
+    }
+    !closes_refused
+  }
+}
+
+/// Elements without a closing tag.
+const VOID_TAGS: [&str; 8] = ["br", "hr", "img", "input", "wbr", "source", "track", "embed"];
+
+pub fn sanitize(html: &str, content: &ContentConfig) -> String {
+  let tags = allowed_tags(content);
+
+  let mut attributes: HashMap<&str, HashSet<&str>> = HashMap::new();
+  for (tag, names) in MARKDOWN_ATTRIBUTES {
+    attributes.entry(tag).or_default().extend(names.iter().copied());
+  }
+  match &content.allowed_attributes {
+    Some(configured) => {
+      for (tag, names) in configured {
+        attributes.entry(tag.as_str()).or_default().extend(names.iter().map(String::as_str));
+      }
+    }
+    None => {
+      for (tag, names) in SAFE_ATTRIBUTES {
+        attributes.entry(tag).or_default().extend(names.iter().copied());
+      }
+    }
+  }
+
+  Builder::default()
+    .tags(tags)
+    .tag_attributes(attributes)
+    .generic_attributes(["id", "class"].into_iter().collect())
+    .link_rel(None)
+    .clean(html)
+    .to_string()
+}
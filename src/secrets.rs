@@ -0,0 +1,89 @@
+//! Looks for credentials and other things that should not be published in
+//! the markdown sources.
+
+use regex::Regex;
+
+use crate::config::SecretsConfig;
+
+const DEFAULT_PATTERNS: [(&str, &str); 5] = [
+  ("AWS access key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+  ("GitHub token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+  ("private key", r"-----BEGIN (?:[A-Z]+ )?PRIVATE KEY-----"),
+  ("Slack token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}"),
+  ("Stripe secret key", r"\bsk_live_[A-Za-z0-9]{16,}"),
+];
+const EMAIL_PATTERN: &str = r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b";
+
+/// A line directly after this comment is not scanned.
+pub const ALLOW_COMMENT: &str = "<!-- allow-secret -->";
+
+pub struct Finding {
+  /// 1-based line in the source file.
+  pub line: usize,
+  pub kind: String,
+  /// The match with all but its first characters masked.
+  pub preview: String,
+  pub in_code_block: bool,
+}
+
+pub struct SecretScanner {
+  patterns: Vec<(String, Regex)>,
+}
+
+impl SecretScanner {
+  pub fn new(config: &SecretsConfig) -> Result<SecretScanner, String> {
+    let mut patterns: Vec<(String, Regex)> = DEFAULT_PATTERNS.iter()
+      .map(|(kind, pattern)| (kind.to_string(), Regex::new(pattern).unwrap()))
+      .collect();
+    if config.emails {
+      patterns.push(("email address".to_string(), Regex::new(EMAIL_PATTERN).unwrap()));
+    }
+    for pattern in &config.patterns {
+      let regex = Regex::new(pattern).map_err(|e| format!("invalid secrets pattern '{}': {}", pattern, e))?;
+      patterns.push((format!("match for '{}'", pattern), regex));
+    }
+    Ok(SecretScanner { patterns })
+  }
+
+  /// Scans a whole source file, frontmatter included, line by line.
+  pub fn scan(&self, source: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut allowed = false;
+    for (i, line) in source.lines().enumerate() {
+      let trimmed = line.trim_start();
+      let in_code_block = match fence {
+        Some(marker) => {
+          if trimmed.starts_with(marker) {
+            fence = None;
+          }
+          true
+        }
+        None => {
+          fence = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker));
+          false
+        }
+      };
+
+      if !allowed {
+        for (kind, regex) in &self.patterns {
+          for found in regex.find_iter(line) {
+            findings.push(Finding {
+              line: i + 1,
+              kind: kind.clone(),
+              preview: redact(found.as_str()),
+              in_code_block,
+            });
+          }
+        }
+      }
+      allowed = line.trim() == ALLOW_COMMENT;
+    }
+    findings
+  }
+}
+
+fn redact(secret: &str) -> String {
+  let shown: String = secret.chars().take(4).collect();
+  format!("{}{}", shown, "*".repeat(secret.chars().count().saturating_sub(4).min(12)))
+}
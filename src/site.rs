@@ -0,0 +1,836 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::anchors::{self, AnchorMap};
+use crate::assets::Assets;
+use crate::cache::{self, BuildCache, CachedEntry};
+use crate::changes::ChangeSet;
+use crate::config::{self, AuthorRule, AuthorsMode, Config, ContentConfig, ContentMode, SlugCollision, SlugConfig, DEFAULT_MARKDOWN_EXTENSIONS};
+use crate::component::{
+  content_file, generate_reader_component, generate_svelte_component, page_stylesheet, shared_stylesheet, CONTENT_FILE, PAGE_STYLESHEET, SHARED_STYLESHEET,
+};
+use crate::data::{
+  authors_module, count_tags, generate_archive_data, generate_data, generate_pages, generate_tag_cloud, generate_tag_data, DataFormat, AUTHORS_MODULE,
+};
+use crate::entry::Entry;
+use crate::feed;
+use crate::ids::{self, IdLock};
+use crate::images::{self, ImageDir};
+use crate::origins::{self, Origins};
+use crate::output::{self, ManifestEntry};
+use crate::related::resolve_related;
+use crate::secrets::SecretScanner;
+use crate::sitemap;
+use crate::slug;
+use crate::timings::{self, Timings};
+use crate::walk;
+use crate::RenderOptions;
+
+/// A section of content, read from `input_dir` and generated into
+/// `output_dir`. Listed under `[[content_types]]` in the config, or the
+/// defaults of `default_content_types`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContentType {
+  /// Name in the manifest and in `[content.<name>]`, such as `articles`.
+  pub name: String,
+  pub input_dir: String,
+  pub output_dir: String,
+  /// Default URL prefix of the images, which are copied to the same path
+  /// below the static root; `/images/<name>` when empty.
+  /// `image_url_prefix` in the content config overrides it.
+  #[serde(default)]
+  pub image_url_prefix: String,
+  /// What the entries are, such as `article`; exported by every component
+  /// and data file entry, and what per-kind behavior is keyed off.
+  #[serde(default = "default_kind")]
+  pub kind: String,
+  /// Name the data file exports the entries as. Defaults to `articles` and
+  /// `projects` for the built-in kinds and to `name` for any other.
+  #[serde(default)]
+  pub data_export: String,
+}
+
+/// Kind of the built-in content types. Articles are dated once; their
+/// frontmatter has no `date_end` or `ongoing`.
+pub const ARTICLE_KIND: &str = "article";
+pub const PROJECT_KIND: &str = "project";
+
+fn default_kind() -> String {
+  ARTICLE_KIND.to_string()
+}
+
+/// Switches that affect a whole build rather than how a page renders.
+pub struct BuildOptions {
+  /// Scanner run over every source before it is parsed.
+  pub secrets: Option<SecretScanner>,
+  /// Report secrets as errors instead of warnings.
+  pub strict_secrets: bool,
+  /// Remove generated pages whose source file no longer exists.
+  pub prune: bool,
+  /// Also build entries marked `draft: true`.
+  pub drafts: bool,
+  /// Leave out files git ignores.
+  pub respect_gitignore: bool,
+  /// Report slugs differing only in case as warnings instead of errors.
+  pub allow_case_collisions: bool,
+  /// How sources sharing a slug are handled.
+  pub on_slug_collision: SlugCollision,
+  /// Write a `+page.ts` with the `trailingSlash` option next to every page.
+  pub route_options: bool,
+  /// Run once over all generated files after each build, with the files
+  /// appended as arguments.
+  pub format_command: Option<String>,
+  /// Read sources that are not valid UTF-8 with invalid bytes replaced
+  /// instead of skipping them.
+  pub lossy_utf8: bool,
+  /// Extensions, without the dot, of the files read as markdown.
+  pub markdown_extensions: Vec<String>,
+  /// Authors of entries whose frontmatter lists none, by source path.
+  pub author_rules: Vec<AuthorRule>,
+  /// How slugs are derived from file names.
+  pub slugs: SlugConfig,
+  /// Slugs pinned by source path, used instead of deriving them.
+  pub slug_lock: BTreeMap<PathBuf, String>,
+  /// Where parsed entries are cached between runs; `None` disables the
+  /// cache.
+  pub cache_dir: Option<PathBuf>,
+  /// Print a suggested Content-Security-Policy after the build.
+  pub csp_report: bool,
+  /// Only regenerate the pages of these sources, reusing the cache and the
+  /// previous manifest for the rest.
+  pub changed_since: Option<ChangeSet>,
+  /// Print how long each source took after the build.
+  pub timings: bool,
+  /// Warn about sources taking longer than this many milliseconds.
+  pub slow_file_ms: Option<u64>,
+  /// Write the reading text of every published article to
+  /// `<dir>/<slug>.txt`.
+  pub emit_plaintext: Option<PathBuf>,
+  /// Avatar and page stylesheet of the generated pages.
+  pub assets: Arc<Assets>,
+}
+
+impl Default for BuildOptions {
+  fn default() -> BuildOptions {
+    BuildOptions {
+      secrets: None,
+      strict_secrets: false,
+      prune: false,
+      drafts: false,
+      respect_gitignore: false,
+      allow_case_collisions: false,
+      on_slug_collision: SlugCollision::Error,
+      route_options: false,
+      format_command: None,
+      lossy_utf8: false,
+      markdown_extensions: DEFAULT_MARKDOWN_EXTENSIONS.map(String::from).to_vec(),
+      author_rules: Vec::new(),
+      slugs: SlugConfig::default(),
+      slug_lock: BTreeMap::new(),
+      cache_dir: None,
+      csp_report: false,
+      changed_since: None,
+      timings: false,
+      slow_file_ms: None,
+      emit_plaintext: None,
+      assets: Arc::new(Assets::default()),
+    }
+  }
+}
+
+impl ContentType {
+  /// URL path the output directory is served at, such as `/articles`.
+  pub fn route(&self) -> String {
+    let route = self.output_dir.strip_prefix("src/routes").unwrap_or(&self.output_dir);
+    format!("/{}", route.trim_matches('/'))
+  }
+
+  /// URL prefix of this type's images, without a trailing slash.
+  pub fn image_url_prefix(&self, content_config: &ContentConfig) -> String {
+    let prefix = match &content_config.image_url_prefix {
+      Some(prefix) => prefix.clone(),
+      None if self.image_url_prefix.is_empty() => format!("/images/{}", self.name),
+      None => self.image_url_prefix.clone(),
+    };
+    prefix.trim_end_matches('/').to_string()
+  }
+
+  /// Name the data file exports the entries as, such as `articles`.
+  pub fn data_export(&self) -> &str {
+    match self.kind.as_str() {
+      _ if !self.data_export.is_empty() => &self.data_export,
+      ARTICLE_KIND => "articles",
+      PROJECT_KIND => "projects",
+      _ => &self.name,
+    }
+  }
+}
+
+/// The content types whose input directory exists. The others, which only
+/// the defaults can have, are skipped with a warning; an error is reported
+/// when none exists, as there would be nothing to build.
+pub(crate) fn existing_content_types(config: &Config) -> Vec<ContentType> {
+  let (existing, missing): (Vec<ContentType>, Vec<ContentType>) = config.content_types()
+    .into_iter()
+    .partition(|content_type| Path::new(&content_type.input_dir).is_dir());
+  for content_type in &missing {
+    warn!("Input directory {} does not exist, skipping", content_type.input_dir);
+  }
+  if existing.is_empty() {
+    let dirs: Vec<&str> = missing.iter().map(|content_type| content_type.input_dir.as_str()).collect();
+    error!("Error: no input directory exists ({}), nothing to build", dirs.join(", "));
+  }
+  existing
+}
+
+/// The content types of a config that lists none.
+pub fn default_content_types() -> Vec<ContentType> {
+  vec![
+    ContentType {
+      name: "articles".to_string(),
+      input_dir: "data/articles".to_string(),
+      output_dir: "src/routes/articles".to_string(),
+      image_url_prefix: "/images/articles".to_string(),
+      kind: ARTICLE_KIND.to_string(),
+      data_export: String::new(),
+    },
+    ContentType {
+      name: "projects".to_string(),
+      input_dir: "data/projects".to_string(),
+      output_dir: "src/routes/projects".to_string(),
+      image_url_prefix: "/images/projects".to_string(),
+      kind: PROJECT_KIND.to_string(),
+      data_export: String::new(),
+    },
+  ]
+}
+
+pub fn build(config: &Config, build_options: &BuildOptions) {
+  let mut cache = build_options.cache_dir.as_deref().map(BuildCache::load).unwrap_or_default();
+  rebuild(config, build_options, &mut cache);
+}
+
+/// Builds like [`build`], parsing only sources that changed since they were
+/// put in `cache`. Data files and cross-entry exports are always written
+/// from the whole set. The cache is saved to the cache directory afterwards.
+/// Returns what each page built cost, by source.
+pub fn rebuild(config: &Config, build_options: &BuildOptions, cache: &mut BuildCache) -> BTreeMap<PathBuf, Timings> {
+  let variables = env_variables(&config.env_allowlist);
+  let kept = cache.check_key(cache_key(config, &build_options.assets, &variables, &build_options.slug_lock));
+  let incremental = match &build_options.changed_since {
+    Some(changes) => {
+      let previous = match output::read_manifest() {
+        Ok(previous) => previous,
+        Err(e) => {
+          error!("{}; --changed-since needs the manifest of a previous build", e);
+          return BTreeMap::new();
+        }
+      };
+      if kept {
+        Some(Incremental::new(changes, previous))
+      }
+      else {
+        info!("Config, assets or environment changed since the last build, rebuilding everything");
+        None
+      }
+    }
+    None => None,
+  };
+  let build_report = match &incremental {
+    Some(incremental) => {
+      let files = incremental.changes.files();
+      info!("Rebuilding {} files changed since {}", files.len(), incremental.changes.git_ref);
+      for file in &files {
+        info!("  {}", file);
+      }
+      json!({ "mode": "changed-since", "ref": incremental.changes.git_ref, "files": files })
+    }
+    None => json!({ "mode": "full" }),
+  };
+  let mut ids = match config.id_namespace().map(IdLock::read).transpose() {
+    Ok(ids) => ids,
+    Err(e) => {
+      error!("{}", e);
+      return BTreeMap::new();
+    }
+  };
+  let mut manifest = Vec::new();
+  let mut anchors = AnchorMap::new();
+  let mut pages = Vec::new();
+  let mut site_origins = Origins::new();
+  let mut tag_counts = BTreeMap::new();
+  // Input directories of the entries counted in `tag_counts`.
+  let mut tag_sources = Vec::new();
+  let mut image_dirs = Vec::new();
+  // Indexes into `pages`.
+  let mut articles = Vec::new();
+  let content_types = existing_content_types(config);
+  for content_type in &content_types {
+    let content_config = config.content_config(&content_type.name);
+    let image_url_prefix = content_type.image_url_prefix(&content_config);
+    let options = render_options(content_type, content_config)
+      .variables(variables.clone())
+      .default_author(config.default_author.clone())
+      .url_style(config.url_style)
+      .page_routes(config.page_routes())
+      .frontmatter_aliases(config.frontmatter_aliases.clone())
+      .strict_frontmatter(config.strict_frontmatter)
+      .authors(config.authors)
+      .plaintext(config.plaintext.clone())
+      .site_url(config.site_url.clone())
+      .source_comment(config.source_comment.clone())
+      .assets(build_options.assets.clone())
+      .ids(ids.is_some());
+    let entries = process_content(content_type, &options, build_options, cache, incremental.as_ref(), ids.as_mut());
+    if options.content.css_split {
+      let path = Path::new(&content_type.output_dir).join(SHARED_STYLESHEET);
+      output::write_generated(&path, &shared_stylesheet(&build_options.assets))
+        .unwrap_or_else(|e| error!("Error writing to {}: {}", path.display(), e));
+    }
+    let header = output::ts_header(&config.source_comment, &content_type.input_dir);
+    let data_format = DataFormat {
+      kind: &content_type.kind,
+      export: content_type.data_export(),
+      authors: config.authors,
+      locale: config.content_locale.as_deref(),
+      header: &header,
+      typed: config.typed_data,
+    };
+    generate_data(&entries, Path::new(&content_type.output_dir), &data_format)
+      .unwrap_or_else(|e| error!("Error generating data: {}", e));
+    if let Some(page_size) = options.content.page_size {
+      generate_pages(&entries, Path::new(&content_type.output_dir), page_size, &data_format)
+        .unwrap_or_else(|e| error!("Error generating paginated data: {}", e));
+    }
+    let authors = (config.authors == AuthorsMode::Normalized).then(|| authors_module(&entries, &header));
+    write_sibling(&Path::new(&content_type.output_dir).join(format!("{}.ts", AUTHORS_MODULE)), authors);
+    generate_tag_data(&entries, Path::new(&content_type.output_dir), &header)
+      .unwrap_or_else(|e| error!("Error generating tag data: {}", e));
+    if content_type.kind == ARTICLE_KIND || config.tag_cloud.projects {
+      count_tags(&mut tag_counts, &entries);
+      tag_sources.push(content_type.input_dir.as_str());
+    }
+    if content_type.kind == ARTICLE_KIND {
+      generate_archive_data(&entries, Path::new(&content_type.output_dir), &header)
+        .unwrap_or_else(|e| error!("Error generating archive data: {}", e));
+    }
+    info!("Generated {} pages from {}", entries.len(), content_type.input_dir);
+    manifest.extend(entries.iter().map(|entry| json!({
+      "type": content_type.name,
+      "kind": content_type.kind,
+      "id": entry.id,
+      "slug": entry.frontmatter.slug,
+      "url": entry.url,
+      "permalink": entry.permalink,
+      "source": entry.path,
+      "checksum": entry.checksum,
+      "author_rule": entry.default_authors
+        .then(|| config::author_rule(&build_options.author_rules, Path::new(&content_type.input_dir), &entry.path))
+        .flatten()
+        .map(|rule| &rule.pattern),
+      "social": entry.social,
+      "related": entry.related.iter().map(|related| &related.slug).collect::<Vec<_>>(),
+      "origins": entry.origins,
+    })));
+    for entry in &entries {
+      origins::merge(&mut site_origins, &entry.origins);
+    }
+    anchors::collect(&mut anchors, &content_type.name, &entries);
+    if content_type.kind == ARTICLE_KIND {
+      articles.extend(pages.len()..pages.len() + entries.len());
+    }
+    pages.extend(entries);
+
+    let input_images = Path::new(&content_type.input_dir).join("images");
+    if input_images.exists() {
+      let static_dir = config.static_root.join(image_url_prefix.trim_start_matches('/'));
+      image_dirs.push(ImageDir { src: input_images, dst: static_dir });
+    }
+  }
+  anchors::check_links(&pages);
+  report_timings(&pages, build_options);
+  if pages.iter().any(|entry| !entry.frontmatter.authors.is_empty()) {
+    build_options.assets.write_avatar(&config.static_root)
+      .unwrap_or_else(|e| error!("Error writing avatar to {}: {}", config.static_root.display(), e));
+  }
+  if let Some(path) = &config.tag_cloud.path {
+    let header = output::ts_header(&config.source_comment, &tag_sources.join(", "));
+    generate_tag_cloud(&tag_counts, path, config.tag_cloud.buckets(), &header)
+      .unwrap_or_else(|e| error!("Error writing {}: {}", path.display(), e));
+  }
+  let mut site_files = Vec::new();
+  let published: Vec<&Entry> = pages.iter().filter(|entry| entry.frontmatter.held_back().is_none()).collect();
+  let published_articles: Vec<&Entry> = articles.iter().map(|&i| &pages[i]).filter(|entry| entry.frontmatter.held_back().is_none()).collect();
+  match &config.site_url {
+    Some(site_url) if config.sitemap.enabled => {
+      sitemap::write(&published, site_url, config.sitemap.max_urls(), &config.static_root)
+        .map(|files| site_files.extend(files))
+        .unwrap_or_else(|e| error!("Error writing sitemap to {}: {}", config.static_root.display(), e));
+    }
+    _ => sitemap::remove_stale(&config.static_root, &[])
+      .unwrap_or_else(|e| error!("Error removing sitemap from {}: {}", config.static_root.display(), e)),
+  }
+  match &config.site_url {
+    Some(site_url) if config.feed.enabled => {
+      feed::write(&published_articles, site_url, &config.feed, &config.static_root)
+        .map(|files| site_files.extend(files))
+        .unwrap_or_else(|e| error!("Error writing feed to {}: {}", config.static_root.display(), e));
+    }
+    _ => feed::remove_stale(&config.static_root, &[])
+      .unwrap_or_else(|e| error!("Error removing feed from {}: {}", config.static_root.display(), e)),
+  }
+  if let Some(dir) = &build_options.emit_plaintext {
+    write_plaintext(&published_articles, dir)
+      .map(|files| site_files.extend(files))
+      .unwrap_or_else(|e| error!("Error writing plain text to {}: {}", dir.display(), e));
+  }
+  if let Some(command) = &build_options.format_command {
+    let files: Vec<PathBuf> = content_types
+      .iter()
+      .flat_map(|content_type| output::generated_files(Path::new(&content_type.output_dir)))
+      .collect();
+    if !files.is_empty() {
+      output::run_format_command(command, &files).unwrap_or_else(|e| error!("{}", e));
+    }
+  }
+  output::write_manifest(manifest, config.content_locale.as_deref(), &site_origins, build_report, &site_files)
+    .unwrap_or_else(|e| error!("Error writing {}: {}", output::MANIFEST_PATH, e));
+  anchors::write(&anchors)
+    .unwrap_or_else(|e| error!("Error writing {}: {}", anchors::ANCHORS_PATH, e));
+  if let Some(ids) = &ids {
+    ids.save()
+      .unwrap_or_else(|e| error!("Error writing {}: {}", ids::ID_LOCK_PATH, e));
+  }
+  if build_options.csp_report {
+    println!("Content-Security-Policy: {}", origins::csp(&site_origins));
+  }
+  if let Some(dir) = &build_options.cache_dir {
+    cache.save(dir)
+      .unwrap_or_else(|e| error!("Error writing cache to {}: {}", dir.display(), e));
+  }
+  // Last, so stopping it with Ctrl-C leaves everything else complete.
+  match images::copy(&image_dirs, build_options.respect_gitignore, build_options.cache_dir.as_deref()) {
+    Ok(Some(remaining)) => error!("Interrupted with {} images left to copy; run again to copy them", remaining),
+    Ok(None) => {}
+    Err(e) => error!("Error copying images: {}", e),
+  }
+  pages.into_iter().map(|entry| (entry.path, entry.timings)).collect()
+}
+
+/// Writes the reading text of each article to `<dir>/<slug>.txt` and
+/// returns the files written.
+fn write_plaintext(articles: &[&Entry], dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+  std::fs::create_dir_all(dir)?;
+  let mut written = Vec::new();
+  for entry in articles {
+    let path = dir.join(format!("{}.txt", entry.frontmatter.slug));
+    std::fs::write(&path, format!("{}\n", entry.plaintext))?;
+    written.push(path);
+  }
+  Ok(written)
+}
+
+/// Warns about sources slower than `slow_file_ms`, slowest first, and with
+/// `--timings` prints the time of every source and of each of its phases.
+fn report_timings(pages: &[Entry], build_options: &BuildOptions) {
+  let mut timings: Vec<(&Path, &Timings)> = pages.iter().map(|entry| (entry.path.as_path(), &entry.timings)).collect();
+  timings.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then(a.0.cmp(b.0)));
+  if let Some(limit) = build_options.slow_file_ms {
+    for (path, time) in timings.iter().filter(|(_, time)| time.total().as_millis() > limit as u128) {
+      warn!("[slow-file] {}: took {} ms, more than slow_file_ms ({} ms)", path.display(), time.total().as_millis(), limit);
+    }
+  }
+  if build_options.timings {
+    let mut total = Timings::default();
+    println!("{:>10}  {:>10}  {:>10}  {:>10}  source", "time", "parse", "generate", "write");
+    for (path, time) in &timings {
+      println!("{}  {}", timing_columns(time), path.display());
+      total.add(time);
+    }
+    println!("{}  total for {} sources", timing_columns(&total), timings.len());
+  }
+}
+
+fn timing_columns(timings: &Timings) -> String {
+  [timings.total(), timings.parse, timings.generate, timings.write]
+    .map(|time| format!("{:>10}", timings::format_duration(time)))
+    .join("  ")
+}
+
+/// What a `--changed-since` build takes over from the previous one.
+struct Incremental<'a> {
+  changes: &'a ChangeSet,
+  /// Pages of the previous build by URL.
+  previous: BTreeMap<String, ManifestEntry>,
+}
+
+impl<'a> Incremental<'a> {
+  fn new(changes: &'a ChangeSet, previous: Vec<ManifestEntry>) -> Incremental<'a> {
+    let previous = previous.into_iter().map(|page| (page.url.clone(), page)).collect();
+    Incremental { changes, previous }
+  }
+
+  /// The previous build's record of the page of `entry`, if that page is
+  /// still current: its source did not change, it lists the same related
+  /// entries, and none of those changed either.
+  fn current(&self, entry: &Entry, changed_slugs: &BTreeSet<&str>) -> Option<&ManifestEntry> {
+    if self.changes.is_changed(&entry.path) {
+      return None;
+    }
+    let page = self.previous.get(&entry.url)?;
+    let same_related = page.related.iter().eq(entry.related.iter().map(|related| &related.slug));
+    let related_changed = entry.related.iter().any(|related| changed_slugs.contains(related.slug.as_str()));
+    (same_related && !related_changed).then_some(page)
+  }
+}
+
+/// Everything besides the source that a parsed entry depends on.
+fn cache_key(config: &Config, assets: &Assets, variables: &BTreeMap<String, String>, slug_lock: &BTreeMap<PathBuf, String>) -> String {
+  let variables = serde_json::to_string(variables).unwrap();
+  let slug_lock = serde_json::to_string(slug_lock).unwrap();
+  cache::hash(&format!("{}\n{}\n{}\n{}\n{}", env!("CARGO_PKG_VERSION"), config.fingerprint, assets.fingerprint, variables, slug_lock))
+}
+
+/// Reads the allowlisted environment variables, warning about unset ones.
+pub(crate) fn env_variables(allowlist: &[String]) -> BTreeMap<String, String> {
+  let mut variables = BTreeMap::new();
+  for name in allowlist {
+    match std::env::var(name) {
+      Ok(value) => {
+        variables.insert(name.clone(), value);
+      }
+      Err(_) => warn!("Environment variable {} from env_allowlist is not set", name),
+    }
+  }
+  variables
+}
+
+pub(crate) fn render_options(content_type: &ContentType, content_config: ContentConfig) -> RenderOptions {
+  RenderOptions::default()
+    .kind(&content_type.kind)
+    .image_base(content_type.image_url_prefix(&content_config))
+    .route(content_type.route())
+    .content(content_config)
+}
+
+/// Parses every markdown file of a content type, then links related entries.
+/// Drafts are left out unless the build asks for them. Sources unchanged
+/// since they were cached are not parsed again, but their warnings are
+/// reported again; entries of sources that are gone are dropped from the
+/// cache. Cached sources not in `changes` are not even read.
+pub(crate) fn load_entries(content_type: &ContentType, options: &RenderOptions, build_options: &BuildOptions, cache: &mut BuildCache, changes: Option<&ChangeSet>) -> Vec<Entry> {
+  let mut cached = std::mem::take(&mut cache.entries);
+  let (mut entries, drafts): (Vec<Entry>, Vec<Entry>) = walk::source_files(Path::new(&content_type.input_dir), build_options.respect_gitignore)
+    .into_iter()
+    .filter(|path| walk::has_extension(path, &build_options.markdown_extensions))
+    .filter_map(|input_path| {
+      let unchanged = changes.is_some_and(|changes| !changes.is_changed(&input_path));
+      let started = Instant::now();
+      let memory = timings::resident_memory();
+      let record = match cached.remove(&input_path) {
+        Some(record) if unchanged => record,
+        record => {
+          let content = read_source(&input_path, build_options.lossy_utf8)?;
+          if let Some(scanner) = &build_options.secrets {
+            report_secrets(&input_path, &content, scanner, build_options.strict_secrets);
+          }
+          let source = cache::hash(&content);
+          match record.filter(|record| record.source == source) {
+            Some(record) => record,
+            None => {
+              let (entry, warnings) = parse_entry(&input_path, &content, content_type, options, build_options)
+                .map_err(|e| error!("Error in {}: {}", input_path.display(), e))
+                .ok()?;
+              CachedEntry { source, warnings, draft: entry.frontmatter.draft, published: entry.frontmatter.published, entry }
+            }
+          }
+        }
+      };
+      for warning in &record.warnings {
+        warn!("{}: {}", input_path.display(), warning);
+      }
+      check_images(&input_path, &record.entry, content_type);
+      let mut entry = record.entry.clone();
+      entry.frontmatter.draft = record.draft;
+      entry.frontmatter.published = record.published;
+      entry.timings.parse = started.elapsed();
+      entry.timings.record_memory(memory);
+      cache.entries.insert(input_path, record);
+      Some(entry)
+    })
+    .partition(|entry| build_options.drafts || entry.frontmatter.held_back().is_none());
+  // Entries of other content types pass through untouched.
+  cached.retain(|path, _| !path.starts_with(&content_type.input_dir));
+  cache.entries.append(&mut cached);
+  resolve_collisions(&mut entries, build_options.on_slug_collision, "slug", |entry| &mut entry.frontmatter.slug);
+  for entry in &mut entries {
+    entry.permalink = slug::permalink(options.content.permalink(), &entry.frontmatter.slug, entry.date);
+  }
+  // Patterns without `{slug}` can place different slugs at the same path.
+  resolve_collisions(&mut entries, build_options.on_slug_collision, "permalink", |entry| &mut entry.permalink);
+  for entry in &mut entries {
+    entry.url = output::page_url(&content_type.route(), &entry.permalink, options.url_style.unwrap_or_default());
+  }
+  check_case_collisions(&entries, build_options.allow_case_collisions);
+  for entry in drafts.iter().filter(|entry| !entry.frontmatter.published) {
+    warn!("[unpublished] {}: not built, `published` is false", entry.path.display());
+  }
+  let held_back = drafts.into_iter()
+    .map(|entry| (entry.frontmatter.slug.clone(), entry.frontmatter.held_back().unwrap_or_default()))
+    .collect();
+  resolve_related(&mut entries, &held_back, options.content.related_limit());
+  entries
+}
+
+fn report_secrets(path: &Path, content: &str, scanner: &SecretScanner, strict: bool) {
+  for finding in scanner.scan(content) {
+    let location = if finding.in_code_block { " in a code block" } else { "" };
+    let message = format!(
+      "{}:{}: possible {}{}: {} (add {} on the line before to allow it)",
+      path.display(),
+      finding.line,
+      finding.kind,
+      location,
+      finding.preview,
+      crate::secrets::ALLOW_COMMENT
+    );
+    if strict {
+      error!("Error: {}", message);
+    }
+    else {
+      warn!("{}", message);
+    }
+  }
+}
+
+/// Sources with the same slug or permalink, the `what` that `key` picks out,
+/// would write the same page. Sources are taken in path order, so the same
+/// one always keeps the value.
+fn resolve_collisions(entries: &mut Vec<Entry>, strategy: SlugCollision, what: &str, key: fn(&mut Entry) -> &mut String) {
+  let mut order: Vec<usize> = (0..entries.len()).collect();
+  order.sort_by(|&a, &b| entries[a].path.cmp(&entries[b].path));
+  let mut taken: BTreeMap<String, PathBuf> = BTreeMap::new();
+  let mut all: BTreeSet<String> = entries.iter_mut().map(|entry| key(entry).clone()).collect();
+  let mut dropped = BTreeSet::new();
+  for index in order {
+    let entry = &mut entries[index];
+    let slug = key(entry).clone();
+    let Some(first) = taken.get(&slug) else {
+      taken.insert(slug, entry.path.clone());
+      continue;
+    };
+    let message = format!("{} has the same {} '{}' as {}", entry.path.display(), what, slug, first.display());
+    match strategy {
+      SlugCollision::Error => {
+        error!("Error: {}", message);
+        dropped.insert(index);
+      }
+      SlugCollision::WarnSkip => {
+        warn!("{}; skipped", message);
+        dropped.insert(index);
+      }
+      SlugCollision::WarnSuffix => {
+        let mut n = 2;
+        while all.contains(&format!("{}-{}", slug, n)) {
+          n += 1;
+        }
+        let suffixed = format!("{}-{}", slug, n);
+        warn!("{}; using '{}'", message, suffixed);
+        all.insert(suffixed.clone());
+        taken.insert(suffixed.clone(), entry.path.clone());
+        *key(entry) = suffixed;
+      }
+    }
+  }
+  let mut index = 0;
+  entries.retain(|_| {
+    index += 1;
+    !dropped.contains(&(index - 1))
+  });
+}
+
+/// Slugs that differ only in case name the same output directory on
+/// case-insensitive filesystems such as macOS and Windows defaults.
+fn check_case_collisions(entries: &[Entry], allow: bool) {
+  let mut by_folded: BTreeMap<String, Vec<&Entry>> = BTreeMap::new();
+  for entry in entries {
+    by_folded.entry(entry.frontmatter.slug.to_lowercase()).or_default().push(entry);
+  }
+  for group in by_folded.values() {
+    let slugs: BTreeSet<&str> = group.iter().map(|entry| entry.frontmatter.slug.as_str()).collect();
+    if slugs.len() < 2 {
+      continue;
+    }
+    let paths: Vec<String> = group.iter().map(|entry| entry.path.display().to_string()).collect();
+    let message = format!(
+      "slugs differing only in case collide on case-insensitive filesystems: {}",
+      paths.join(", ")
+    );
+    if allow {
+      warn!("{}", message);
+    }
+    else {
+      error!("Error: {}", message);
+    }
+  }
+}
+
+fn process_content(content_type: &ContentType, options: &RenderOptions, build_options: &BuildOptions, cache: &mut BuildCache, incremental: Option<&Incremental>, ids: Option<&mut IdLock>) -> Vec<Entry> {
+  let mut entries = load_entries(content_type, options, build_options, cache, incremental.map(|incremental| incremental.changes));
+  if let Some(ids) = ids {
+    ids.assign(&content_type.input_dir, &mut entries);
+  }
+
+  let output_dir = Path::new(&content_type.output_dir);
+  output::check_slug_collisions(output_dir, entries.iter().map(|entry| entry.permalink.as_str()));
+  output::check_slug_case(output_dir, entries.iter().map(|entry| entry.permalink.as_str()));
+  let permalinks: Vec<&str> = entries.iter().map(|entry| entry.permalink.as_str()).collect();
+  output::check_route_conflicts(output_dir, &permalinks);
+
+  let changed_slugs: BTreeSet<&str> = match incremental {
+    Some(incremental) => entries.iter()
+      .filter(|entry| incremental.changes.is_changed(&entry.path))
+      .map(|entry| entry.frontmatter.slug.as_str())
+      .collect(),
+    None => BTreeSet::new(),
+  };
+  let current: Vec<Option<Origins>> = entries.iter()
+    .map(|entry| incremental?.current(entry, &changed_slugs).map(|page| page.origins.clone()))
+    .collect();
+  for (entry, current) in entries.iter_mut().zip(current) {
+    if let Some(origins) = current {
+      entry.origins = origins;
+      continue;
+    }
+    let started = Instant::now();
+    let memory = timings::resident_memory();
+    let output_path = output_dir.join(&entry.permalink).join(output::PAGE_FILE);
+    let svelte_content = generate_svelte_component(entry, options);
+    let stylesheet = options.content.css_file.then(|| page_stylesheet(options));
+    let content = (options.content.content_mode == ContentMode::File).then(|| content_file(entry, options));
+    let route_options = build_options.route_options.then(|| route_options_module(entry, options));
+    let reader = options.content.reader.then(|| generate_reader_component(entry, options)).and_then(|reader| {
+      reader.map_err(|e| warn!("[reader-math] {}: {}; no reader page written", entry.path.display(), e)).ok()
+    });
+    // Taken from what is written, so only emitted references count.
+    let mut page_origins = Origins::new();
+    for text in [Some(&svelte_content), content.as_ref(), reader.as_ref()].into_iter().flatten() {
+      origins::collect(text, &mut page_origins);
+    }
+    entry.origins = page_origins;
+    entry.timings.output_size = [Some(&svelte_content), stylesheet.as_ref(), content.as_ref(), route_options.as_ref(), reader.as_ref()]
+      .into_iter()
+      .flatten()
+      .map(|text| text.len() as u64)
+      .sum();
+    entry.timings.generate = started.elapsed();
+    entry.timings.record_memory(memory);
+
+    let started = Instant::now();
+    output::write_generated(&output_path, &svelte_content)
+      .unwrap_or_else(|e| error!("Error writing to {}: {}", output_path.display(), e));
+    write_sibling(&output_path.with_file_name(PAGE_STYLESHEET), stylesheet);
+    write_sibling(&output_path.with_file_name(CONTENT_FILE), content);
+    write_sibling(&output_path.with_file_name(output::ROUTE_OPTIONS_FILE), route_options);
+    write_sibling(&output_path.with_file_name(output::READER_DIR).join(output::PAGE_FILE), reader);
+    entry.timings.write = started.elapsed();
+  }
+
+  // An entry that failed to parse must not lose its previous output.
+  let removed = incremental.is_some_and(|incremental| incremental.changes.removed_below(Path::new(&content_type.input_dir)));
+  if (build_options.prune || removed) && !crate::log::failed() {
+    let permalinks = entries.iter().map(|entry| entry.permalink.clone()).collect();
+    output::prune(output_dir, &permalinks)
+      .unwrap_or_else(|e| error!("Error pruning {}: {}", output_dir.display(), e));
+  }
+
+  entries
+}
+
+/// Page options for SvelteKit matching the URL style of the emitted links.
+fn route_options_module(entry: &Entry, options: &RenderOptions) -> String {
+  let trailing_slash = options.url_style.unwrap_or_default().trailing_slash();
+  let header = output::ts_header(&options.source_comment, &entry.path.to_string_lossy().replace('\\', "/"));
+  format!("{}\nexport const trailingSlash = '{}';\n", header, trailing_slash)
+}
+
+/// Writes an optional file next to a page, or removes a previously
+/// generated one when the setting producing it is off. A directory left
+/// empty by the removal is removed too.
+fn write_sibling(path: &Path, content: Option<String>) {
+  match content {
+    Some(content) => output::write_generated(path, &content)
+      .unwrap_or_else(|e| error!("Error writing to {}: {}", path.display(), e)),
+    None if output::is_generated(path) => {
+      std::fs::remove_file(path)
+        .unwrap_or_else(|e| error!("Error removing {}: {}", path.display(), e));
+      let _ = std::fs::remove_dir(path.parent().unwrap());
+    }
+    None => {}
+  }
+}
+
+/// Reads a source file. Files that are not valid UTF-8 are skipped with a
+/// warning, or decoded lossily when `lossy` is set; read errors are reported
+/// and the file skipped.
+pub(crate) fn read_source(path: &Path, lossy: bool) -> Option<String> {
+  let bytes = std::fs::read(path)
+    .map_err(|e| error!("Error reading file {}: {}", path.display(), e))
+    .ok()?;
+  match String::from_utf8(bytes) {
+    Ok(content) => Some(content),
+    Err(e) if lossy => {
+      warn!("{}: not valid UTF-8, invalid bytes were replaced", path.display());
+      Some(String::from_utf8_lossy(e.as_bytes()).into_owned())
+    }
+    Err(e) => {
+      warn!("{}: skipped, not valid UTF-8 ({}); use --lossy-utf8 to read it anyway", path.display(), e.utf8_error());
+      None
+    }
+  }
+}
+
+/// Parses one source. The slug is the one pinned in the slug lock, or else
+/// derived from the file name. Authors for entries listing none come from
+/// the most specific matching author rule, then the default author.
+fn parse_entry(input_path: &Path, content: &str, content_type: &ContentType, options: &RenderOptions, build_options: &BuildOptions) -> Result<(Entry, Vec<String>), String> {
+  let relative_path = input_path.strip_prefix(&content_type.input_dir).unwrap();
+  let file_stem = relative_path.file_stem().unwrap().to_str().unwrap();
+  let slug = match build_options.slug_lock.get(input_path) {
+    Some(slug) => slug.clone(),
+    None => slug::page_slug(file_stem, &build_options.slugs)?,
+  };
+
+  let author_rules = &build_options.author_rules;
+  let mut options = options.clone().slug(&slug);
+  if let Some(rule) = config::author_rule(author_rules, Path::new(&content_type.input_dir), input_path) {
+    options = options.default_author(Some(rule.author.clone()));
+  }
+  let (mut entry, mut warnings) = Entry::parse(content, &options)?;
+  if slug != file_stem && !build_options.slug_lock.contains_key(input_path) {
+    warnings.push(format!("[slug] file name '{}' is published as '{}'", file_stem, slug));
+  }
+  if entry.frontmatter.authors.is_empty() && !author_rules.is_empty() {
+    warnings.push("no `authors`, and neither an author_rules pattern nor default_author applies".to_string());
+  }
+  entry.path = input_path.to_path_buf();
+  Ok((entry, warnings))
+}
+
+/// Warns about images below `images/` the entry shows that are not in the
+/// content type's image directory, so none is copied. Checked on every
+/// build, cached or not, since images change without their source.
+fn check_images(input_path: &Path, entry: &Entry, content_type: &ContentType) {
+  let input_dir = Path::new(&content_type.input_dir);
+  for asset in entry.assets.iter().filter(|asset| asset.starts_with("images/")) {
+    if !input_dir.join(asset.replace("%20", " ")).is_file() {
+      warn!("[missing-image] {}: {} is not in {}", input_path.display(), asset, input_dir.join("images").display());
+    }
+  }
+}
+
@@ -0,0 +1,148 @@
+//! `sitemap.xml` for search engines, split into numbered files listed by an
+//! index once the site has more pages than one file may hold.
+
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use crate::entry::{Entry, ISO_DATE_FORMAT};
+use crate::output::{self, escape_xml, XML_HEADER};
+
+pub const SITEMAP_FILE: &str = "sitemap.xml";
+pub const INDEX_FILE: &str = "sitemap_index.xml";
+
+const NAMESPACE: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+/// Writes the sitemap of `pages` to `static_root`, in files of at most
+/// `max_urls` pages, and returns the files written. Pages are listed oldest
+/// first, then by slug, so a new page only changes the last file. Files
+/// left from a build with more chunks are removed.
+pub fn write(pages: &[&Entry], site_url: &str, max_urls: usize, static_root: &Path) -> std::io::Result<Vec<PathBuf>> {
+  let mut pages = pages.to_vec();
+  pages.sort_by(|a, b| (a.date, &a.frontmatter.slug, &a.url).cmp(&(b.date, &b.frontmatter.slug, &b.url)));
+  let chunks: Vec<&[&Entry]> = pages.chunks(max_urls).collect();
+
+  let mut written = Vec::new();
+  if chunks.len() <= 1 {
+    let path = static_root.join(SITEMAP_FILE);
+    output::write_generated(&path, &url_set(&pages, site_url))?;
+    written.push(path);
+  }
+  else {
+    let mut index = format!("{}\n<sitemapindex xmlns=\"{}\">\n", XML_HEADER, NAMESPACE);
+    for (i, chunk) in chunks.iter().enumerate() {
+      let name = format!("sitemap-{}.xml", i + 1);
+      let path = static_root.join(&name);
+      output::write_generated(&path, &url_set(chunk, site_url))?;
+      written.push(path);
+      index.push_str(&format!("  <sitemap><loc>{}/{}</loc></sitemap>\n", escape_xml(site_url), name));
+    }
+    index.push_str("</sitemapindex>\n");
+    let path = static_root.join(INDEX_FILE);
+    output::write_generated(&path, &index)?;
+    written.push(path);
+  }
+  remove_stale(static_root, &written)?;
+  Ok(written)
+}
+
+fn url_set(pages: &[&Entry], site_url: &str) -> String {
+  let mut xml = format!("{}\n<urlset xmlns=\"{}\">\n", XML_HEADER, NAMESPACE);
+  for entry in pages {
+    xml.push_str(&format!(
+      "  <url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+      escape_xml(&format!("{}{}", site_url, entry.url)),
+      entry.date.format(ISO_DATE_FORMAT),
+    ));
+  }
+  xml.push_str("</urlset>\n");
+  xml
+}
+
+/// Removes generated sitemap files of an earlier build that are not in
+/// `written`.
+pub(crate) fn remove_stale(static_root: &Path, written: &[PathBuf]) -> std::io::Result<()> {
+  let name = Regex::new(r"^(sitemap\.xml|sitemap_index\.xml|sitemap-\d+\.xml)$").unwrap();
+  let Ok(read_dir) = std::fs::read_dir(static_root) else {
+    return Ok(());
+  };
+  for entry in read_dir {
+    let path = entry?.path();
+    let matches = path.file_name().and_then(|name| name.to_str()).is_some_and(|file| name.is_match(file));
+    if matches && !written.contains(&path) && output::is_generated(&path) {
+      std::fs::remove_file(&path)?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::NaiveDate;
+  use crate::config::ContentConfig;
+  use crate::RenderOptions;
+
+  #[test]
+  fn lastmod_is_a_w3c_date_whatever_the_time_format() {
+    let content = ContentConfig { time_format: Some("%I:%M %p (%Z), %A".to_string()), ..ContentConfig::default() };
+    let options = RenderOptions::default().content(content);
+    let source = "---\ntitle: Post\ndate: 2024-02-10T23:30:00-05:00\ntags: []\n---\nText\n";
+    let (entry, _) = Entry::parse(source, &options).unwrap();
+
+    let xml = url_set(&[&entry], "https://example.com");
+    let lastmod = Regex::new(r"<lastmod>([^<]*)</lastmod>").unwrap().captures(&xml).unwrap()[1].to_string();
+    assert_eq!(lastmod, "2024-02-10");
+    assert!(NaiveDate::parse_from_str(&lastmod, ISO_DATE_FORMAT).is_ok());
+  }
+
+  #[test]
+  fn thousands_of_pages_are_chunked_and_indexed() {
+    let options = RenderOptions::default();
+    let (entry, _) = Entry::parse("---\ntitle: Post & more\ndate: 2020-01-01\ntags: []\n---\nText\n", &options).unwrap();
+    let pages: Vec<Entry> = (0..2500)
+      .map(|i| {
+        let mut page = entry.clone();
+        page.date = entry.date + chrono::Days::new(i / 3);
+        page.frontmatter.slug = format!("post-{}", i);
+        page.url = format!("/blog/post-{}?a=1&b=2", i);
+        page
+      })
+      .collect();
+    let pages: Vec<&Entry> = pages.iter().rev().collect();
+    let dir = tempfile::tempdir().unwrap();
+    let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+    let loc = Regex::new(r"<loc>([^<]*)</loc>").unwrap();
+
+    let written = write(&pages, "https://example.com", 1000, dir.path()).unwrap();
+    let names: Vec<String> = written.iter().map(|path| path.file_name().unwrap().to_string_lossy().into_owned()).collect();
+    assert_eq!(names, ["sitemap-1.xml", "sitemap-2.xml", "sitemap-3.xml", INDEX_FILE]);
+    let index = read(INDEX_FILE);
+    crate::output::tests::assert_well_formed(&index);
+    let chunks: Vec<&str> = loc.captures_iter(&index).map(|caps| caps.get(1).unwrap().as_str()).collect();
+    assert_eq!(chunks, ["https://example.com/sitemap-1.xml", "https://example.com/sitemap-2.xml", "https://example.com/sitemap-3.xml"]);
+
+    let mut urls = Vec::new();
+    for (name, count) in [("sitemap-1.xml", 1000), ("sitemap-2.xml", 1000), ("sitemap-3.xml", 500)] {
+      let xml = read(name);
+      crate::output::tests::assert_well_formed(&xml);
+      let chunk: Vec<String> = loc.captures_iter(&xml).map(|caps| caps[1].to_string()).collect();
+      assert_eq!(chunk.len(), count, "{}", name);
+      urls.extend(chunk);
+    }
+    let expected: Vec<String> = (0..2500).map(|i| format!("https://example.com/blog/post-{}?a=1&amp;b=2", i)).collect();
+    let mut sorted = urls.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(sorted.len(), 2500);
+    assert_eq!(urls.first(), expected.first());
+    assert_eq!(urls.last(), expected.last());
+
+    let written = write(&pages, "https://example.com", 5000, dir.path()).unwrap();
+    assert_eq!(written, [dir.path().join(SITEMAP_FILE)]);
+    let left: Vec<_> = std::fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(left, [SITEMAP_FILE]);
+    let xml = read(SITEMAP_FILE);
+    crate::output::tests::assert_well_formed(&xml);
+    assert_eq!(loc.find_iter(&xml).count(), 2500);
+  }
+}
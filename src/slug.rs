@@ -0,0 +1,189 @@
+//! URL segments for tags and pages. Page slugs come from file names and are
+//! pinned in the slug lock once published, so a change to the slug rules
+//! never moves an existing page. A content type's `permalink` pattern places
+//! each page by its slug and date.
+
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::cache::BuildCache;
+use crate::config::{Config, DotPolicy, SlugConfig};
+use crate::site::{self, BuildOptions};
+
+/// Source path to the slug it is published at, for sources whose slug must
+/// not change.
+pub const SLUG_LOCK_PATH: &str = "md_to_svelte.slugs.json";
+
+/// Nested tags produce nested URL paths: `lang/C++` -> `lang/cplusplus`.
+/// Returns an empty string if any segment has nothing usable in a URL.
+pub fn tag_slug(tag: &str) -> String {
+  let segments: Vec<String> = tag.split('/').map(slugify).collect();
+  if segments.iter().any(String::is_empty) {
+    return String::new();
+  }
+  segments.join("/")
+}
+
+/// Last segment of a tag path, the form shown on pages by default.
+pub fn tag_leaf(tag: &str) -> &str {
+  tag.rsplit('/').next().unwrap_or(tag)
+}
+
+/// Turns a display label into a URL path segment, e.g. `C++` -> `cplusplus`,
+/// `.NET` -> `dotnet`, `a b` -> `a-b`. Returns an empty string when nothing
+/// usable is left.
+pub fn slugify(text: &str) -> String {
+  let mut slug = String::new();
+  for (i, c) in text.trim().chars().enumerate() {
+    match c {
+      '+' => slug.push_str("plus"),
+      '#' => slug.push_str("sharp"),
+      '.' if i == 0 => slug.push_str("dot"),
+      c if c.is_alphanumeric() => slug.extend(c.to_lowercase()),
+      _ => {
+        if !slug.is_empty() && !slug.ends_with('-') {
+          slug.push('-');
+        }
+      }
+    }
+  }
+  slug.trim_end_matches('-').to_string()
+}
+
+/// The slug of a page from the stem of its source file, the file name
+/// without its last extension. Letters, digits and `-_+~` are kept; with
+/// `ascii` set, non-ASCII letters are transliterated where possible. Every
+/// other run of characters becomes one hyphen, none at the start or end.
+/// Dots follow `dots`.
+pub fn page_slug(stem: &str, config: &SlugConfig) -> Result<String, String> {
+  let mut slug = String::new();
+  let separator = |slug: &mut String| {
+    if !slug.is_empty() && !slug.ends_with('-') {
+      slug.push('-');
+    }
+  };
+  for c in stem.chars() {
+    if c == '.' {
+      match config.dots {
+        DotPolicy::Keep => slug.push('.'),
+        DotPolicy::Hyphen => separator(&mut slug),
+        DotPolicy::Error => return Err(format!("file name '{}' has a dot, which slugs.dots = \"error\" does not allow", stem)),
+      }
+    }
+    else if c.is_ascii_alphanumeric() || "-_+~".contains(c) || (c.is_alphanumeric() && !config.ascii) {
+      slug.push(c);
+    }
+    else if let Some(ascii) = transliterate(c).filter(|_| config.ascii) {
+      slug.push_str(&ascii);
+    }
+    else {
+      separator(&mut slug);
+    }
+  }
+  let slug = slug.trim_end_matches('-');
+  if slug.is_empty() {
+    return Err(format!("file name '{}' leaves nothing usable in a URL", stem));
+  }
+  Ok(slug.to_string())
+}
+
+/// Path of a page below its content type's route and output directory:
+/// `pattern` with `{year}`, `{month}`, `{day}` and `{slug}` filled in.
+pub(crate) fn permalink(pattern: &str, slug: &str, date: NaiveDate) -> String {
+  pattern
+    .replace("{year}", &date.format("%Y").to_string())
+    .replace("{month}", &date.format("%m").to_string())
+    .replace("{day}", &date.format("%d").to_string())
+    .replace("{slug}", slug)
+}
+
+/// ASCII spelling of a Latin letter with diacritics, keeping its case.
+fn transliterate(c: char) -> Option<String> {
+  let ascii = match c.to_lowercase().next()? {
+    'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+    'æ' => "ae",
+    'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+    'ď' | 'đ' | 'ð' => "d",
+    'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+    'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+    'ĥ' | 'ħ' => "h",
+    'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+    'ĵ' => "j",
+    'ķ' => "k",
+    'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+    'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+    'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+    'œ' => "oe",
+    'ŕ' | 'ŗ' | 'ř' => "r",
+    'ś' | 'ŝ' | 'ş' | 'š' => "s",
+    'ß' => "ss",
+    'ţ' | 'ť' | 'ŧ' => "t",
+    'þ' => "th",
+    'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+    'ŵ' => "w",
+    'ý' | 'ÿ' | 'ŷ' => "y",
+    'ź' | 'ż' | 'ž' => "z",
+    _ => return None,
+  };
+  Some(if c.is_uppercase() { ascii.to_uppercase() } else { ascii.to_string() })
+}
+
+/// Reads the slug lock, empty when there is none.
+pub fn read_lock() -> Result<BTreeMap<PathBuf, String>, String> {
+  let path = Path::new(SLUG_LOCK_PATH);
+  if !path.exists() {
+    return Ok(BTreeMap::new());
+  }
+  let text = std::fs::read_to_string(path)
+    .map_err(|e| format!("Error reading {}: {}", SLUG_LOCK_PATH, e))?;
+  serde_json::from_str(&text).map_err(|e| format!("Error reading {}: {}", SLUG_LOCK_PATH, e))
+}
+
+/// Pins the slug of every source, drafts included, in the slug lock.
+/// Pins of sources that still exist but failed to parse are kept; those of
+/// sources that are gone are dropped.
+pub fn lock(config: &Config, build_options: &BuildOptions) -> std::io::Result<()> {
+  let variables = site::env_variables(&config.env_allowlist);
+  let mut slugs: BTreeMap<PathBuf, String> = build_options.slug_lock.iter()
+    .filter(|(path, _)| path.is_file())
+    .map(|(path, slug)| (path.clone(), slug.clone()))
+    .collect();
+  for content_type in site::existing_content_types(config) {
+    let options = site::render_options(&content_type, config.content_config(&content_type.name))
+      .variables(variables.clone())
+      .frontmatter_aliases(config.frontmatter_aliases.clone());
+    for entry in site::load_entries(&content_type, &options, build_options, &mut BuildCache::default(), None) {
+      slugs.insert(entry.path, entry.frontmatter.slug);
+    }
+  }
+  std::fs::write(SLUG_LOCK_PATH, serde_json::to_string_pretty(&slugs).unwrap() + "\n")?;
+  info!("Pinned {} slugs in {}", slugs.len(), SLUG_LOCK_PATH);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::entry::Entry;
+  use crate::RenderOptions;
+
+  #[test]
+  fn tag_slugs_are_route_segments() {
+    assert_eq!(tag_slug("C++"), "cplusplus");
+    assert_eq!(tag_slug(".NET"), "dotnet");
+    assert_eq!(tag_slug("C#"), "csharp");
+    assert_eq!(tag_slug("a/b"), "a/b");
+    assert_eq!(tag_slug("Web Dev/Node.js"), "web-dev/node-js");
+    assert_eq!(tag_slug("a/!!"), "");
+  }
+
+  #[test]
+  fn tag_without_a_slug_is_left_unlinked() {
+    let source = "---\ntitle: Post\ndate: 2024-01-05\ntags: [C++, .NET, a/b, '!!']\n---\nText\n";
+    let (entry, warnings) = Entry::parse(source, &RenderOptions::default()).unwrap();
+    let slugs: Vec<&str> = entry.tag_slugs.values().map(String::as_str).collect();
+    assert_eq!(slugs, ["dotnet", "cplusplus", "a/b"]);
+    assert!(warnings.iter().any(|warning| warning.contains("tag '!!' has no characters usable in a URL")), "{:?}", warnings);
+  }
+}
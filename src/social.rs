@@ -0,0 +1,92 @@
+//! Open Graph and Twitter card metadata for a page.
+
+use serde::{Deserialize, Serialize};
+
+use crate::frontmatter::{FrontMatter, Social};
+use crate::markdown::image_path;
+use crate::site::ARTICLE_KIND;
+use crate::text::truncate;
+use crate::RenderOptions;
+
+const OG_TYPES: [&str; 9] = [
+  "article",
+  "website",
+  "profile",
+  "book",
+  "music.song",
+  "music.album",
+  "video.movie",
+  "video.episode",
+  "video.other",
+];
+const TWITTER_CARDS: [&str; 4] = ["summary", "summary_large_image", "app", "player"];
+
+/// Longest derived description, in grapheme clusters; longer first
+/// paragraphs are cut at a word.
+const DESCRIPTION_LENGTH: usize = 160;
+
+/// What a page advertises when shared. Frontmatter `social:` values win over
+/// the values derived from the entry.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SocialMeta {
+  pub og_type: String,
+  pub twitter_card: String,
+  pub title: String,
+  pub description: String,
+  pub image: Option<String>,
+  /// The primary author's URL, or name when there is none.
+  pub author: Option<String>,
+}
+
+/// Rejects values outside what Open Graph and Twitter accept and warns about
+/// keys this tool does not know.
+pub fn validate(social: &Social, warnings: &mut Vec<String>) -> Result<(), String> {
+  if let Some(og_type) = &social.og_type {
+    if !OG_TYPES.contains(&og_type.as_str()) {
+      return Err(format!("social.og_type '{}' is not one of {}", og_type, OG_TYPES.join(", ")));
+    }
+  }
+  if let Some(card) = &social.twitter_card {
+    if !TWITTER_CARDS.contains(&card.as_str()) {
+      return Err(format!("social.twitter_card '{}' is not one of {}", card, TWITTER_CARDS.join(", ")));
+    }
+  }
+  for key in social.unknown.keys() {
+    warnings.push(format!("unknown key social.{} is ignored", key));
+  }
+  Ok(())
+}
+
+pub fn resolve(frontmatter: &FrontMatter, text: &str, assets: &[String], options: &RenderOptions) -> SocialMeta {
+  let no_overrides = Social::default();
+  let social = frontmatter.social.as_ref().unwrap_or(&no_overrides);
+  let image = social.image.as_deref()
+    .map(|image| image_path(image, options.content.root_images))
+    .or(assets.first().cloned())
+    .map(|image| match image.strip_prefix("images/") {
+      Some(file) => format!("{}/{}", options.image_base, file),
+      None => image.to_string(),
+    });
+  let default_card = if image.is_some() { "summary_large_image" } else { "summary" };
+  let default_type = if options.kind == ARTICLE_KIND { "article" } else { "website" };
+  SocialMeta {
+    og_type: social.og_type.clone().unwrap_or_else(|| default_type.to_string()),
+    twitter_card: social.twitter_card.clone().unwrap_or_else(|| default_card.to_string()),
+    title: social.title.as_deref().map(collapse_whitespace).unwrap_or_else(|| frontmatter.title.clone()),
+    description: social.description.as_deref().map(collapse_whitespace).unwrap_or_else(|| describe(text)),
+    image,
+    author: frontmatter.primary_author().map(|author| author.url.clone().unwrap_or_else(|| author.name.clone())),
+  }
+}
+
+/// A frontmatter value on one line. Block scalars (`description: |`) keep
+/// their line breaks and a trailing newline, which share cards don't show.
+fn collapse_whitespace(value: &str) -> String {
+  value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The first paragraph of the plain text, shortened to fit a share card.
+fn describe(text: &str) -> String {
+  let paragraph = text.split("\n\n").map(str::trim).find(|block| !block.is_empty()).unwrap_or("");
+  truncate(&collapse_whitespace(paragraph), DESCRIPTION_LENGTH)
+}
@@ -0,0 +1,17 @@
+// Run by `md_to_svelte verify --compile` with `node --input-type=module`.
+// Compiles the components listed on stdin, as a JSON array of paths, with
+// the site's own Svelte compiler and prints one result per file as JSON.
+import { readFileSync } from 'node:fs';
+
+const { compile } = await import('svelte/compiler');
+const files = JSON.parse(readFileSync(0, 'utf8'));
+const results = files.map((file) => {
+  try {
+    compile(readFileSync(file, 'utf8'), { filename: file });
+    return { file, error: null, line: null, column: null };
+  }
+  catch (e) {
+    return { file, error: e.message, line: e.start?.line ?? null, column: e.start?.column ?? null };
+  }
+});
+process.stdout.write(JSON.stringify(results));
@@ -0,0 +1,117 @@
+//! Counting and shortening prose that mixes scripts. Chinese and Japanese
+//! don't separate words with spaces, so their characters are counted on
+//! their own and text is cut between grapheme clusters, never inside one.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How much prose a document has.
+#[derive(Default, Clone, Copy)]
+pub struct TextCount {
+  /// Words of scripts that separate them with spaces.
+  pub words: usize,
+  /// Han, Hiragana and Katakana characters.
+  pub cjk_characters: usize,
+}
+
+impl TextCount {
+  pub fn add(&mut self, text: &str) {
+    for word in text.unicode_words() {
+      let cjk = word.chars().filter(|c| is_cjk(*c)).count();
+      self.cjk_characters += cjk;
+      if cjk < word.chars().count() {
+        self.words += 1;
+      }
+    }
+  }
+
+  /// Words with each CJK character counted as one.
+  pub fn total(&self) -> usize {
+    self.words + self.cjk_characters
+  }
+
+  /// Minutes to read at the given rates, rounded up.
+  pub fn reading_minutes(&self, words_per_minute: u32, cjk_characters_per_minute: u32) -> usize {
+    let minutes = self.words as f64 / words_per_minute as f64 + self.cjk_characters as f64 / cjk_characters_per_minute as f64;
+    minutes.ceil() as usize
+  }
+}
+
+fn is_cjk(c: char) -> bool {
+  matches!(
+    c,
+    '\u{3040}'..='\u{30ff}' // Hiragana, Katakana
+      | '\u{31f0}'..='\u{31ff}' // Katakana phonetic extensions
+      | '\u{3400}'..='\u{4dbf}' // CJK extension A
+      | '\u{4e00}'..='\u{9fff}' // CJK unified ideographs
+      | '\u{f900}'..='\u{faff}' // CJK compatibility ideographs
+      | '\u{ff66}'..='\u{ff9f}' // Halfwidth Katakana
+      | '\u{20000}'..='\u{2fa1f}' // CJK extensions B to F and supplement
+  )
+}
+
+/// `text` shortened to at most `max` grapheme clusters, ending at the last
+/// word boundary that fits, with `…` appended. Each CJK character is a word
+/// boundary; a single word longer than `max` is cut between graphemes.
+/// Text that fits is returned as is, without the ellipsis.
+pub fn truncate(text: &str, max: usize) -> String {
+  let Some((cut, _)) = text.grapheme_indices(true).nth(max) else {
+    return text.to_string();
+  };
+  let end = text.split_word_bound_indices()
+    .map(|(start, word)| start + word.len())
+    .take_while(|end| *end <= cut)
+    .last()
+    .filter(|end| !text[..*end].trim().is_empty())
+    .unwrap_or(cut);
+  format!("{}…", text[..end].trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn count(text: &str) -> TextCount {
+    let mut count = TextCount::default();
+    count.add(text);
+    count
+  }
+
+  #[test]
+  fn mixed_scripts_count_words_and_characters() {
+    let count = count("Rust は速い language, v1.2 and 中文!");
+    assert_eq!(count.words, 4);
+    assert_eq!(count.cjk_characters, 5);
+    assert_eq!(count.total(), 9);
+  }
+
+  #[test]
+  fn pure_cjk_reading_time_counts_characters() {
+    let count = count(&"日本語の文章です。".repeat(225));
+    assert_eq!(count.words, 0);
+    assert_eq!(count.cjk_characters, 1800);
+    assert_eq!(count.reading_minutes(200, 500), 4);
+    assert_eq!(count.reading_minutes(200, 600), 3);
+  }
+
+  #[test]
+  fn truncation_keeps_emoji_sequences_whole() {
+    let family = "👨\u{200d}👩\u{200d}👧";
+    let text = format!("Hi {}{}{}", family, family, family);
+    // The cut falls after the second sequence, which is one grapheme.
+    assert_eq!(truncate(&text, 5), format!("Hi {}{}…", family, family));
+    assert_eq!(truncate(&text, 6), text);
+    for max in 0..6 {
+      let cut = truncate(&text, max);
+      assert!(!cut.trim_end_matches('…').ends_with('\u{200d}'), "{:?}", cut);
+      assert_eq!(cut.trim_end_matches('…').matches('👧').count(), cut.trim_end_matches('…').matches('👨').count(), "{:?}", cut);
+    }
+  }
+
+  #[test]
+  fn truncation_cuts_at_word_boundaries() {
+    assert_eq!(truncate("short", 10), "short");
+    assert_eq!(truncate("one two three", 9), "one two…");
+    assert_eq!(truncate("日本語の文章です", 4), "日本語の…");
+    assert_eq!(truncate("Supercalifragilistic", 5), "Super…");
+  }
+}
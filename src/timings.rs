@@ -0,0 +1,74 @@
+//! What building each page cost: time per phase, bytes written and, with the
+//! `memory-stats` feature, memory. Read by `--timings`, `slow_file_ms` and
+//! the status line of `--watch`.
+
+use std::time::Duration;
+
+#[derive(Clone, Default)]
+pub struct Timings {
+  /// Reading and parsing the source, or taking it from the cache.
+  pub parse: Duration,
+  /// Generating the component and the files beside it.
+  pub generate: Duration,
+  /// Writing them to disk.
+  pub write: Duration,
+  /// Bytes of the files written for the page.
+  pub output_size: u64,
+  /// Largest growth of the resident memory while the page was built.
+  pub memory: Option<u64>,
+}
+
+impl Timings {
+  pub fn total(&self) -> Duration {
+    self.parse + self.generate + self.write
+  }
+
+  /// The phases with their time, slowest first.
+  pub fn phases(&self) -> Vec<(&'static str, Duration)> {
+    let mut phases = vec![("parse", self.parse), ("generate", self.generate), ("write", self.write)];
+    phases.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+    phases
+  }
+
+  /// Adds the times and sizes of `other`, keeping the larger memory growth.
+  pub fn add(&mut self, other: &Timings) {
+    self.parse += other.parse;
+    self.generate += other.generate;
+    self.write += other.write;
+    self.output_size += other.output_size;
+    self.memory = self.memory.max(other.memory);
+  }
+
+  /// Records the growth of the resident memory since `start`, a value of
+  /// `resident_memory`, if it is the largest so far.
+  pub fn record_memory(&mut self, start: Option<u64>) {
+    if let (Some(start), Some(now)) = (start, resident_memory()) {
+      self.memory = self.memory.max(Some(now.saturating_sub(start)));
+    }
+  }
+}
+
+/// Resident memory of the process in bytes, `None` without the
+/// `memory-stats` feature or on platforms it does not support.
+pub fn resident_memory() -> Option<u64> {
+  #[cfg(feature = "memory-stats")]
+  {
+    memory_stats::memory_stats().map(|stats| stats.physical_mem as u64)
+  }
+  #[cfg(not(feature = "memory-stats"))]
+  {
+    None
+  }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+  format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+pub fn format_size(bytes: u64) -> String {
+  match bytes {
+    0..=1023 => format!("{} B", bytes),
+    1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+    _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+  }
+}
@@ -0,0 +1,152 @@
+//! Checks of the output of the last build that need no rebuild: every page
+//! it recorded is still there, and with `--compile` every generated
+//! component passes the site's own Svelte compiler, run through Node.js.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::cache;
+use crate::output;
+use crate::site::ContentType;
+
+/// File in the cache directory listing the components that compiled.
+pub const COMPILE_CACHE_FILE: &str = "compiled.json";
+/// Package file of the site's Svelte compiler, relative to the site root.
+const SVELTE_PACKAGE: &str = "node_modules/svelte/package.json";
+const COMPILE_SCRIPT: &str = include_str!("static/compile.mjs");
+
+#[derive(Deserialize)]
+struct CompileResult {
+  file: PathBuf,
+  error: Option<String>,
+  line: Option<u64>,
+  column: Option<u64>,
+}
+
+/// Reports pages of `content_types` recorded in the manifest that are
+/// missing or no longer generated, then with `compile` compiles the
+/// generated components.
+/// Components that compiled are remembered in `cache_dir` by a hash of the
+/// compiler version and their text, so later runs only compile those that
+/// changed.
+pub fn verify(content_types: &[ContentType], compile: bool, cache_dir: Option<&Path>) -> Result<(), String> {
+  let manifest = output::read_manifest()?;
+  // Source of the page in each page directory.
+  let mut sources = BTreeMap::new();
+  for entry in &manifest {
+    let Some(content_type) = content_types.iter().find(|content_type| content_type.name == entry.content_type) else {
+      continue;
+    };
+    let page_dir = Path::new(&content_type.output_dir).join(&entry.permalink);
+    let page = page_dir.join(output::PAGE_FILE);
+    if !output::is_generated(&page) {
+      error!("Error: {}, the page of {}, is missing or no longer generated", page.display(), entry.source.display());
+    }
+    sources.insert(page_dir, entry.source.clone());
+  }
+  info!("Checked {} pages", manifest.len());
+  if compile {
+    compile_components(content_types, &sources, cache_dir)?;
+  }
+  Ok(())
+}
+
+/// Compiles every generated component not known to compile and reports each
+/// error with the markdown source the component was generated from.
+fn compile_components(content_types: &[ContentType], sources: &BTreeMap<PathBuf, PathBuf>, cache_dir: Option<&Path>) -> Result<(), String> {
+  let version = svelte_version()?;
+  let components: Vec<PathBuf> = content_types
+    .iter()
+    .flat_map(|content_type| output::generated_files(Path::new(&content_type.output_dir)))
+    .filter(|path| path.extension().is_some_and(|extension| extension == "svelte"))
+    .collect();
+  let mut hashes = BTreeMap::new();
+  for path in &components {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    hashes.insert(path.clone(), cache::hash(&format!("{}\0{}", version, text)));
+  }
+  let mut compiled = cache_dir.map(read_compiled).unwrap_or_default();
+  let pending: Vec<&PathBuf> = components.iter().filter(|path| !compiled.contains(&hashes[*path])).collect();
+  let results = if pending.is_empty() { Vec::new() } else { run_compiler(&pending)? };
+
+  for result in &results {
+    let Some(message) = &result.error else {
+      if let Some(hash) = hashes.get(&result.file) {
+        compiled.insert(hash.clone());
+      }
+      continue;
+    };
+    let mut location = result.file.display().to_string();
+    if let Some(line) = result.line {
+      location = format!("{}:{}:{}", location, line, result.column.unwrap_or(0));
+    }
+    // Reader pages sit one directory below the page directory.
+    match result.file.ancestors().skip(1).take(2).find_map(|dir| sources.get(dir)) {
+      Some(source) => error!("Error in {} (generated from {}): {}", location, source.display(), message),
+      None => error!("Error in {}: {}", location, message),
+    }
+  }
+  let current: BTreeSet<&String> = hashes.values().collect();
+  compiled.retain(|hash| current.contains(hash));
+  if let Some(dir) = cache_dir {
+    write_compiled(dir, &compiled).map_err(|e| format!("Error writing {}: {}", dir.join(COMPILE_CACHE_FILE).display(), e))?;
+  }
+  info!(
+    "Compiled {} components with Svelte {}; {} unchanged since they last compiled",
+    results.len(),
+    version,
+    components.len() - pending.len()
+  );
+  Ok(())
+}
+
+/// Version of the Svelte compiler installed in the site's `node_modules`.
+fn svelte_version() -> Result<String, String> {
+  let text = std::fs::read_to_string(SVELTE_PACKAGE).map_err(|_| {
+    format!("verify --compile uses the site's Svelte compiler, but there is no {}; run npm install first", SVELTE_PACKAGE)
+  })?;
+  let package: serde_json::Value = serde_json::from_str(&text).map_err(|e| format!("Error reading {}: {}", SVELTE_PACKAGE, e))?;
+  package["version"].as_str()
+    .map(String::from)
+    .ok_or_else(|| format!("Error reading {}: no version", SVELTE_PACKAGE))
+}
+
+/// Compiles `files` in one Node.js process.
+fn run_compiler(files: &[&PathBuf]) -> Result<Vec<CompileResult>, String> {
+  let mut child = Command::new("node")
+    .args(["--input-type=module", "--eval", COMPILE_SCRIPT])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|e| match e.kind() {
+      ErrorKind::NotFound => "verify --compile needs Node.js, but no `node` was found on PATH; install Node.js or run verify without --compile".to_string(),
+      _ => format!("Error running node: {}", e),
+    })?;
+  // The script reads all of stdin before writing anything.
+  child.stdin.take().unwrap()
+    .write_all(serde_json::to_string(files).unwrap().as_bytes())
+    .map_err(|e| format!("Error running node: {}", e))?;
+  let output = child.wait_with_output().map_err(|e| format!("Error running node: {}", e))?;
+  if !output.status.success() {
+    return Err(format!("Error running the Svelte compiler: {}", String::from_utf8_lossy(&output.stderr).trim()));
+  }
+  serde_json::from_slice(&output.stdout).map_err(|e| format!("Error reading the Svelte compiler's output: {}", e))
+}
+
+/// Hashes of the components that compiled, empty when the file is missing or
+/// unreadable.
+fn read_compiled(dir: &Path) -> BTreeSet<String> {
+  std::fs::read_to_string(dir.join(COMPILE_CACHE_FILE))
+    .ok()
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default()
+}
+
+fn write_compiled(dir: &Path, compiled: &BTreeSet<String>) -> std::io::Result<()> {
+  std::fs::create_dir_all(dir)?;
+  std::fs::write(dir.join(COMPILE_CACHE_FILE), serde_json::to_string(compiled).unwrap())
+}
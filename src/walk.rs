@@ -0,0 +1,60 @@
+//! Lists source files the way git would see them.
+
+use ignore::gitignore::GitignoreBuilder;
+use ignore::WalkBuilder;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::log::{self, Level};
+
+/// Ignore file with `.gitignore` syntax that only this tool reads.
+pub const IGNORE_FILE: &str = ".mdsvelteignore";
+
+/// Every file below `dir` not excluded by a `.mdsvelteignore` or, when
+/// `respect_gitignore` is set, by git's ignore rules. Ignored files are
+/// listed at debug level.
+pub fn source_files(dir: &Path, respect_gitignore: bool) -> Vec<PathBuf> {
+  let files: Vec<PathBuf> = WalkBuilder::new(dir)
+    .hidden(false)
+    .ignore(false)
+    .git_ignore(respect_gitignore)
+    .git_exclude(respect_gitignore)
+    .git_global(respect_gitignore)
+    .require_git(false)
+    .add_custom_ignore_filename(IGNORE_FILE)
+    .build()
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| entry.file_type().is_some_and(|ty| ty.is_file()))
+    .filter(|entry| entry.file_name() != IGNORE_FILE)
+    .map(|entry| entry.into_path())
+    .collect();
+
+  if log::enabled(Level::Debug) {
+    let kept: BTreeSet<&PathBuf> = files.iter().collect();
+    let all_files = WalkBuilder::new(dir)
+      .standard_filters(false)
+      .build()
+      .filter_map(|entry| entry.ok())
+      .filter(|entry| entry.file_type().is_some_and(|ty| ty.is_file()));
+    for entry in all_files {
+      if entry.file_name() != IGNORE_FILE && !kept.contains(&entry.path().to_path_buf()) {
+        debug!("Ignoring {}", entry.path().display());
+      }
+    }
+  }
+  files
+}
+
+/// Whether `path`, a file below `dir`, matches `pattern`, written in
+/// `.gitignore` syntax relative to `dir` like the rules of `.mdsvelteignore`.
+pub fn matches_pattern(dir: &Path, pattern: &str, path: &Path) -> Result<bool, String> {
+  let mut builder = GitignoreBuilder::new(dir);
+  builder.add_line(None, pattern).map_err(|e| e.to_string())?;
+  let matcher = builder.build().map_err(|e| e.to_string())?;
+  Ok(matcher.matched_path_or_any_parents(path, false).is_ignore())
+}
+
+/// Whether `path` ends in one of `extensions`, given without the dot.
+pub fn has_extension(path: &Path, extensions: &[String]) -> bool {
+  path.extension().is_some_and(|ext| extensions.iter().any(|known| ext == known.as_str()))
+}
@@ -0,0 +1,134 @@
+//! Rebuilds the site whenever a source changes, reusing the parsed entries of
+//! unchanged sources.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config::Config;
+use crate::images;
+use crate::log;
+use crate::cache::BuildCache;
+use crate::site::{self, BuildOptions};
+use crate::timings::{self, Timings};
+use crate::walk;
+
+/// How often the content directories are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Rebuilds of more changed files than this get one summarized status line
+/// instead of one per file.
+const STATUS_LINES: usize = 3;
+
+/// Builds once, then rebuilds on every change to the content directories
+/// until the process is stopped, printing a status line of what each
+/// changed page cost. Entering `r` forces a full rebuild that parses every
+/// source again.
+pub fn watch(config: &Config, build_options: &BuildOptions) -> ! {
+  let mut cache = build_options.cache_dir.as_deref().map(BuildCache::load).unwrap_or_default();
+  site::rebuild(config, build_options, &mut cache);
+
+  let resync = Arc::new(AtomicBool::new(false));
+  let stdin_resync = Arc::clone(&resync);
+  std::thread::spawn(move || {
+    for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+      if line.trim() == "r" {
+        stdin_resync.store(true, Ordering::Relaxed);
+      }
+    }
+  });
+  info!("Watching for changes (enter r to rebuild everything)");
+
+  let mut stamps = source_stamps(config, build_options);
+  loop {
+    std::thread::sleep(POLL_INTERVAL);
+    let full = resync.swap(false, Ordering::Relaxed);
+    let current = source_stamps(config, build_options);
+    if !full && current == stamps {
+      continue;
+    }
+    let changed: Vec<PathBuf> = if full {
+      current.keys().cloned().collect()
+    }
+    else {
+      current.iter()
+        .filter(|(path, modified)| stamps.get(*path) != Some(*modified))
+        .map(|(path, _)| path.clone())
+        .chain(stamps.keys().filter(|path| !current.contains_key(*path)).cloned())
+        .collect()
+    };
+    stamps = current;
+    if full {
+      cache.clear();
+    }
+    log::reset();
+    let started = Instant::now();
+    let pages = site::rebuild(config, build_options, &mut cache);
+    if images::interrupted() {
+      std::process::exit(130);
+    }
+    report_status(&changed, &pages, started.elapsed());
+  }
+}
+
+/// Prints what rebuilding each changed page cost, or a single summarized
+/// line when more than `STATUS_LINES` files changed at once.
+fn report_status(changed: &[PathBuf], pages: &BTreeMap<PathBuf, Timings>, elapsed: Duration) {
+  let rebuilt: Vec<(&PathBuf, &Timings)> = changed.iter().filter_map(|path| Some((path, pages.get(path)?))).collect();
+  // Images, removed sources and sources that failed to parse.
+  if rebuilt.is_empty() {
+    info!("Rebuilt after {} changed files in {}", changed.len(), timings::format_duration(elapsed));
+    return;
+  }
+  if changed.len() > STATUS_LINES {
+    let mut total = Timings::default();
+    for (_, page) in &rebuilt {
+      total.add(page);
+    }
+    info!(
+      "Rebuilt after {} changed files in {}: {} pages, {}",
+      changed.len(),
+      timings::format_duration(elapsed),
+      rebuilt.len(),
+      status(&total)
+    );
+    return;
+  }
+  for (path, page) in rebuilt {
+    info!("Rebuilt {}: {}", path.display(), status(page));
+  }
+}
+
+/// Total time, the two slowest phases, output size and memory growth.
+fn status(page: &Timings) -> String {
+  let phases: Vec<String> = page.phases()
+    .into_iter()
+    .take(2)
+    .map(|(phase, time)| format!("{} {}", phase, timings::format_duration(time)))
+    .collect();
+  let mut status = format!(
+    "{} ({}), {} written",
+    timings::format_duration(page.total()),
+    phases.join(", "),
+    timings::format_size(page.output_size)
+  );
+  if let Some(memory) = page.memory {
+    status.push_str(&format!(", peak memory +{}", timings::format_size(memory)));
+  }
+  status
+}
+
+/// Modification time of every file in the content directories, images
+/// included, so additions, edits, deletions and renames all show up.
+fn source_stamps(config: &Config, build_options: &BuildOptions) -> BTreeMap<PathBuf, Option<SystemTime>> {
+  config.content_types()
+    .iter()
+    .flat_map(|content_type| walk::source_files(Path::new(&content_type.input_dir), build_options.respect_gitignore))
+    .map(|path| {
+      let modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+      (path, modified)
+    })
+    .collect()
+}
@@ -0,0 +1,639 @@
+//! Builds of small sites in temporary directories, run through the binary
+//! as a user would.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+/// Runs the tool in `dir` with `args`.
+fn run(dir: &Path, args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_md_to_svelte"))
+    .args(args)
+    .current_dir(dir)
+    .output()
+    .unwrap()
+}
+
+/// Writes `text` to `path` below `dir`, creating its directories.
+fn write(dir: &Path, path: &str, text: &str) {
+  let path = dir.join(path);
+  std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+  std::fs::write(path, text).unwrap();
+}
+
+fn read(dir: &Path, path: &str) -> String {
+  std::fs::read_to_string(dir.join(path)).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+  String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn build_without_any_input_directory_fails() {
+  let dir = tempfile::tempdir().unwrap();
+  let output = run(dir.path(), &[]);
+  assert!(!output.status.success());
+  assert!(stderr(&output).contains("no input directory exists (data/articles, data/projects)"), "{}", stderr(&output));
+}
+
+#[test]
+fn nested_permalinks_import_from_the_output_directory() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "md_to_svelte.toml", "authors = \"normalized\"\n\n[content.articles]\npermalink = \"{year}/{month}/{slug}\"\ncss_split = true\n");
+  write(dir.path(), "data/articles/post.md", "---\ntitle: Post\ndate: 2024-06-03\ntags: []\nauthors:\n  - name: Ada\n---\nText\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+
+  let page_dir = dir.path().join("src/routes/articles/2024/06/post");
+  let component = read(&page_dir, "+page.svelte");
+  assert!(component.contains("import { authors as authorIndex } from '../../../authors';"), "{}", component);
+  assert!(component.contains("import '../../../md_to_svelte.css';"), "{}", component);
+  assert!(page_dir.join("../../../authors.ts").is_file());
+  assert!(page_dir.join("../../../md_to_svelte.css").is_file());
+}
+
+#[test]
+fn pruned_build_leaves_user_files_untouched() {
+  let dir = tempfile::tempdir().unwrap();
+  let user_files = [
+    ("src/routes/articles/+page.svelte", "<script>\n  import { articles } from './articleData';\n</script>\n"),
+    ("src/routes/articles/+layout.svelte", "<slot />\n"),
+    ("src/routes/articles/special/+page.svelte", "<h1>Special</h1>\n"),
+    // Looks like the output of versions before the generated-file header.
+    ("src/routes/articles/featured.ts", "export const articles = [\n  'post',\n];\n"),
+    ("src/routes/articles/old/+page.svelte", "<script>\n    import { onMount } from 'svelte';\n    import Prism from 'prismjs';\n</script>\n"),
+  ];
+  for (path, text) in user_files {
+    write(dir.path(), path, text);
+  }
+  write(dir.path(), "data/articles/post.md", "---\ntitle: Post\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  write(dir.path(), "data/articles/gone.md", "---\ntitle: Gone\ndate: 2024-06-04\ntags: []\n---\nText\n");
+  let output = run(dir.path(), &["--prune"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  std::fs::remove_file(dir.path().join("data/articles/gone.md")).unwrap();
+  let output = run(dir.path(), &["--prune"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+
+  assert!(dir.path().join("src/routes/articles/post/+page.svelte").is_file());
+  assert!(!dir.path().join("src/routes/articles/gone").exists());
+  for (path, text) in user_files {
+    assert_eq!(read(dir.path(), path), text, "{}", path);
+  }
+}
+
+#[test]
+fn hand_written_data_file_is_not_overwritten() {
+  let dir = tempfile::tempdir().unwrap();
+  let data = "export const articles = [\n  { slug: 'mine' },\n];\n";
+  write(dir.path(), "src/routes/articles/articleData.ts", data);
+  write(dir.path(), "data/articles/post.md", "---\ntitle: Post\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  let output = run(dir.path(), &["--prune"]);
+  assert!(!output.status.success());
+  assert!(stderr(&output).contains("refusing to overwrite"), "{}", stderr(&output));
+  assert_eq!(read(dir.path(), "src/routes/articles/articleData.ts"), data);
+}
+
+#[test]
+fn concurrent_runs_are_locked_out() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "data/articles/post.md", "---\ntitle: Post\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  let mut watcher = Command::new(env!("CARGO_BIN_EXE_md_to_svelte"))
+    .arg("--watch")
+    .current_dir(dir.path())
+    .stdin(Stdio::piped())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+  let lock = dir.path().join(".md_to_svelte.lock");
+  let started = Instant::now();
+  while std::fs::read_to_string(&lock).map_or(true, |pid| pid.is_empty()) {
+    assert!(started.elapsed() < Duration::from_secs(10), "the watcher never took the lock");
+    std::thread::sleep(Duration::from_millis(20));
+  }
+
+  let output = run(dir.path(), &[]);
+  assert!(!output.status.success());
+  assert!(stderr(&output).contains(&format!("Another md_to_svelte run (process {}) holds", watcher.id())), "{}", stderr(&output));
+  let started = Instant::now();
+  let output = run(dir.path(), &["--wait-for-lock", "1"]);
+  assert!(!output.status.success());
+  assert!(started.elapsed() >= Duration::from_secs(1));
+
+  // Killed, the watcher cannot release the lock itself; the OS does.
+  watcher.kill().unwrap();
+  watcher.wait().unwrap();
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn export_lists_every_published_entry_once() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "data/articles/first.md", "---\ntitle: First\ndate: 2024-06-03\ntags: [rust]\n---\nSee ![chart](images/chart.png).\n");
+  write(dir.path(), "data/articles/images/chart.png", "png");
+  write(dir.path(), "data/articles/second.md", "---\ntitle: Second\ndate: 2024-06-04T09:30:00+02:00\ntags: []\n---\n*Text* &amp; more\n");
+  write(dir.path(), "data/articles/draft.md", "---\ntitle: Draft\ndate: 2024-06-05\ntags: []\ndraft: true\n---\nText\n");
+  write(dir.path(), "data/articles/hidden.md", "---\ntitle: Hidden\ndate: 2024-06-06\ntags: []\npublished: false\n---\nText\n");
+  write(dir.path(), "data/projects/tool.md", "---\ntitle: Tool\ndate: 2023-01-01\ntags: []\n---\nText\n");
+  let output = run(dir.path(), &["export", "out"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+
+  let index: serde_json::Value = serde_json::from_str(&read(dir.path(), "out/index.json")).unwrap();
+  assert_eq!(index["format"], "md_to_svelte-export");
+  let mut paths: Vec<&str> = index["entries"].as_array().unwrap().iter()
+    .map(|entry| entry["path"].as_str().unwrap())
+    .collect();
+  paths.sort();
+  assert_eq!(paths, ["articles/first", "articles/second", "projects/tool"]);
+
+  let out = dir.path().join("out");
+  for path in paths {
+    let frontmatter: serde_json::Value = serde_json::from_str(&read(&out, &format!("{}/frontmatter.json", path))).unwrap();
+    let entry = index["entries"].as_array().unwrap().iter().find(|entry| entry["path"] == path).unwrap();
+    assert_eq!(frontmatter["title"], entry["title"]);
+    assert_eq!(frontmatter["slug"], entry["slug"]);
+  }
+  let second: serde_json::Value = serde_json::from_str(&read(&out, "articles/second/frontmatter.json")).unwrap();
+  assert_eq!(second["date"], "2024-06-04T09:30:00+02:00");
+  assert_eq!(read(&out, "articles/second/content.txt").trim(), "Text & more");
+  assert!(read(&out, "articles/first/content.html").contains("src=\"assets/chart.png\""));
+  assert_eq!(read(&out, "articles/first/assets/chart.png"), "png");
+  assert_eq!(read(&out, "articles/first/assets.json").split_whitespace().collect::<String>(), "[\"assets/chart.png\"]");
+}
+
+#[test]
+fn custom_image_url_prefix_moves_urls_and_copies() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "md_to_svelte.toml", "[content.articles]\nimage_url_prefix = \"/p/img/\"\n");
+  write(dir.path(), "data/articles/post.md", "---\ntitle: Post\ndate: 2024-06-03\ntags: []\n---\n![chart](images/chart.png)\n");
+  write(dir.path(), "data/articles/images/chart.png", "png");
+  write(dir.path(), "data/projects/tool.md", "---\ntitle: Tool\ndate: 2023-01-01\ntags: []\n---\n![shot](images/shot.png)\n");
+  write(dir.path(), "data/projects/images/shot.png", "png");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+
+  assert!(read(dir.path(), "src/routes/articles/post/+page.svelte").contains(r#"src=\"/p/img/chart.png\""#));
+  assert_eq!(read(dir.path(), "static/p/img/chart.png"), "png");
+  assert!(!dir.path().join("static/images/articles").exists());
+  // Projects keep the default prefix.
+  assert!(read(dir.path(), "src/routes/projects/tool/+page.svelte").contains(r#"src=\"/images/projects/shot.png\""#));
+  assert_eq!(read(dir.path(), "static/images/projects/shot.png"), "png");
+
+  write(dir.path(), "md_to_svelte.toml", "[content.articles]\nimage_url_prefix = \"/p/../img\"\n");
+  let output = run(dir.path(), &[]);
+  assert!(!output.status.success());
+  assert!(stderr(&output).contains("content.articles.image_url_prefix: '/p/../img' must be an absolute path without '..'"), "{}", stderr(&output));
+}
+
+const SUBCOMMANDS: [&str; 7] = ["export", "doctor", "list", "completions", "config", "slugs", "verify"];
+
+#[test]
+fn completions_cover_every_subcommand() {
+  let dir = tempfile::tempdir().unwrap();
+  for shell in ["bash", "zsh", "fish", "powershell", "elvish"] {
+    let output = run(dir.path(), &["completions", shell]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let script = String::from_utf8(output.stdout).unwrap();
+    for subcommand in SUBCOMMANDS {
+      assert!(script.contains(subcommand), "{} completions lack {}", shell, subcommand);
+    }
+  }
+}
+
+#[test]
+fn man_pages_cover_every_subcommand() {
+  let dir = tempfile::tempdir().unwrap();
+  // Works without a config or any content.
+  let output = run(dir.path(), &["--generate-man", "man"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  let main_page = read(dir.path(), "man/md_to_svelte.1");
+  for subcommand in SUBCOMMANDS {
+    assert!(main_page.contains(subcommand), "md_to_svelte.1 lacks {}", subcommand);
+    assert!(read(dir.path(), &format!("man/md_to_svelte-{}.1", subcommand)).contains(".TH"), "{}", subcommand);
+  }
+}
+
+#[test]
+fn non_utf8_source_is_skipped_unless_lossy() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "data/articles/good.md", "---\ntitle: Good\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  // Latin-1 `é`, as an editor with the wrong encoding would save it.
+  std::fs::write(dir.path().join("data/articles/latin1.md"), b"---\ntitle: Latin\ndate: 2024-06-04\ntags: []\n---\nCaf\xe9\n").unwrap();
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(stderr(&output).contains("latin1.md: skipped, not valid UTF-8"), "{}", stderr(&output));
+  assert!(dir.path().join("src/routes/articles/good/+page.svelte").is_file());
+  assert!(!dir.path().join("src/routes/articles/latin1").exists());
+
+  let output = run(dir.path(), &["--lossy-utf8"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(stderr(&output).contains("latin1.md: not valid UTF-8, invalid bytes were replaced"), "{}", stderr(&output));
+  assert!(read(dir.path(), "src/routes/articles/latin1/+page.svelte").contains("Caf\u{fffd}"));
+}
+
+#[test]
+fn markdown_extensions_choose_the_sources() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "data/articles/long.markdown", "---\ntitle: Long\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  write(dir.path(), "data/articles/notes.txt", "---\ntitle: Notes\ndate: 2024-06-04\ntags: []\n---\nText\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(dir.path().join("src/routes/articles/long/+page.svelte").is_file());
+  assert!(!dir.path().join("src/routes/articles/notes").exists());
+
+  write(dir.path(), "md_to_svelte.toml", "markdown_extensions = [\"md\", \"txt\"]\n");
+  let output = run(dir.path(), &["--prune"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(dir.path().join("src/routes/articles/notes/+page.svelte").is_file());
+  assert!(!dir.path().join("src/routes/articles/long").exists());
+}
+
+#[test]
+fn routes_claiming_the_same_urls_are_reported() {
+  let dir = tempfile::tempdir().unwrap();
+  for route in ["[param]", "[...rest]", "(group)/post"] {
+    write(dir.path(), &format!("src/routes/articles/{}/+page.svelte", route), "<h1>Mine</h1>\n");
+  }
+  write(dir.path(), "data/articles/post.md", "---\ntitle: Post\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  write(dir.path(), "data/articles/[slug].md", "---\ntitle: Bracketed\ndate: 2024-06-04\ntags: []\n---\nText\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  let stderr = stderr(&output);
+  for route in ["[param]", "[...rest]"] {
+    assert!(stderr.contains(&format!("src/routes/articles/{} is a dynamic route overlapping the generated pages", route)), "{}", stderr);
+  }
+  assert!(stderr.contains("src/routes/articles/(group)/post conflicts with the generated src/routes/articles/post"), "{}", stderr);
+  // A source named like a dynamic segment is published under a plain slug.
+  assert!(stderr.contains("file name '[slug]' is published as 'slug'"), "{}", stderr);
+  assert!(dir.path().join("src/routes/articles/slug/+page.svelte").is_file());
+  for route in ["[param]", "[...rest]", "(group)/post"] {
+    assert_eq!(read(dir.path(), &format!("src/routes/articles/{}/+page.svelte", route)), "<h1>Mine</h1>\n");
+  }
+}
+
+#[test]
+fn output_outside_the_project_is_refused() {
+  let dir = tempfile::tempdir().unwrap();
+  let site = dir.path().join("site");
+  write(&site, "data/articles/post.md", "---\ntitle: Post\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  write(&site, "md_to_svelte.toml", "static_root = \"../../etc\"\n");
+  let output = run(&site, &[]);
+  assert!(!output.status.success());
+  assert!(stderr(&output).contains("Refusing to write outside the output root"), "{}", stderr(&output));
+  assert!(stderr(&output).contains("static_root: ../../etc resolves to"), "{}", stderr(&output));
+  assert!(!site.join("src").exists());
+
+  #[cfg(unix)]
+  {
+    std::os::unix::fs::symlink(dir.path(), site.join("escape")).unwrap();
+    write(&site, "md_to_svelte.toml", "static_root = \"escape/static\"\n");
+    let output = run(&site, &[]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("static_root: escape/static resolves to"), "{}", stderr(&output));
+  }
+
+  // A wider root given on the command line allows it.
+  write(&site, "md_to_svelte.toml", "static_root = \"../static\"\n");
+  let output = run(&site, &["--output-root", ".."]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(site.join("src/routes/articles/post/+page.svelte").is_file());
+}
+
+#[test]
+fn slug_collisions_follow_the_strategy() {
+  let sources = |dir: &Path| {
+    write(dir, "data/articles/a/post.md", "---\ntitle: First\ndate: 2024-06-03\ntags: []\n---\nText\n");
+    write(dir, "data/articles/b/post.md", "---\ntitle: Second\ndate: 2024-06-04\ntags: []\n---\nText\n");
+    write(dir, "data/articles/post-2.md", "---\ntitle: Third\ndate: 2024-06-05\ntags: []\n---\nText\n");
+  };
+  let title = |dir: &Path, slug: &str| {
+    let component = read(dir, &format!("src/routes/articles/{}/+page.svelte", slug));
+    component.split("export const title = '").nth(1).unwrap().split('\'').next().unwrap().to_string()
+  };
+
+  let dir = tempfile::tempdir().unwrap();
+  sources(dir.path());
+  let output = run(dir.path(), &[]);
+  assert!(!output.status.success());
+  assert!(stderr(&output).contains("Error: data/articles/b/post.md has the same slug 'post' as data/articles/a/post.md"), "{}", stderr(&output));
+
+  let dir = tempfile::tempdir().unwrap();
+  sources(dir.path());
+  write(dir.path(), "md_to_svelte.toml", "on_slug_collision = \"warn-suffix\"\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  // post-2 is taken by post-2.md, so b/post.md moves on to post-3.
+  assert!(stderr(&output).contains("data/articles/b/post.md has the same slug 'post' as data/articles/a/post.md; using 'post-3'"), "{}", stderr(&output));
+  assert_eq!(title(dir.path(), "post"), "First");
+  assert_eq!(title(dir.path(), "post-2"), "Third");
+  assert_eq!(title(dir.path(), "post-3"), "Second");
+
+  let dir = tempfile::tempdir().unwrap();
+  sources(dir.path());
+  write(dir.path(), "md_to_svelte.toml", "on_slug_collision = \"warn-skip\"\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(stderr(&output).contains("data/articles/b/post.md has the same slug 'post' as data/articles/a/post.md; skipped"), "{}", stderr(&output));
+  assert_eq!(title(dir.path(), "post"), "First");
+  assert!(!dir.path().join("src/routes/articles/post-3").exists());
+}
+
+/// Every file below `dir`, recursively.
+fn files(dir: &Path) -> Vec<PathBuf> {
+  let mut found = Vec::new();
+  for entry in std::fs::read_dir(dir).unwrap() {
+    let path = entry.unwrap().path();
+    if path.is_dir() {
+      found.extend(files(&path));
+    }
+    else {
+      found.push(path);
+    }
+  }
+  found
+}
+
+#[test]
+fn every_emitted_url_follows_the_url_style() {
+  // Internal URLs start with the site URL, a quoted string or a markdown-link
+  // target; file paths such as data/articles/first.md do not.
+  let internal = regex::Regex::new(r#"(?:https://example\.com|["'(])(/articles/(?:first|second)/?)[#"'<)\\]"#).unwrap();
+  for (style, slash) in [("no-slash", false), ("slash", true)] {
+    let dir = tempfile::tempdir().unwrap();
+    write(dir.path(), "md_to_svelte.toml", &format!(
+      "url_style = \"{}\"\nroute_options = true\nsite_url = \"https://example.com\"\n\n[sitemap]\nenabled = true\n\n[feed]\nenabled = true\n",
+      style
+    ));
+    write(dir.path(), "data/articles/first.md", "---\ntitle: First\ndate: 2024-06-03\ntags: [rust]\nrelated: [second]\n---\nSee [second](/articles/second), [usage](/articles/second/#usage) and [elsewhere](https://other.org/articles/second/).\n");
+    write(dir.path(), "data/articles/second.md", "---\ntitle: Second\ndate: 2024-06-04\ntags: [rust]\n---\n## Usage\n\nBack to [first](/articles/first/).\n");
+    let output = run(dir.path(), &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let mut checked = 0;
+    for root in ["src/routes", "static", ".md_to_svelte"] {
+      for path in files(&dir.path().join(root)) {
+        let text = std::fs::read_to_string(&path).unwrap();
+        for captures in internal.captures_iter(&text) {
+          let url = &captures[1];
+          assert_eq!(url.ends_with('/'), slash, "{} in {} with url_style {}", url, path.display(), style);
+          checked += 1;
+        }
+      }
+    }
+    assert!(checked >= 10, "only {} URLs found", checked);
+    assert!(read(dir.path(), "src/routes/articles/first/+page.svelte").contains("https://other.org/articles/second/"));
+    let trailing_slash = if slash { "always" } else { "never" };
+    assert!(read(dir.path(), "src/routes/articles/first/+page.ts").contains(&format!("export const trailingSlash = '{}';", trailing_slash)));
+  }
+}
+
+/// Contents of every file the build writes, by path.
+fn snapshot(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+  let mut snapshot = Vec::new();
+  for root in ["src", "static", ".md_to_svelte"] {
+    for path in files(&dir.join(root)) {
+      let bytes = std::fs::read(&path).unwrap();
+      snapshot.push((path, bytes));
+    }
+  }
+  snapshot.sort();
+  snapshot
+}
+
+#[test]
+fn rebuilding_gives_identical_output() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "md_to_svelte.toml", "site_url = \"https://example.com\"\n\n[sitemap]\nenabled = true\n\n[feed]\nenabled = true\n");
+  write(dir.path(), "data/articles/first.md", "---\ntitle: First\ndate: 2024-06-03\ntags: [rust, web]\n---\n# Intro\n\nText with `code`.\n\n```rust\nfn main() {}\n```\n");
+  write(dir.path(), "data/articles/second.md", "---\ntitle: Second\ndate: 2024-06-04T09:30:00+02:00\ntags: [rust]\nauthors: [Ada, Grace]\n---\nText\n");
+  write(dir.path(), "data/projects/tool.md", "---\ntitle: Tool\ndate: 2023-01-01\ntags: []\n---\nText\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  let first = snapshot(dir.path());
+  let modified: Vec<_> = first.iter().map(|(path, _)| std::fs::metadata(path).unwrap().modified().unwrap()).collect();
+
+  let output = run(dir.path(), &["--no-cache"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(first == snapshot(dir.path()), "a rebuild changed the output");
+  // Build state below .md_to_svelte is saved every run; the site is not.
+  for ((path, _), modified) in first.iter().zip(modified).filter(|((path, _), _)| !path.starts_with(dir.path().join(".md_to_svelte"))) {
+    assert_eq!(std::fs::metadata(path).unwrap().modified().unwrap(), modified, "{} was rewritten", path.display());
+  }
+
+  #[cfg(unix)]
+  {
+    let format = ["--format-command", "sed -i -e s/Text/TEXT/"];
+    let output = run(dir.path(), &format);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let formatted = snapshot(dir.path());
+    assert!(read(dir.path(), "src/routes/articles/first/+page.svelte").contains("TEXT with"));
+    let output = run(dir.path(), &format);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(formatted == snapshot(dir.path()), "a formatted rebuild changed the output");
+  }
+}
+
+#[test]
+fn reader_pages_have_math_as_mathml() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "md_to_svelte.toml", "[content.articles]\nreader = true\n");
+  write(dir.path(), "data/articles/plain.md", "---\ntitle: Plain\ndate: 2024-06-03\ntags: []\n---\nInline $\\alpha_1$ and\n\n$$\\sum_{i=0}^n i$$\n");
+  write(dir.path(), "data/articles/odd.md", "---\ntitle: Odd\ndate: 2024-06-04\ntags: []\n---\nInline $\\overbrace{x}$.\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+
+  let reader = read(dir.path(), "src/routes/articles/plain/reader/+page.svelte");
+  assert!(reader.contains("<math><msub><mi>α</mi><mn>1</mn></msub></math>"), "{}", reader);
+  assert!(reader.contains("<math display=\\\"block\\\"><mrow><munderover><mo>∑</mo>"), "{}", reader);
+  assert!(!reader.contains("\\\\("), "{}", reader);
+  assert!(!dir.path().join("src/routes/articles/odd/reader").exists());
+  assert!(dir.path().join("src/routes/articles/odd/+page.svelte").exists());
+  assert!(stderr(&output).contains("[reader-math] data/articles/odd.md: \\overbrace{x}: unsupported command \\overbrace; no reader page written"), "{}", stderr(&output));
+}
+
+#[test]
+fn every_external_origin_is_recorded() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "md_to_svelte.toml", "site_url = \"https://example.com\"\n\n[content.projects]\nreader = true\ncontent_mode = \"file\"\n");
+  write(dir.path(), "data/articles/math.md", "---\ntitle: Math\ndate: 2024-06-03\ntags: []\n---\nInline $x^2$ and a [link](https://example.org/page).\n");
+  write(dir.path(), "data/articles/embeds.md", "---\ntitle: Embeds\ndate: 2024-06-04\ntags: []\n---\n<iframe src=\"https://www.youtube.com/embed/abc\"></iframe>\n\n<script src=\"https://cdn.example.net/widget.js\"></script>\n");
+  write(dir.path(), "data/projects/tool.md", "---\ntitle: Tool\ndate: 2023-01-01\ntags: []\n---\n<link rel=\"stylesheet\" href=\"//fonts.example.com/font.css\">\n\n```rust\nfn main() {}\n```\n");
+  let output = run(dir.path(), &["--csp-report"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+
+  let manifest: serde_json::Value = serde_json::from_str(&read(dir.path(), ".md_to_svelte/manifest.json")).unwrap();
+  let recorded: Vec<&str> = manifest["origins"].as_object().unwrap().values()
+    .flat_map(|origins| origins.as_array().unwrap().iter().map(|origin| origin.as_str().unwrap()))
+    .collect();
+  // Vocabulary URLs and plain links load nothing.
+  let not_loaded = ["https://schema.org", "https://example.org"];
+  let url = regex::Regex::new(r#"(https?:)?//([a-z0-9.-]+\.[a-z]+)"#).unwrap();
+  let mut seen = 0;
+  for path in files(&dir.path().join("src/routes")) {
+    let text = std::fs::read_to_string(&path).unwrap();
+    for captures in url.captures_iter(&text) {
+      let origin = format!("{}//{}", captures.get(1).map_or("https:", |scheme| scheme.as_str()), &captures[2]);
+      if not_loaded.contains(&origin.as_str()) {
+        continue;
+      }
+      assert!(recorded.contains(&origin.as_str()), "{} in {} is not in the manifest's origins {:?}", origin, path.display(), recorded);
+      seen += 1;
+    }
+  }
+  assert!(seen >= 4, "only {} external references found", seen);
+
+  let entry_origins = |slug: &str| {
+    let entry = manifest["entries"].as_array().unwrap().iter().find(|entry| entry["slug"] == slug).unwrap();
+    entry["origins"].clone()
+  };
+  assert_eq!(entry_origins("embeds")["frame-src"], serde_json::json!(["https://www.youtube.com"]));
+  assert_eq!(entry_origins("tool")["style-src"], serde_json::json!(["https://fonts.example.com"]));
+  assert!(String::from_utf8_lossy(&output.stdout).contains("frame-src 'self' https://www.youtube.com"));
+}
+
+#[test]
+fn data_file_dates_match_the_pages() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "data/articles/loose.md", "---\ntitle: Loose\ndate: 2024-1-5\ntags: []\n---\nText\n");
+  write(dir.path(), "data/articles/late.md", "---\ntitle: Late\ndate: 2024-02-10T23:30:00-05:00\ntags: []\n---\nText\n");
+  write(dir.path(), "data/articles/plain.md", "---\ntitle: Plain\ndate: 2023-12-31\ntags: []\n---\nText\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(stderr(&output).contains("[date-format] date '2024-1-5' is read as 2024-01-05"), "{}", stderr(&output));
+
+  let data = read(dir.path(), "src/routes/articles/articleData.ts");
+  let dates: Vec<(String, String)> = regex::Regex::new(r"slug: '([^']*)',[^}]*?\n    date: '([^']*)'").unwrap()
+    .captures_iter(&data)
+    .map(|caps| (caps[1].to_string(), caps[2].to_string()))
+    .collect();
+  assert_eq!(dates.len(), 3, "{}", data);
+  let shown = regex::Regex::new(r"export const date = '([^']*)';").unwrap();
+  let published = regex::Regex::new(r#"\\"datePublished\\":\\"([^\\]*)\\""#).unwrap();
+  for (slug, date) in dates {
+    let page = read(dir.path(), &format!("src/routes/articles/{}/+page.svelte", slug));
+    let day = chrono::NaiveDate::parse_from_str(&shown.captures(&page).unwrap()[1], "%B %d, %Y").unwrap();
+    assert_eq!(day.format("%Y-%m-%d").to_string(), date, "{}", slug);
+    assert!(published.captures(&page).unwrap()[1].starts_with(&date), "{}", slug);
+  }
+}
+
+#[test]
+fn wiki_embeds_are_copied_images() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "md_to_svelte.toml", "[content.articles]\nwiki_embeds = true\n");
+  write(dir.path(), "data/articles/post.md", "---\ntitle: Post\ndate: 2024-06-03\ntags: []\n---\n![[diagram.png]]\n\n![[chart.png|Sales]]\n");
+  write(dir.path(), "data/articles/images/diagram.png", "png");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(stderr(&output).contains("[missing-image] data/articles/post.md: images/chart.png is not in data/articles/images"), "{}", stderr(&output));
+  assert!(!stderr(&output).contains("diagram.png"), "{}", stderr(&output));
+  let page = read(dir.path(), "src/routes/articles/post/+page.svelte");
+  assert!(page.contains(r#"<img src=\"/images/articles/diagram.png\" alt=\"diagram\""#), "{}", page);
+  assert!(page.contains(r#"<img src=\"/images/articles/chart.png\" alt=\"Sales\""#), "{}", page);
+  assert_eq!(read(dir.path(), "static/images/articles/diagram.png"), "png");
+
+  write(dir.path(), "data/articles/images/chart.png", "png");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(!stderr(&output).contains("[missing-image]"), "{}", stderr(&output));
+  assert_eq!(read(dir.path(), "static/images/articles/chart.png"), "png");
+}
+
+#[test]
+fn archive_leaves_out_drafts_built_with_drafts() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "data/articles/post.md", "---\ntitle: Post\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  write(dir.path(), "data/articles/draft.md", "---\ntitle: Draft\ndate: 2024-06-04\ntags: []\ndraft: true\n---\nText\n");
+  write(dir.path(), "data/articles/hidden.md", "---\ntitle: Hidden\ndate: 2024-07-01\ntags: []\npublished: false\n---\nText\n");
+  let output = run(dir.path(), &["--drafts"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(dir.path().join("src/routes/articles/draft/+page.svelte").exists());
+  let archive = read(dir.path(), "src/routes/articles/archiveData.ts");
+  assert!(archive.contains("\"slug\":\"post\""), "{}", archive);
+  assert!(!archive.contains("draft") && !archive.contains("hidden") && !archive.contains("month: 7"), "{}", archive);
+}
+
+#[test]
+fn data_exports_are_named_by_the_content_type() {
+  let dir = tempfile::tempdir().unwrap();
+  let types = [
+    ("blog", "article", ""),
+    ("news", "news", ""),
+    ("categories", "category", "data_export = \"categoryList\"\n"),
+  ];
+  let mut config = String::new();
+  for (name, kind, export) in types {
+    config.push_str(&format!(
+      "[[content_types]]\nname = \"{0}\"\ninput_dir = \"data/{0}\"\noutput_dir = \"src/routes/{0}\"\nkind = \"{1}\"\n{2}\n",
+      name, kind, export
+    ));
+    write(dir.path(), &format!("data/{}/post.md", name), "---\ntitle: Post\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  }
+  write(dir.path(), "md_to_svelte.toml", &config);
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(read(dir.path(), "src/routes/blog/articleData.ts").contains("export const articles = ["));
+  assert!(read(dir.path(), "src/routes/news/newsData.ts").contains("export const news = ["));
+  assert!(read(dir.path(), "src/routes/categories/categoryData.ts").contains("export const categoryList = ["));
+
+  write(dir.path(), "md_to_svelte.toml", &config.replace("name = \"news\"", "name = \"news-items\""));
+  let output = run(dir.path(), &["--validate-config"]);
+  assert!(!output.status.success());
+  assert!(stderr(&output).contains("content_types: news-items: data_export 'news-items' must be a JavaScript identifier"), "{}", stderr(&output));
+}
+
+/// Runs git in `dir` with `args`, failing the test if it fails.
+fn git(dir: &Path, args: &[&str]) {
+  let output = Command::new("git")
+    .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+    .args(args)
+    .current_dir(dir)
+    .output()
+    .unwrap();
+  assert!(output.status.success(), "git {:?}: {}", args, stderr(&output));
+}
+
+#[test]
+fn changed_since_follows_a_dot_relative_input_dir() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "md_to_svelte.toml", "[[content_types]]\nname = \"blog\"\ninput_dir = \"./content/blog\"\noutput_dir = \"src/routes/blog\"\nkind = \"article\"\n");
+  write(dir.path(), ".gitignore", "src/\n.md_to_svelte/\n.md_to_svelte.lock\n.cache/\n");
+  write(dir.path(), "content/blog/edited.md", "---\ntitle: Edited\ndate: 2024-06-03\ntags: []\n---\nOld text\n");
+  write(dir.path(), "content/blog/removed.md", "---\ntitle: Removed\ndate: 2024-06-04\ntags: []\n---\nText\n");
+  git(dir.path(), &["init", "-q"]);
+  git(dir.path(), &["add", "."]);
+  git(dir.path(), &["commit", "-q", "-m", "Initial"]);
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+
+  write(dir.path(), "content/blog/edited.md", "---\ntitle: Edited\ndate: 2024-06-03\ntags: []\n---\nNew text\n");
+  write(dir.path(), "content/blog/added.md", "---\ntitle: Added\ndate: 2024-06-05\ntags: []\n---\nText\n");
+  std::fs::remove_file(dir.path().join("content/blog/removed.md")).unwrap();
+  let output = run(dir.path(), &["--changed-since", "HEAD"]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(read(dir.path(), "src/routes/blog/edited/+page.svelte").contains("New text"));
+  assert!(dir.path().join("src/routes/blog/added/+page.svelte").exists());
+  assert!(!dir.path().join("src/routes/blog/removed").exists());
+}
+
+#[test]
+fn page_template_comes_from_assets_dir() {
+  let dir = tempfile::tempdir().unwrap();
+  write(dir.path(), "md_to_svelte.toml", "assets_dir = \"assets\"\n");
+  std::fs::create_dir(dir.path().join("assets")).unwrap();
+  write(dir.path(), "data/articles/post.md", "---\ntitle: It's here\ndate: 2024-06-03\ntags: []\n---\nText\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  assert!(read(dir.path(), "src/routes/articles/post/+page.svelte").contains("MathJax"));
+
+  write(dir.path(), "assets/page.svelte", "{{header}}\n<script>\n  export const title = {{title}};\n{{content}}\n</script>\n<main>{{body}}</main>\n");
+  let output = run(dir.path(), &[]);
+  assert!(output.status.success(), "{}", stderr(&output));
+  let page = read(dir.path(), "src/routes/articles/post/+page.svelte");
+  assert!(page.starts_with("<!-- Generated by md_to_svelte. Do not edit. -->"), "{}", page);
+  assert!(page.contains("  export const title = 'It\\'s here';\n"), "{}", page);
+  assert!(page.contains("let content = \"<p>Text</p>\\n\";"), "{}", page);
+  assert!(page.contains("<main>") && !page.contains("MathJax") && !page.contains("{{"), "{}", page);
+}